@@ -126,5 +126,5 @@ fn run_app() {
 }
 
 fn main() {
-  objrs::autoreleasepool(run_app);
+  objrs::autoreleasepool(|_| run_app());
 }
@@ -4,8 +4,9 @@
 // licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
 
 use crate::cgbase::CGFloat;
+use objrs::TypeEncoding;
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
 #[repr(C)]
 pub struct CGPoint {
   pub x: CGFloat,
@@ -15,7 +16,7 @@ pub struct CGPoint {
 unsafe impl objrs::marker::Zeroed for CGPoint {}
 impl objrs::marker::Forgettable for CGPoint {}
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
 #[repr(C)]
 pub struct CGSize {
   pub width: CGFloat,
@@ -25,7 +26,7 @@ pub struct CGSize {
 unsafe impl objrs::marker::Zeroed for CGSize {}
 impl objrs::marker::Forgettable for CGSize {}
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
 #[repr(C)]
 pub struct CGVector {
   pub dx: CGFloat,
@@ -35,7 +36,7 @@ pub struct CGVector {
 unsafe impl objrs::marker::Zeroed for CGVector {}
 impl objrs::marker::Forgettable for CGVector {}
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
 #[repr(C)]
 pub struct CGRect {
   pub origin: CGPoint,
@@ -10,19 +10,25 @@ extern crate objrs;
 extern crate objrs_frameworks_foundation_macros;
 
 mod nsarray;
+mod nsdictionary;
 mod nserror;
+mod nsexception;
 mod nsmutable_string;
 mod nsnotification;
 mod nsobject;
 mod nsprocess_info;
+mod nsrange;
 mod nsstring;
 
 pub use crate::nsarray::*;
+pub use crate::nsdictionary::*;
 pub use crate::nserror::*;
+pub use crate::nsexception::*;
 pub use crate::nsmutable_string::*;
 pub use crate::nsnotification::*;
 pub use crate::nsobject::*;
 pub use crate::nsprocess_info::*;
+pub use crate::nsrange::*;
 pub use crate::nsstring::*;
 
 #[doc(hidden)]
@@ -0,0 +1,23 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+extern crate objrs;
+
+use crate::nsobject;
+use objrs::objrs;
+
+#[objrs(class, super = nsobject::NSObject)]
+#[link(name = "Foundation", kind = "framework")]
+pub struct NSDictionary<K: objrs::marker::Class + ?Sized, V: objrs::marker::Class + ?Sized>;
+
+#[objrs(impl)]
+#[link(name = "Foundation", kind = "framework")]
+impl<K: objrs::marker::Class + ?Sized, V: objrs::marker::Class + ?Sized> NSDictionary<K, V> {
+  #[objrs(selector = "count")]
+  pub fn count(&self) -> usize {}
+
+  #[objrs(selector = "objectForKey:")]
+  pub fn object_for_key<'a>(&'a self, key: &K) -> Option<&'a V> {}
+}
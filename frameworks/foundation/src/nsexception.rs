@@ -0,0 +1,97 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// See https://developer.apple.com/documentation/foundation/nsexception
+
+extern crate core;
+extern crate objrs;
+#[cfg(feature = "std")]
+extern crate std;
+
+use crate::nsarray::NSArray;
+use crate::nsdictionary::NSDictionary;
+use crate::nsobject::NSObject;
+use crate::nsstring::NSString;
+use objrs::objrs;
+
+#[objrs(class, super = NSObject)]
+#[link(name = "Foundation", kind = "framework")]
+pub struct NSException;
+
+#[objrs(impl)]
+#[link(name = "Foundation", kind = "framework")]
+impl NSException {
+  #[objrs(selector = "exceptionWithName:reason:userInfo:")]
+  pub fn exception_with_name_reason_user_info(
+    name: &NSString,
+    reason: Option<&NSString>,
+    user_info: Option<&NSDictionary<objrs::Id, objrs::Id>>,
+  ) -> objrs::Strong<NSException> {
+  }
+
+  #[objrs(selector = "name")]
+  pub fn name(&self) -> &NSString {}
+
+  #[objrs(selector = "reason")]
+  pub fn reason(&self) -> Option<&NSString> {}
+
+  #[objrs(selector = "userInfo")]
+  pub fn user_info(&self) -> Option<&NSDictionary<objrs::Id, objrs::Id>> {}
+
+  // The call stack at the time the exception was first raised (one frame per string).
+  #[objrs(selector = "callStackSymbols")]
+  pub fn call_stack_symbols(&self) -> &NSArray<NSString> {}
+
+  // Creates and throws an exception (like +[NSException raise:format:], without the formatting).
+  #[inline(never)]
+  pub fn raise(name: &NSString, reason: &NSString) -> ! {
+    let exception = Self::exception_with_name_reason_user_info(name, Some(reason), None);
+    objrs::throw(into_id(exception));
+  }
+}
+
+#[inline(always)]
+fn into_id(exception: objrs::Strong<NSException>) -> objrs::Strong<objrs::Id> {
+  return unsafe { core::mem::transmute(exception) };
+}
+
+// Like objrs::catch_exception, but only catches NSExceptions (like `@catch (NSException *e)` in
+// Objective-C). Other thrown objects are rethrown.
+#[inline]
+pub fn catch_ns_exception<F: FnOnce() -> R, R>(f: F) -> Result<R, objrs::Strong<NSException>> {
+  match objrs::catch_exception(f) {
+    Ok(ret) => return Ok(ret),
+    Err(exception) => match exception.downcast::<NSException>() {
+      Ok(exception) => return Err(exception),
+      Err(other) => objrs::throw(other),
+    },
+  }
+}
+
+// Formats the exception as "name: reason" (or just "name" if it doesn't have a reason).
+impl core::fmt::Display for NSException {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let name: &str = self.name().as_ref();
+    return match self.reason() {
+      Some(reason) => write!(formatter, "{}: {}", name, AsRef::<str>::as_ref(reason)),
+      None => formatter.write_str(name),
+    };
+  }
+}
+
+impl core::fmt::Debug for NSException {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let name: &str = self.name().as_ref();
+    let reason: Option<&str> = self.reason().map(|reason| reason.as_ref());
+    return formatter
+      .debug_struct("NSException")
+      .field("name", &name)
+      .field("reason", &reason)
+      .finish();
+  }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NSException {}
@@ -0,0 +1,33 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+use objrs::TypeEncoding;
+
+// NSRange is a typedef of `struct _NSRange`, so its encoding is `{_NSRange=QQ}`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, TypeEncoding)]
+#[objrs(encoding_name = "_NSRange")]
+#[repr(C)]
+pub struct NSRange {
+  pub location: usize,
+  pub length: usize,
+}
+
+unsafe impl objrs::marker::Zeroed for NSRange {}
+impl objrs::marker::Forgettable for NSRange {}
+
+#[allow(non_snake_case)]
+#[inline(always)]
+pub fn NSMakeRange(location: usize, length: usize) -> NSRange {
+  return NSRange {
+    location: location,
+    length: length,
+  };
+}
+
+#[allow(non_snake_case)]
+#[inline(always)]
+pub fn NSMaxRange(range: NSRange) -> usize {
+  return range.location + range.length;
+}
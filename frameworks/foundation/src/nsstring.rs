@@ -9,6 +9,7 @@ extern crate core;
 extern crate objrs;
 
 use crate::nsobject;
+use crate::nsrange::NSRange;
 use objrs::objrs;
 
 // TODO: LLVM's Early CSE pass ("early-cse": http://llvm.org/doxygen/EarlyCSE_8cpp_source.html) is breaking objrs. It optimizes out the static selref variable. Even marking the variable as #[used] doesn't fix it (that causes the variable to not be optimized out, but the LLVM IR still doesn't use it, as it prefers loading the address of the method name instead). Run rustc with ` -C opt-level=0 -C passes=early-cse` to see the impact (use `-C llvm-args=-print-after-all` to verify that it's indeed the Early CSE pass that's causing this).
@@ -144,6 +145,9 @@ impl NSString {
   #[objrs(selector = "length")]
   pub fn length(&self) -> usize {}
 
+  #[objrs(selector = "rangeOfString:")]
+  pub fn range_of_string(&self, string: &NSString) -> NSRange {}
+
   // Raises an Objective-C exception if out of bounds.
   #[objrs(selector = "characterAtIndex:")]
   pub fn character_at_index(&self, index: usize) -> u16 {}
@@ -3,7 +3,7 @@
 // may not be copied, modified, or distributed except according to those terms. For copies of these
 // licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
 
-#![feature(arbitrary_self_types, extern_types)]
+#![feature(arbitrary_self_types, extern_types, linkage)]
 #![no_std]
 
 extern crate libc;
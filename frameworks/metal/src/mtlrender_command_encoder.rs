@@ -5,7 +5,7 @@
 
 use crate::mtlbuffer::MTLBuffer;
 use crate::mtlrender_pipeline::MTLRenderPipelineState;
-use objrs::{objrs, Id};
+use objrs::{objrs, Id, TypeEncoding};
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 #[repr(transparent)]
@@ -28,7 +28,9 @@ impl MTLPrimitiveType {
 unsafe impl objrs::marker::Zeroed for MTLPrimitiveType {}
 impl objrs::marker::Forgettable for MTLPrimitiveType {}
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+// MTLViewport is a typedef of an anonymous struct, which clang encodes as `{?=dddddd}`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
+#[objrs(encoding_name = "?")]
 #[repr(C)]
 #[allow(non_snake_case)]
 pub struct MTLViewport {
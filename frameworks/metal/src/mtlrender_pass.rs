@@ -4,9 +4,12 @@
 // licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
 
 use objrs::objrs;
+use objrs::TypeEncoding;
 use objrs_frameworks_foundation::NSObject;
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+// MTLClearColor is a typedef of an anonymous struct, which clang encodes as `{?=dddd}`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, TypeEncoding)]
+#[objrs(encoding_name = "?")]
 #[repr(C)]
 pub struct MTLClearColor {
   pub red: f64,
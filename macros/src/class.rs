@@ -9,8 +9,11 @@ extern crate proc_macro2;
 extern crate syn;
 
 use crate::gen::gen_class::pub_item_struct_and_deref_impls;
+use crate::gen::gen_property::{backing_ivar, property_list, property_t, synthesize_accessors};
+use crate::gen::ivar_layout::ivar_layout;
 use crate::parse::class_attr::Class;
-use crate::util::{link_attribute, priv_ident_at};
+use crate::parse::property_attr::PropertyImpl;
+use crate::util::{link_attribute, priv_ident_at, require_apple_runtime, AppleOnly};
 use proc_macro::Diagnostic;
 use proc_macro2::{Literal, Span, TokenStream, TokenTree};
 use quote::{quote, quote_spanned, ToTokens};
@@ -35,8 +38,109 @@ pub fn ivar_list_ident(class_name: &str) -> Ident {
   return Ident::new(&["__objrs_ivars_", class_name].concat(), Span::call_site());
 }
 
+pub fn ivar_layout_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_ivar_layout_", class_name].concat(), Span::call_site());
+}
+
+pub fn weak_ivar_layout_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_weak_ivar_layout_", class_name].concat(), Span::call_site());
+}
+
+pub fn property_list_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_props_", class_name].concat(), Span::call_site());
+}
+
+pub fn class_property_list_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_class_props_", class_name].concat(), Span::call_site());
+}
+
+pub fn protocol_list_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_protocols_", class_name].concat(), Span::call_site());
+}
+
+pub fn selectors_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_selectors_", class_name].concat(), Span::call_site());
+}
+
+pub fn synthesized_selectors_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_synthesized_", class_name].concat(), Span::call_site());
+}
+
+pub fn accessors_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_accessors_", class_name].concat(), Span::call_site());
+}
+
+pub fn accessor_count_ident(class_name: &str) -> Ident {
+  return Ident::new(&["__objrs_accessor_count_", class_name].concat(), Span::call_site());
+}
+
 pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
+  if !class.force_extern {
+    require_apple_runtime(class.class_name.span(), AppleOnly::Class)?;
+  }
+
   let pub_item = pub_item_struct_and_deref_impls(&class);
+
+  // Properties need to be generated before the class's item and ivars are consumed below.
+  let property_lists;
+  let accessors;
+  let has_properties;
+  let has_class_properties;
+  if !class.force_extern {
+    let class_name_str = &class.class_name.value();
+    let mut instance_entries = TokenStream::new();
+    let mut class_entries = TokenStream::new();
+    let mut instance_count = 0usize;
+    let mut class_count = 0usize;
+    for property in class.properties.iter() {
+      let ivar_name = backing_ivar(&class, property).map(|(_, ivar)| ivar.name.value());
+      let name = property.name.to_string();
+      if property.is_class_property() {
+        let symbol_id = [class_name_str, "::+", &name].concat();
+        let entry = property_t(property, PropertyImpl::Dynamic, &symbol_id, &class.objrs);
+        class_entries.extend(entry);
+        class_count += 1;
+      } else {
+        let symbol_id = [class_name_str, "::", &name].concat();
+        let implementation = match ivar_name {
+          Some(ref ivar_name) => PropertyImpl::Synthesized(ivar_name),
+          None => PropertyImpl::Dynamic,
+        };
+        instance_entries.extend(property_t(property, implementation, &symbol_id, &class.objrs));
+        instance_count += 1;
+      }
+    }
+
+    let instance_list = property_list(
+      &property_list_ident(class_name_str),
+      &["\x01l_OBJC_$_PROP_LIST_", class_name_str].concat(),
+      false,
+      instance_count,
+      instance_entries,
+      &class.objrs,
+    );
+    let class_list = property_list(
+      &class_property_list_ident(class_name_str),
+      &["\x01l_OBJC_$_CLASS_PROP_LIST_", class_name_str].concat(),
+      false,
+      class_count,
+      class_entries,
+      &class.objrs,
+    );
+    property_lists = Some(quote! {
+      #instance_list
+      #class_list
+    });
+    accessors = Some(synthesize_accessors(&class)?);
+    has_properties = instance_count > 0;
+    has_class_properties = class_count > 0;
+  } else {
+    property_lists = None;
+    accessors = None;
+    has_properties = false;
+    has_class_properties = false;
+  }
+
   let mut item = class.item;
   let objrs_root = class.objrs;
 
@@ -53,6 +157,9 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
   let class_name = class.class_name;
   let class_name_str = &class_name.value();
   let class_name_cstr = LitStr::new(&[&class_name_str, "\0"].concat(), class_name.span());
+  let extended_encoding = ["@\"", class_name_str, "\""].concat();
+  let extended_encoding_len = extended_encoding.len();
+  let extended_encoding = LitByteStr::new(extended_encoding.as_bytes(), class_name.span());
 
   let super_class = class.super_class;
   let super_class_name = class.super_class_name;
@@ -104,6 +211,23 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
     }
   });
 
+  // The class's protocol list is filled in by the #[objrs(impl)] blocks that implement protocols
+  // for the class (see gen/protocol_list.rs).
+  let protocol_list;
+  if class.force_extern {
+    protocol_list = quote!(#objrs_root::__objrs::core::option::Option::None);
+  } else {
+    let protocol_list_ident = protocol_list_ident(class_name_str);
+    let protocol_list_export_name = ["\x01l_OBJC_CLASS_PROTOCOLS_$_", class_name_str].concat();
+    statics.extend(quote! {
+      #[doc(hidden)]
+      #[link_section = "__DATA,__data"]
+      #[export_name = #protocol_list_export_name]
+      static #protocol_list_ident: #objrs_root::__objrs::ClassProtocolList = #objrs_root::__objrs::ClassProtocolList::new();
+    });
+    protocol_list = quote!(#objrs_root::__objrs::core::option::Option::Some(&#protocol_list_ident));
+  }
+
   // TODO: Add a unit test that checks ivar names and types (same as the unit test to check selector
   // argument/return types).
 
@@ -152,6 +276,7 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
   let mut field_count: usize = 0;
   let mut field_tokens = quote!();
   let mut force_cxx_construct: bool = false;
+  let mut layout_fields = vec![];
   for (i, (field, ivar)) in item.fields.iter_mut().zip(class.ivars).enumerate() {
     field_count += 1;
     let field_ident: TokenTree =
@@ -264,30 +389,7 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
       unsafe { #objrs_root::__objrs::TransmuteHack::<_, *const #native_ty::u8> { from: &IVAR_TYPE }.to }
     }};
 
-    // TODO: the following is incorrect and incomplete. It builds the weak ivar layout counting
-    // the number of fields. Instead, the layout should be based on the number of bytes and the
-    // offset of each field. That really complicates things. I think we need const fn in order to
-    // do this properly.
-    // quote!{{
-    //   const WEAK_IVAR_LAYOUT: [u8; 1] = [0x00];  // TODO: replace with a proper value.
-    //   const OLD_LEN: usize = 1;  // TODO: replace with real value.
-
-    //   const IS_WEAK: bool = <#field_ty as #objrs_root::__objrs::IsWeak>::VALUE;
-    //   const LAST_BYTE: u8 = WEAK_IVAR_LAYOUT[OLD_LEN - 1];  // TODO: replace with real value.
-    //   const WEAKS: u8 = LAST_BYTE & 0x0f;
-    //   const SKIPS: u8 = LAST_BYTE >> 4;
-    //   const NEW_WEAKS: u8 = WEAKS + IS_WEAK as u8;
-    //   const NEW_SKIPS: u8 = SKIPS + !IS_WEAK as u8;
-    //   const PUSH: bool = NEW_WEAKS | NEW_SKIPS > 0x0f || (!IS_WEAK && WEAKS > 0);
-    //   const MASK: u8 = 0xff * PUSH as u8;
-    //   const NEW_LAST_BYTE: u8 = ((IS_WEAK as u8) & MASK) |
-    //                             ((((NEW_SKIPS & 0x0f) << 4) | (NEW_WEAKS & 0x0f)) & !MASK);
-    //   const TRUNCATED_LEN: usize = OLD_LEN - !PUSH as usize;
-
-    //   unsafe { #objrs_root::__objrs::TransmuteHack::<_, [u8; TRUNCATED_LEN + 1]> { from: #objrs_root::__objrs::Packed2(#objrs_root::__objrs::TransmuteHack::<_, [u8; TRUNCATED_LEN]> { from: WEAK_IVAR_LAYOUT }.to, NEW_LAST_BYTE) }.to }
-    // }};
-    // TODO: convert WEAK_IVAR_LAYOUT to nil if no ivars are weak.
-    // TODO: truncate the trailing SKIPS in the array.
+    layout_fields.push((field_ident, field_ty.clone()));
 
     field_tokens.extend(quote! {
       #objrs_root::__objrs::runtime::ivar_t {
@@ -300,6 +402,32 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
     });
   }
 
+  let ivar_layouts;
+  if !force_extern {
+    let strong_layout = ivar_layout(
+      &ivar_layout_ident(class_name_str),
+      quote!(#objrs_root::__objrs::IsStrong),
+      pub_ident,
+      &original_item,
+      &layout_fields,
+      &objrs_root,
+    );
+    let weak_layout = ivar_layout(
+      &weak_ivar_layout_ident(class_name_str),
+      quote!(#objrs_root::__objrs::IsWeak),
+      pub_ident,
+      &original_item,
+      &layout_fields,
+      &objrs_root,
+    );
+    ivar_layouts = Some(quote! {
+      #strong_layout
+      #weak_layout
+    });
+  } else {
+    ivar_layouts = None;
+  }
+
   if !force_extern {
     let ivar_list_ident = ivar_list_ident(class_name_str);
 
@@ -425,6 +553,12 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
     // requested fields. And
     #ivar_list
 
+    #ivar_layouts
+
+    #property_lists
+
+    #accessors
+
     #item_fields
 
     impl #generics #objrs_root::__objrs::Fields for #pub_ident <#generic_idents> #where_clause {
@@ -449,6 +583,8 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
       const CLASS_NAME: &'static #native_ty::str = #class_name;
       const CLASS_NAME_CSTR: &'static #native_ty::str = #class_name_cstr;
       const HAS_IVARS: #native_ty::bool = #has_ivars;
+      const HAS_PROPERTIES: #native_ty::bool = #has_properties;
+      const HAS_CLASS_PROPERTIES: #native_ty::bool = #has_class_properties;
       const IS_ROOT_CLASS: #native_ty::bool = #is_root_class;
       const REQUIRES_CXX_CONSTRUCT: #native_ty::bool = {
         #requires_cxx_construct false
@@ -456,6 +592,7 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
       const REQUIRES_CXX_DESTRUCT: #native_ty::bool = {
         #requires_cxx_destruct false
       };
+      const IS_EXTERN: #native_ty::bool = #force_extern;
       const INSTANCE_START: #native_ty::usize = {
         const ALIGN: #native_ty::usize = {
           #original_item
@@ -492,10 +629,30 @@ pub fn transform_class(class: Class) -> Result<TokenStream, Diagnostic> {
           #cxx_destruct
         }
       }
+
+      #[inline(always)]
+      fn protocol_list() -> #objrs_root::__objrs::core::option::Option<&'static #objrs_root::__objrs::ClassProtocolList> {
+        return #protocol_list;
+      }
     }
 
     unsafe impl #generics #objrs_root::marker::Class for #pub_ident <#generic_idents> #where_clause {}
 
+    // Object pointers are encoded as "@".
+    unsafe impl #generics #objrs_root::PointeeTypeEncoding for #pub_ident <#generic_idents> #where_clause {
+      type Type = [#native_ty::u8; 1];
+      const BYTES: Self::Type = [b'@'];
+
+      type ConstType = [#native_ty::u8; 1];
+      const CONST_BYTES: Self::ConstType = [b'@'];
+    }
+
+    // Extended type encodings (used by protocols) include the class's name.
+    unsafe impl #generics #objrs_root::__objrs::ExtendedPointeeTypeEncoding for #pub_ident <#generic_idents> #where_clause {
+      type ExtendedType = [#native_ty::u8; #extended_encoding_len];
+      const EXTENDED_BYTES: Self::ExtendedType = *#extended_encoding;
+    }
+
     #impls
 
     #statics
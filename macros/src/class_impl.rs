@@ -8,22 +8,28 @@ extern crate proc_macro;
 extern crate proc_macro2;
 
 use crate::class::{
-  ivar_list_ident, root_metaclass_ident, super_class_ident, super_metaclass_ident,
+  accessor_count_ident, accessors_ident, class_property_list_ident, ivar_layout_ident,
+  ivar_list_ident, property_list_ident, protocol_list_ident, root_metaclass_ident, selectors_ident,
+  super_class_ident, super_metaclass_ident, weak_ivar_layout_ident,
 };
 use crate::gen::class_ref::{
   gen_class_ref_value, gen_super_class_ref_value, gen_super_meta_ref_value,
 };
+use crate::gen::gen_property::implemented_selector_ident;
+use crate::gen::gen_selector::gen_method_type_encoding;
 use crate::gen::ivar::transform_ivars;
+use crate::gen::protocol_list::{category_protocols, class_protocols};
 use crate::parse::impl_attr::ImplAttr;
 use crate::selector::{parse_selector_method, ObjrsMethod};
-use crate::util::{is_instance_method, link_attribute, priv_ident, RandomIdentifier};
+use crate::util::{
+  is_instance_method, link_attribute, priv_ident, require_apple_runtime, AppleOnly, RandomIdentifier,
+};
 use proc_macro::Diagnostic;
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use syn::spanned::Spanned;
 use syn::{
-  parse2, Attribute, FnArg, Ident, ImplItem, ImplItemMethod, ItemImpl, LitByteStr, LitStr,
-  ReturnType, Type,
+  parse2, Attribute, Ident, ImplItem, ImplItemMethod, ItemImpl, LitByteStr, LitStr, Type,
 };
 
 struct ClassImpl {
@@ -71,100 +77,35 @@ struct ClassImpl {
 //   }
 // }
 
-fn method_type(
+pub fn method_type(
   method: &ImplItemMethod,
   class_name: &LitStr,
   is_instance_method: bool,
   objrs_root: &Ident,
 ) -> TokenStream {
-  assert!(method.sig.inputs.len() >= 2, "BUG: selector methods should always have >= 2 arguments");
-
-  let native_ty = quote!(#objrs_root::__objrs);
-
-  let mut tokens = quote! {
-    const SIZE_OF_USIZE: #native_ty::usize = #objrs_root::__objrs::core::mem::size_of::<#native_ty::usize>();
-  };
-
-  let arg0;
-  if is_instance_method {
-    arg0 = b'@';
-  } else {
-    arg0 = b'#';
-  }
-
-  let last_input_index = method.sig.inputs.len() - 1;
-
-  // TDOO: this is an ugly hack. We need to NUL terminate this some how, but this isn't elegant at all.
-  let mut prev_args = priv_ident("ARGS_1");
-  let mut prev_args_len = priv_ident("ARGS_1_LEN");
-  if last_input_index == 1 {
-    tokens.extend(quote! {
-      const #prev_args_len: #native_ty::usize = 5;
-      const #prev_args: [#native_ty::u8; #prev_args_len] = [#arg0, b'0', b':', b'0' + SIZE_OF_USIZE as #native_ty::u8, 0];
-    });
-  } else {
-    tokens.extend(quote! {
-      const #prev_args_len: #native_ty::usize = 4;
-      const #prev_args: [#native_ty::u8; #prev_args_len] = [#arg0, b'0', b':', b'0' + SIZE_OF_USIZE as #native_ty::u8];
-    });
-  }
+  return method_type_static(method, class_name, is_instance_method, false, objrs_root);
+}
 
-  let mut prev_frame_offset = priv_ident("FrameOffset2");
-  let mut total_frame_size = quote!(SIZE_OF_USIZE * 2);
-  for (i, input) in method.sig.inputs.iter().enumerate().skip(2) {
-    let value;
-    if i == 2 {
-      value = quote!(SIZE_OF_USIZE * 2);
-    } else {
-      let previous_type = match method.sig.inputs[i - 1] {
-        FnArg::Typed(ref pat_ty) => &pat_ty.ty,
-        _ => panic!("BUG: unexpected argument type"),
-      };
-      value = quote!(#prev_frame_offset::VALUE + #objrs_root::__objrs::core::mem::size_of::<#previous_type>());
-    }
-    let input_type = match input {
-      FnArg::Typed(ref pat_ty) => &pat_ty.ty,
-      _ => panic!("BUG: unexpected argument type"),
-    };
-    // let encoded = type_encoding(input_type);
-    // TODO: +1 to nul terminate.
-    let add_nul_terminator = (i == last_input_index) as usize; // +1 to NUL terminate.
-    let encoded_len = quote!(#objrs_root::__objrs::core::mem::size_of::<<#input_type as #objrs_root::__objrs::TypeEncodingHack>::Type>() + #add_nul_terminator);
-    let encoded = quote!(<#input_type as #objrs_root::__objrs::TypeEncodingHack>::BYTES);
-
-    let args = priv_ident(&format!("ARGS_{}", i));
-    let args_len = priv_ident(&[args.to_string().as_ref(), "_LEN"].concat());
-
-    let frame_offset = priv_ident(&format!("FrameOffset{}", i));
-    tokens.extend(quote!{
-      struct #frame_offset;
-      impl #objrs_root::__objrs::ToAsciiHack for #frame_offset { const VALUE: #native_ty::usize = #value; }
-      const #args_len: #native_ty::usize = #prev_args_len + #encoded_len + <#frame_offset as #objrs_root::__objrs::ToAsciiHack>::LEN;
-      const #args: [#native_ty::u8; #args_len] = unsafe { #objrs_root::__objrs::TransmuteHack { from: #objrs_root::__objrs::Packed3(#prev_args, #encoded, <#frame_offset as #objrs_root::__objrs::ToAsciiHack>::STR) }.to };
-    });
-    prev_args = args;
-    prev_args_len = args_len;
-    prev_frame_offset = frame_offset;
-
-    if i == last_input_index {
-      total_frame_size = quote! {
-        #prev_frame_offset::VALUE + #objrs_root::__objrs::core::mem::size_of::<#input_type>()
-      };
-    }
-  }
+// Like method_type, but uses the extended type encoding (which protocols use for their extended
+// method types).
+pub fn extended_method_type(
+  method: &ImplItemMethod,
+  class_name: &LitStr,
+  is_instance_method: bool,
+  objrs_root: &Ident,
+) -> TokenStream {
+  return method_type_static(method, class_name, is_instance_method, true, objrs_root);
+}
 
-  let encoded;
-  let encoded_len;
-  match method.sig.output {
-    ReturnType::Default => {
-      encoded = quote!(b'v');
-      encoded_len = quote!(1usize);
-    }
-    ReturnType::Type(_, ref ty) => {
-      encoded = quote!(<#ty as #objrs_root::__objrs::TypeEncodingHack>::BYTES);
-      encoded_len = quote!(#objrs_root::__objrs::core::mem::size_of::<<#ty as #objrs_root::__objrs::TypeEncodingHack>::Type>());
-    }
-  };
+fn method_type_static(
+  method: &ImplItemMethod,
+  class_name: &LitStr,
+  is_instance_method: bool,
+  extended: bool,
+  objrs_root: &Ident,
+) -> TokenStream {
+  let method_type_encoding =
+    gen_method_type_encoding(&method.sig, is_instance_method, extended, objrs_root);
 
   let type_export_name = [
     "\x01L_OBJC_METH_VAR_TYPE_.__objrs_meth.",
@@ -174,22 +115,17 @@ fn method_type(
   ]
   .concat();
 
-  tokens.extend(quote!{
-    struct TotalFrameSize;
-    impl #objrs_root::__objrs::ToAsciiHack for TotalFrameSize { const VALUE: #native_ty::usize = #total_frame_size; }
-    const RET_LEN: #native_ty::usize = #encoded_len + <TotalFrameSize as #objrs_root::__objrs::ToAsciiHack>::LEN;
-    const RET: [#native_ty::u8; RET_LEN] = unsafe { #objrs_root::__objrs::TransmuteHack { from: #objrs_root::__objrs::Packed2(#encoded, <TotalFrameSize as #objrs_root::__objrs::ToAsciiHack>::STR) }.to };
+  return quote!({
+    #method_type_encoding
 
     #[link_section = "__TEXT,__objc_methtype,cstring_literals"]
     #[export_name = #type_export_name]
-    static METH_VAR_TYPE: [#native_ty::u8; RET_LEN + #prev_args_len] = unsafe { #objrs_root::__objrs::TransmuteHack { from: #objrs_root::__objrs::Packed2(RET, #prev_args) }.to };
+    static METH_VAR_TYPE: [#objrs_root::__objrs::u8; METHOD_TYPE_LEN] = METHOD_TYPE;
     &METH_VAR_TYPE
   });
-
-  return quote!({#tokens});
 }
 
-fn meth_var_name(
+pub fn meth_var_name(
   method_name: &[u8],
   class_name_str: &str,
   method_ident: &str,
@@ -276,7 +212,11 @@ fn method_list(
     });
   }
 
-  if method_tokens.is_empty() {
+  // The class's own instance method list also holds .cxx_construct, .cxx_destruct, and the
+  // synthesized property accessors, so it can't be omitted just because the impl block doesn't
+  // define any instance methods.
+  let is_class_instance_list = instance_methods && category.is_none();
+  if method_tokens.is_empty() && !is_class_instance_list {
     return Ok(quote!(0 as *mut #objrs_root::__objrs::runtime::method_list_t));
   }
 
@@ -285,7 +225,9 @@ fn method_list(
   let total_count;
   let cxx_destruct;
   let cxx_construct;
-  if instance_methods && category.is_none() {
+  let accessors_ty;
+  let accessors;
+  if is_class_instance_list {
     let cxx_destruct_type_export_name =
       ["\x01L_OBJC_METH_VAR_TYPE_.__objrs_meth.", class_name_str, "::.cxx_destruct"].concat();
 
@@ -301,7 +243,11 @@ fn method_list(
       quote!(<#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::REQUIRES_CXX_DESTRUCT);
     requires_cxx_construct =
       quote!(<#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::REQUIRES_CXX_CONSTRUCT);
-    total_count = quote!(#count + REQUIRES_CXX_DESTRUCT as #native_ty::usize + REQUIRES_CXX_CONSTRUCT as #native_ty::usize);
+    let accessors_ident = accessors_ident(class_name_str);
+    let accessor_count_ident = accessor_count_ident(class_name_str);
+    total_count = quote!(#count + #accessor_count_ident + REQUIRES_CXX_DESTRUCT as #native_ty::usize + REQUIRES_CXX_CONSTRUCT as #native_ty::usize);
+    accessors_ty = quote!(#accessors_ident);
+    accessors = quote!(#accessors_ident);
     cxx_destruct = quote! {
       unsafe { #objrs_root::__objrs::TransmuteHack {
         from: #objrs_root::__objrs::runtime::method_t {
@@ -340,6 +286,8 @@ fn method_list(
     total_count = quote!(#count);
     cxx_destruct = quote!([]);
     cxx_construct = quote!([]);
+    accessors_ty = quote!([#objrs_root::__objrs::runtime::method_t; 0]);
+    accessors = quote!([]);
   }
 
   let list_ident = priv_ident(class_or_instance);
@@ -357,6 +305,7 @@ fn method_list(
       entsize_and_flags: #native_ty::u32,
       count: #native_ty::u32,
       methods: [#objrs_root::__objrs::runtime::method_t; #count],
+      accessors: #accessors_ty,
       cxx_destruct: [#objrs_root::__objrs::runtime::method_t; REQUIRES_CXX_DESTRUCT as #native_ty::usize],
       cxx_construct: [#objrs_root::__objrs::runtime::method_t; REQUIRES_CXX_CONSTRUCT as #native_ty::usize],
     }
@@ -367,6 +316,7 @@ fn method_list(
       entsize_and_flags: #objrs_root::__objrs::core::mem::size_of::<#objrs_root::__objrs::runtime::method_t>() as #native_ty::u32,
       count: TOTAL_COUNT as #native_ty::u32,
       methods: [ #method_tokens ],
+      accessors: #accessors,
       cxx_destruct: #cxx_destruct,
       cxx_construct: #cxx_construct,
     });
@@ -387,6 +337,7 @@ fn custom_class(
   if force_extern || class_impl.item.trait_.is_some() {
     return Ok(TokenStream::new());
   }
+  require_apple_runtime(class_impl.item.self_ty.span(), AppleOnly::Class)?;
 
   let instance_methods = method_list(class_impl, None, true, objrs_root)?;
   let class_methods = method_list(class_impl, None, false, objrs_root)?;
@@ -408,6 +359,11 @@ fn custom_class(
   let super_metaclass_ident = super_metaclass_ident(&class_str);
   let super_class_ident = super_class_ident(&class_str);
   let ivar_list_ident = ivar_list_ident(&class_str);
+  let ivar_layout_ident = ivar_layout_ident(&class_str);
+  let weak_ivar_layout_ident = weak_ivar_layout_ident(&class_str);
+  let property_list_ident = property_list_ident(&class_str);
+  let class_property_list_ident = class_property_list_ident(&class_str);
+  let protocol_list_ident = protocol_list_ident(&class_str);
   let native_ty = quote!(#objrs_root::__objrs);
 
   let class = quote! {{
@@ -434,10 +390,10 @@ fn custom_class(
         ivar_layout: #objrs_root::__objrs::core::ptr::null(),
         name: &CLASS_NAME as *const _ as *const _,
         base_method_list: #class_methods,
-        base_protocols: #objrs_root::__objrs::core::ptr::null_mut(),
+        base_protocols: &#protocol_list_ident as *const _ as *mut _,
         ivars: #objrs_root::__objrs::core::ptr::null(),
         weak_ivar_layout: #objrs_root::__objrs::core::ptr::null(),
-        base_properties: #objrs_root::__objrs::core::ptr::null_mut(),
+        base_properties: unsafe { #objrs_root::__objrs::TransmuteHack { from: [&#class_property_list_ident as *const _, 0 as *const _][!<#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::HAS_CLASS_PROPERTIES as #native_ty::usize] }.to },
     });
 
     #[link_section = "__DATA,__objc_data"]
@@ -461,27 +417,15 @@ fn custom_class(
       instance_size: <#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::INSTANCE_SIZE as #native_ty::u32,
       #[cfg(target_pointer_width = "64")]
       reserved: 0,
-      ivar_layout: #objrs_root::__objrs::core::ptr::null(),
+      ivar_layout: #ivar_layout_ident,
       name: &CLASS_NAME as *const _ as *const _,
       base_method_list: #instance_methods,
-      base_protocols: #objrs_root::__objrs::core::ptr::null_mut(),
+      base_protocols: &#protocol_list_ident as *const _ as *mut _,
       ivars: unsafe { #objrs_root::__objrs::TransmuteHack { from: [&#ivar_list_ident as *const _, 0 as *const _][!<#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::HAS_IVARS as #native_ty::usize] }.to },
-      // TODO: Add weak ivar layout. I'm currently ignoring it because:
-      //   1. Doing it properly requires (compile-time) reflection. For example, if the ivar is a
-      //      struct that contains a weak pointer, we need to know that (and the offset of the weak
-      //      pointer).
-      //   2. Objective-C++ (using clang-902.0.39.2) omits weak ivars (including weak-containing
-      //      struct ivars mentioned above) if they're misaligned (because the layout encoding
-      //      assumes aligned ivars). This is easy to verify using a packed struct. It might be a
-      //      bug in clang, but if clang omits the weak ivar layout in these situations, I assume
-      //      it's safe for us to do it too (though admittedly I haven't fully tested the impact of
-      //      using packed structs (that contain a weak pointer) in Objective-C++; I've just looked
-      //      at the assembly).
-      // That said, we could do better by using the marker::Weak trait to populate the weak ivar
-      // layout for weak ivars (but not weak-containing struct ivars). Honestly, weak-containing
-      // struct ivars are probably rare, so this would be sufficient for the common case.
-      weak_ivar_layout: #objrs_root::__objrs::core::ptr::null(),
-      base_properties: #objrs_root::__objrs::core::ptr::null_mut(),
+      // The layouts only describe ivars that are themselves strong or weak pointers. Structs that
+      // contain strong or weak pointers would require (compile-time) reflection to describe.
+      weak_ivar_layout: #weak_ivar_layout_ident,
+      base_properties: unsafe { #objrs_root::__objrs::TransmuteHack { from: [&#property_list_ident as *const _, 0 as *const _][!<#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::HAS_PROPERTIES as #native_ty::usize] }.to },
     });
 
     #[link_section = "__DATA,__objc_data"]
@@ -503,7 +447,26 @@ fn custom_class(
   let label_class_link_name = ["\x01L_OBJC_LABEL_CLASS_$", &class_str].concat();
   let ident = priv_ident("LABEL_CLASS");
 
+  // Tell `#[objrs(class)]`'s property synthesis which selectors the class implements (see
+  // gen_property::synthesize_accessors).
+  let selectors_ident = selectors_ident(&class_str);
+  let mut implemented_selectors = TokenStream::new();
+  for method in class_impl.instance_methods.iter() {
+    if method.msg_recv.is_some() {
+      let sel_ident = implemented_selector_ident(&method.selector.sel.value());
+      implemented_selectors.extend(quote! {
+        const #sel_ident: #native_ty::bool = true;
+      });
+    }
+  }
+
   let tokens = quote! {
+    #[allow(dead_code)]
+    #[allow(non_upper_case_globals)]
+    impl #selectors_ident {
+      #implemented_selectors
+    }
+
     #[link_section = "__DATA,__objc_classlist,regular,no_dead_strip"]
     #[export_name = #label_class_link_name]
     #[used]
@@ -523,6 +486,7 @@ fn custom_category(
   if force_extern || class_impl.item.trait_.is_none() {
     return Ok(TokenStream::new());
   }
+  require_apple_runtime(class_impl.item.self_ty.span(), AppleOnly::Category)?;
 
   let native_ty = quote!(#objrs_root::__objrs);
   let mut category_name_str =
@@ -536,44 +500,13 @@ fn custom_category(
   let class_methods = method_list(class_impl, trait_name, false, objrs_root)?;
 
   let trait_name = trait_name.expect("BUG: missing trait name");
-  let protocol_link_name = ["\x01l_OBJC_PROTOCOL_$_", trait_name].concat();
 
   let class_name_str = &class_impl.class_name.value();
 
-  let protocol_list_export_name =
-    ["\x01l_OBJC_CATEGORY_PROTOCOLS_$_", class_name_str, "_$_", category_name_str].concat();
-  let list_ident = priv_ident("PROTOCOL_LIST");
-  let protocols = quote! {{
-    #[repr(C)]
-    struct ProtocolList {
-      count: #native_ty::usize,
-      protocols: [*const #objrs_root::__objrs::runtime::protocol_t; 1],
-      null: #native_ty::usize,
-    }
-
-    #[link_section = "__DATA,__objc_const"]
-    #[export_name = #protocol_list_export_name]
-    static #list_ident: #objrs_root::__objrs::SyncHack<ProtocolList> = #objrs_root::__objrs::SyncHack(ProtocolList {
-      count: 1,
-      protocols: [
-        {
-          // TODO: this hack is undefined behavior. Frameworks don't export the protocol (they're like C++ header-only templates), so we have to create it ourselves. That's a decent amount of work, though, and requires that the Rust code exactly match the Objective-C code. This stupid hack just creates a symbol to satisfy the linker, and we cross our fingers and pray the linker doesn't choose our definition as the "One True Definition" when de-duping the weak symbols.
-          // TODO: this should be weak. Objective-C marks protocols as ".weak_definition" and ".private_extern" (and ".globl", but ".private_extern" trumps ".globl").
-          #[link_section = "__DATA,__data"]
-          #[export_name = #protocol_link_name]
-          static STUPID_HACK: [#native_ty::usize; 0] = [];
-          extern "C" {
-            #[link_name = #protocol_link_name]
-            static PROTOCOL: #objrs_root::__objrs::runtime::protocol_t;
-          }
-          unsafe { &PROTOCOL as *const _ }
-        }
-      ],
-      null: 0,
-    });
-
-    unsafe { #objrs_root::__objrs::TransmuteHack::<_, *mut #objrs_root::__objrs::runtime::protocol_list_t> { from: &#list_ident }.to }
-  }};
+  let self_ty = &class_impl.item.self_ty;
+  let protocols =
+    category_protocols(class_name_str, category_name_str, trait_name, self_ty, objrs_root);
+  let add_protocol = class_protocols(trait_name, self_ty, objrs_root);
 
   let class_link_name = ["OBJC_CLASS_$_", class_name_str].concat();
 
@@ -621,6 +554,8 @@ fn custom_category(
     #[export_name = #label_category_link_name]
     #[used]
     static #ident: &'static #objrs_root::__objrs::SyncHack<#objrs_root::__objrs::runtime::category_t> = #category;
+
+    #add_protocol
   };
 
   return Ok(tokens);
@@ -764,6 +699,18 @@ pub fn transform_impl(attr: ImplAttr, input: TokenStream) -> Result<TokenStream,
   let image_info_name =
     ["\x01L_OBJC_IMAGE_INFO.__objrs_image.", random_id, ".", class_name_str].concat();
   let image_info_name = LitStr::new(&image_info_name, Span::call_site()); // TODO: use Span::def_site().
+  let image_info;
+  if cfg!(feature = "runtime-gnustep") {
+    // libobjc2 doesn't use image info.
+    image_info = TokenStream::new();
+  } else {
+    image_info = quote! {
+      #[link_section = "__DATA,__objc_imageinfo,regular,no_dead_strip"]
+      #[export_name = #image_info_name]
+      #[used]
+      static IMAGE_INFO: #objrs_root::__objrs::runtime::objc_image_info = #objrs_root::__objrs::runtime::objc_image_info::DEFAULT;
+    };
+  }
 
   // let self_as_class = quote!{
   //   <#ident as #objrs_root::__objrs::runtime::__objrs::Class>
@@ -791,6 +738,21 @@ pub fn transform_impl(attr: ImplAttr, input: TokenStream) -> Result<TokenStream,
     let super_class_ref_value = gen_super_class_ref_value(class_name_str, &objrs_root)?;
     let super_meta_ref_value = gen_super_meta_ref_value(class_name_str, &objrs_root)?;
 
+    // Generic classes (e.g. NSArray<T>) don't get ClassRef: isKindOfClass: can only check the class,
+    // not its type parameters, so a downcast to e.g. NSArray<NSString> would be unchecked.
+    let class_ref_impl = if generics.params.is_empty() {
+      Some(quote! {
+        unsafe impl #objrs_root::marker::ClassRef for #self_ty {
+          #[inline(always)]
+          fn __objrs_class() -> &'static #objrs_root::Class {
+            return Self::__objrs_class_ref();
+          }
+        }
+      })
+    } else {
+      None
+    };
+
     refs = quote! {
       impl #generics #self_ty #where_clause {
         // TODO: get rid of the __objrs_class_ref and __objrs_super_ref methods. Just use the extern
@@ -802,10 +764,7 @@ pub fn transform_impl(attr: ImplAttr, input: TokenStream) -> Result<TokenStream,
         fn __objrs_class_ref() -> &'static #objrs_root::Class {
           #class_impl_tokens
 
-          #[link_section = "__DATA,__objc_imageinfo,regular,no_dead_strip"]
-          #[export_name = #image_info_name]
-          #[used]
-          static IMAGE_INFO: #objrs_root::__objrs::runtime::objc_image_info = #objrs_root::__objrs::runtime::objc_image_info::DEFAULT;
+          #image_info
 
           #[inline(#ref_hack_inline)]
           fn ref_hack() -> &'static #objrs_root::Class {
@@ -836,6 +795,8 @@ pub fn transform_impl(attr: ImplAttr, input: TokenStream) -> Result<TokenStream,
           return ref_hack();
         }
       }
+
+      #class_ref_impl
     };
   }
 
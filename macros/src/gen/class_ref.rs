@@ -22,6 +22,10 @@ fn gen_ref(
   link_section: &str,
   export_type: &str,
 ) -> Result<TokenStream, Diagnostic> {
+  if cfg!(feature = "runtime-gnustep") {
+    return Ok(gnustep_ref(class_ref, class_type));
+  }
+
   let random_id = &RandomIdentifier::new();
 
   let class_str = &class_ref.class.value();
@@ -46,6 +50,26 @@ fn gen_ref(
   });
 }
 
+// libobjc2's v2 ABI names classes `._OBJC_CLASS_Name` (and metaclasses `._OBJC_METACLASS_Name`).
+// All class references (including super references) go in the `__objc_class_refs` section.
+fn gnustep_ref(class_ref: ClassRef, class_type: &str) -> TokenStream {
+  let link_name = ["._OBJC_", class_type, "_", &class_ref.class.value()].concat();
+
+  let ident = class_ref.ident;
+  let sync_hack_ty = class_ref.sync_hack_ty;
+  let class_ty = class_ref.class_ty;
+  return quote! {
+    #[link_section = "__objc_class_refs"]
+    static #ident: #sync_hack_ty<&'static #class_ty> = #sync_hack_ty({
+      extern "C" {
+        #[link_name = #link_name]
+        static CLASS: #class_ty;
+      }
+      unsafe { &CLASS }
+    });
+  };
+}
+
 fn gen_value(
   ref_generator: fn(ClassRef) -> Result<TokenStream, Diagnostic>,
   class_name: &str,
@@ -126,6 +150,7 @@ mod tests {
   }
 
   #[test]
+  #[cfg(not(feature = "runtime-gnustep"))]
   fn class_ref() {
     let actual = gen_class_ref(default_class()).unwrap();
 
@@ -144,6 +169,7 @@ mod tests {
   }
 
   #[test]
+  #[cfg(not(feature = "runtime-gnustep"))]
   fn super_class_ref() {
     let actual = gen_super_class_ref(default_class()).unwrap();
 
@@ -162,6 +188,7 @@ mod tests {
   }
 
   #[test]
+  #[cfg(not(feature = "runtime-gnustep"))]
   fn super_meta_ref() {
     let actual = gen_super_meta_ref(default_class()).unwrap();
 
@@ -178,4 +205,20 @@ mod tests {
     };
     assert_tokens_eq!(actual, expected);
   }
+  #[test]
+  fn gnustep_meta_ref() {
+    let actual = gnustep_ref(default_class(), "METACLASS");
+
+    let expected = quote! {
+      #[link_section = "__objc_class_refs"]
+      static CLASS_REF: __objrs_root::__objrs::SyncHack<&'static __objrs_root::Class> = __objrs_root::__objrs::SyncHack({
+        extern "C" {
+          #[link_name = "._OBJC_METACLASS_TheClass"]
+          static CLASS: __objrs_root::Class;
+        }
+        unsafe { &CLASS }
+      });
+    };
+    assert_tokens_eq!(actual, expected);
+  }
 }
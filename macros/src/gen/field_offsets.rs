@@ -65,6 +65,7 @@ mod tests {
       super_class: None,
       force_extern: false,
       root_class_name: None,
+      properties: vec![],
       objrs: Some(priv_ident("__objrs_root")),
     };
     return Class::new(class_attr, tokens).unwrap();
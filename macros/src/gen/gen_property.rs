@@ -0,0 +1,720 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+extern crate core;
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use crate::class::{
+  accessor_count_ident, accessors_ident, selectors_ident, synthesized_selectors_ident,
+};
+use crate::gen::gen_selector::gen_method_type_encoding;
+use crate::parse::class_attr::{Class, Ivar};
+use crate::parse::property_attr::{PropertyAttr, PropertyImpl};
+use crate::util::priv_ident;
+use proc_macro::Diagnostic;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+  parse_quote, Field, GenericArgument, Ident, ImplItemMethod, LitByteStr, PathArguments, Type,
+};
+
+// Returns the ident and generic argument of types like `Option<T>`, `Strong<T>`, `Weak<T>`, etc.
+fn single_generic_argument(ty: &Type) -> Option<(&Ident, &Type)> {
+  let path = match ty {
+    Type::Path(ref type_path) if type_path.qself.is_none() => &type_path.path,
+    Type::Paren(ref inner) => return single_generic_argument(inner.elem.as_ref()),
+    Type::Group(ref inner) => return single_generic_argument(inner.elem.as_ref()),
+    _ => return None,
+  };
+  let last_segment = path.segments.last()?;
+  if let PathArguments::AngleBracketed(ref arguments) = last_segment.arguments {
+    // Lifetime arguments (e.g. the pool lifetime in `Auto<'pool, T>`) are ignored.
+    let mut types = arguments.args.iter().filter(|arg| match arg {
+      GenericArgument::Lifetime(_) => false,
+      _ => true,
+    });
+    if let (Some(GenericArgument::Type(ref inner)), None) = (types.next(), types.next()) {
+      return Some((&last_segment.ident, inner));
+    }
+  }
+  return None;
+}
+
+// Returns the class name of an object type that can be identified syntactically (e.g.
+// `Option<Strong<NSString>>` → `NSString`). This is used for the `@"NSString"` type encoding in
+// property attributes. Returns `None` for `Id` and for types it can't identify.
+// TODO: use the class's CLASS_NAME instead of the type's name (they differ if `name = "..."` is
+// used).
+pub fn object_class_name(ty: &Type) -> Option<String> {
+  let (ident, inner) = single_generic_argument(ty)?;
+  if ident == "Option" {
+    return object_class_name(inner);
+  }
+  if ident != "Strong" && ident != "Weak" && ident != "Auto" {
+    return None;
+  }
+  let class_ident = match inner {
+    Type::Path(ref type_path) if type_path.qself.is_none() => {
+      &type_path.path.segments.last()?.ident
+    }
+    _ => return None,
+  };
+  if class_ident == "Id" {
+    return None;
+  }
+  return Some(class_ident.to_string());
+}
+
+/// Returns the backing ivar of a synthesized property, or `None` if the property is dynamic. A
+/// property is synthesized if it isn't marked `dynamic` and the class has a field with the same
+/// name as the property.
+pub fn backing_ivar<'a>(
+  class: &'a Class,
+  property: &PropertyAttr,
+) -> Option<(&'a Field, &'a Ivar)> {
+  if property.is_class_property() || property.is_dynamic() {
+    return None;
+  }
+  for (field, ivar) in class.item.fields.iter().zip(class.ivars.iter()) {
+    if field.ident.as_ref() == Some(&property.name) {
+      return Some((field, ivar));
+    }
+  }
+  return None;
+}
+
+/// Generates a `property_t` value. `symbol_id` uniquely identifies the property (e.g.
+/// `Class::name`) and is used to build the export names of the property's strings.
+pub fn property_t(
+  property: &PropertyAttr,
+  implementation: PropertyImpl,
+  symbol_id: &str,
+  objrs_root: &Ident,
+) -> TokenStream {
+  let native_ty = quote!(#objrs_root::__objrs);
+
+  let name_export_name = ["\x01L_OBJC_PROP_NAME_ATTR_.__objrs_prop.", symbol_id].concat();
+  let attributes_export_name =
+    ["\x01L_OBJC_PROP_NAME_ATTR_.__objrs_prop_attr.", symbol_id].concat();
+
+  let name = [&property.name.to_string(), "\x00"].concat();
+  let name_len = name.len();
+  let name = LitByteStr::new(name.as_bytes(), Span::call_site()); // TODO: use def_site().
+
+  let encoded_type_ty;
+  let encoded_type;
+  if property.is_object() {
+    let encoded = match object_class_name(&property.ty) {
+      Some(class_name) => ["@\"", &class_name, "\""].concat(),
+      None => "@".to_string(),
+    };
+    let encoded_len = encoded.len();
+    let encoded = LitByteStr::new(encoded.as_bytes(), Span::call_site()); // TODO: use def_site().
+    encoded_type_ty = quote!([#native_ty::u8; #encoded_len]);
+    encoded_type = quote!(*#encoded);
+  } else {
+    let ty = &property.ty;
+    encoded_type_ty = quote!(<#ty as #objrs_root::__objrs::TypeEncodingHack>::Type);
+    encoded_type = quote!(<#ty as #objrs_root::__objrs::TypeEncodingHack>::BYTES);
+  }
+
+  let attributes = [&property.attributes(implementation), "\x00"].concat();
+  let attributes_len = attributes.len();
+  let attributes = LitByteStr::new(attributes.as_bytes(), Span::call_site()); // TODO: use def_site().
+
+  return quote! {
+    #objrs_root::__objrs::runtime::property_t {
+      name: {
+        #[link_section = "__TEXT,__cstring,cstring_literals"]
+        #[export_name = #name_export_name]
+        static PROP_NAME: [#native_ty::u8; #name_len] = *#name;
+        &PROP_NAME as *const _ as *const _
+      },
+      attributes: {
+        #[link_section = "__TEXT,__cstring,cstring_literals"]
+        #[export_name = #attributes_export_name]
+        static PROP_ATTRIBUTES: #objrs_root::__objrs::Packed3<#native_ty::u8, #encoded_type_ty, [#native_ty::u8; #attributes_len]> = #objrs_root::__objrs::Packed3(b'T', #encoded_type, *#attributes);
+        &PROP_ATTRIBUTES as *const _ as *const _
+      },
+    },
+  };
+}
+
+/// Generates a `property_list_t` static named `list_ident` (or a unit static if there are no
+/// properties). Protocols' property lists are weak (like the protocols themselves).
+pub fn property_list(
+  list_ident: &Ident,
+  export_name: &str,
+  weak: bool,
+  count: usize,
+  entries: TokenStream,
+  objrs_root: &Ident,
+) -> TokenStream {
+  if count == 0 {
+    return quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      static #list_ident: () = ();
+    };
+  }
+
+  let native_ty = quote!(#objrs_root::__objrs);
+  let linkage = if weak { quote!(#[linkage = "weak"]) } else { quote!() };
+  return quote! {
+    #[doc(hidden)]
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    struct #list_ident {
+      entsize_and_flags: #native_ty::u32,
+      count: #native_ty::u32,
+      properties: [#objrs_root::__objrs::runtime::property_t; #count],
+    }
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    #linkage
+    #[link_section = "__DATA,__objc_const"]
+    #[export_name = #export_name]
+    static #list_ident: #list_ident = {
+      unsafe impl #objrs_root::__objrs::core::marker::Sync for #list_ident {}
+      #list_ident {
+        entsize_and_flags: #objrs_root::__objrs::core::mem::size_of::<#objrs_root::__objrs::runtime::property_t>() as #native_ty::u32,
+        count: #count as #native_ty::u32,
+        properties: [#entries],
+      }
+    };
+  };
+}
+
+fn getter(
+  property: &PropertyAttr,
+  field: &Field,
+  ident: &Ident,
+  offset: &TokenStream,
+  objrs_root: &Ident,
+) -> ImplItemMethod {
+  let native_ty = quote!(#objrs_root::__objrs);
+  let field_ty = &field.ty;
+  let atomic = !property.nonatomic();
+
+  if property.is_weak() {
+    return parse_quote! {
+      #[doc(hidden)]
+      extern "C" fn #ident(&self, _: &'static #objrs_root::Sel) -> *mut #objrs_root::Id {
+        let offset: #native_ty::usize = #offset;
+        unsafe {
+          let ivar = &*((self as *const Self as *const #native_ty::u8).add(offset) as *const #field_ty);
+          let location = <#field_ty as #objrs_root::__objrs::WeakLocation>::location(ivar);
+          return #objrs_root::__objrs::runtime::objc_loadWeak(&mut *location);
+        }
+      }
+    };
+  }
+
+  if property.is_object() {
+    return parse_quote! {
+      #[doc(hidden)]
+      extern "C" fn #ident(&self, sel: &'static #objrs_root::Sel) -> *mut #objrs_root::Id {
+        // Object properties must be backed by a pointer-sized ivar (e.g. `Option<Strong<T>>`).
+        const IVAR_IS_POINTER_SIZED: () = [()][(#objrs_root::__objrs::core::mem::size_of::<#field_ty>() != #objrs_root::__objrs::core::mem::size_of::<#native_ty::usize>()) as #native_ty::usize];
+        let _ = IVAR_IS_POINTER_SIZED;
+        let offset: #native_ty::usize = #offset;
+        return unsafe { #objrs_root::__objrs::runtime::objc_getProperty(self as *const Self as *mut #objrs_root::Id, sel, offset as #native_ty::isize, #atomic) };
+      }
+    };
+  }
+
+  // TODO: atomic properties of non-trivial structs should use objc_copyStruct.
+  let property_ty = &property.ty;
+  return parse_quote! {
+    #[doc(hidden)]
+    extern "C" fn #ident(&self, _: &'static #objrs_root::Sel) -> #property_ty {
+      let offset: #native_ty::usize = #offset;
+      unsafe {
+        let ivar: &#field_ty = &*((self as *const Self as *const #native_ty::u8).add(offset) as *const #field_ty);
+        return *ivar;
+      }
+    }
+  };
+}
+
+fn setter(
+  property: &PropertyAttr,
+  field: &Field,
+  ident: &Ident,
+  offset: &TokenStream,
+  objrs_root: &Ident,
+) -> ImplItemMethod {
+  let native_ty = quote!(#objrs_root::__objrs);
+  let field_ty = &field.ty;
+  let atomic = !property.nonatomic();
+
+  if property.is_weak() {
+    return parse_quote! {
+      #[doc(hidden)]
+      extern "C" fn #ident(&mut self, _: &'static #objrs_root::Sel, value: *mut #objrs_root::Id) {
+        let offset: #native_ty::usize = #offset;
+        unsafe {
+          let ivar = &*((self as *const Self as *const #native_ty::u8).add(offset) as *const #field_ty);
+          let location = <#field_ty as #objrs_root::__objrs::WeakLocation>::location(ivar);
+          #objrs_root::__objrs::runtime::objc_storeWeak(&mut *location, value);
+        }
+      }
+    };
+  }
+
+  if property.is_object() {
+    let should_copy = property.is_copy() as i8;
+    return parse_quote! {
+      #[doc(hidden)]
+      extern "C" fn #ident(&mut self, sel: &'static #objrs_root::Sel, value: *mut #objrs_root::Id) {
+        let offset: #native_ty::usize = #offset;
+        unsafe { #objrs_root::__objrs::runtime::objc_setProperty(self as *mut Self as *mut #objrs_root::Id, sel, offset as #native_ty::isize, value, #atomic, #should_copy) };
+      }
+    };
+  }
+
+  let property_ty = &property.ty;
+  return parse_quote! {
+    #[doc(hidden)]
+    extern "C" fn #ident(&mut self, _: &'static #objrs_root::Sel, value: #property_ty) {
+      let offset: #native_ty::usize = #offset;
+      unsafe {
+        let ivar: &mut #field_ty = &mut *((self as *mut Self as *mut #native_ty::u8).add(offset) as *mut #field_ty);
+        *ivar = value;
+      }
+    }
+  };
+}
+
+// Returns the ident of the associated const that records whether the class implements the
+// selector (e.g. `setFoo:` → `__objrs_sel_setFoo_c`). Underscores are doubled so distinct
+// selectors can't map to the same ident.
+pub fn implemented_selector_ident(sel: &str) -> Ident {
+  let mut ident = String::from("__objrs_sel_");
+  for c in sel.chars() {
+    match c {
+      '_' => ident.push_str("__"),
+      ':' => ident.push_str("_c"),
+      _ => ident.push(c),
+    }
+  }
+  return Ident::new(&ident, Span::call_site());
+}
+
+/// Synthesizes the getters and setters of properties that are backed by an ivar. The accessors
+/// live in the class's own method list, which is generated by the `#[objrs(impl)]` macro. That
+/// macro can't see the class's properties, and this macro can't see the class's methods, so they
+/// cooperate through the `__objrs_selectors_<Class>` type. This macro gives it a `false`
+/// associated const (through a trait) for each accessor's selector, and the impl macro shadows it
+/// with an inherent `true` const for each selector the class implements. Like clang, an accessor is
+/// only synthesized if the class doesn't implement it. The synthesized accessors are collected in
+/// the `__objrs_accessors_<Class>` const as zero- or one-element arrays of `method_t`.
+pub fn synthesize_accessors(class: &Class) -> Result<TokenStream, Diagnostic> {
+  let objrs_root = &class.objrs;
+  let native_ty = quote!(#objrs_root::__objrs);
+  let class_name_str = &class.class_name.value();
+  let self_ty = &class.item.ident;
+
+  let mut methods = vec![];
+  for property in class.properties.iter() {
+    let (field, ivar) = match backing_ivar(class, property) {
+      Some(value) => value,
+      None => continue,
+    };
+
+    if !class.item.generics.params.is_empty() {
+      return Err(
+        property
+          .name
+          .span()
+          .unstable()
+          .error("properties cannot be synthesized for generic classes")
+          .note("mark the property `dynamic` or remove the field with the same name"),
+      );
+    }
+
+    let offset_export_name = ["OBJC_IVAR_$_", class_name_str, ".", &ivar.name.value()].concat();
+    let offset = quote! {{
+      extern "C" {
+        #[link_name = #offset_export_name]
+        static IVAR_OFFSET: #native_ty::usize;
+      }
+      unsafe { #objrs_root::__objrs::core::ptr::read_volatile(&IVAR_OFFSET) }
+    }};
+
+    let name = property.name.to_string();
+    let getter_sel = property.getter().value();
+    let getter_ident = priv_ident(&["__objrs_property_get_", &name].concat());
+    // The accessors don't get `-[Class selector]` export names (like methods do) since the class
+    // may implement the same selector itself, which would cause a duplicate symbol.
+    let getter = getter(property, field, &getter_ident, &offset, objrs_root);
+    methods.push((getter_sel, getter));
+
+    if !property.readonly() {
+      let setter_sel = property.setter().expect("BUG: readwrite properties must have a setter");
+      let setter_sel = setter_sel.value();
+      let setter_ident = priv_ident(&["__objrs_property_set_", &name].concat());
+      let setter = setter(property, field, &setter_ident, &offset, objrs_root);
+      methods.push((setter_sel, setter));
+    }
+  }
+
+  let selectors_ident = selectors_ident(class_name_str);
+  let synthesized_ident = synthesized_selectors_ident(class_name_str);
+  let accessors_ident = accessors_ident(class_name_str);
+  let accessor_count_ident = accessor_count_ident(class_name_str);
+
+  let mut selector_consts = TokenStream::new();
+  let mut fields = TokenStream::new();
+  let mut values = TokenStream::new();
+  let mut count = quote!(0);
+  for (sel, method) in methods.iter() {
+    let sel_ident = implemented_selector_ident(sel);
+    let method_ident = &method.sig.ident;
+    let sel = [sel, "\x00"].concat();
+    let sel = LitByteStr::new(sel.as_bytes(), Span::call_site()); // TODO: use def_site().
+    let method_type_encoding = gen_method_type_encoding(&method.sig, true, false, objrs_root);
+    let len = quote!((!<#selectors_ident>::#sel_ident) as #native_ty::usize);
+    selector_consts.extend(quote! {
+      const #sel_ident: #native_ty::bool = false;
+    });
+    fields.extend(quote! {
+      #method_ident: [#objrs_root::__objrs::runtime::method_t; #len],
+    });
+    values.extend(quote! {
+      #method_ident: [#objrs_root::__objrs::runtime::method_t {
+        name: #sel as *const _ as *mut _,
+        types: {
+          #method_type_encoding
+          &METHOD_TYPE as *const _ as *const _
+        },
+        imp: unsafe { #objrs_root::__objrs::TransmuteHack { from: #self_ty::#method_ident as *const () }.to },
+      }; #len],
+    });
+    count.extend(quote!(+ #len));
+  }
+
+  let methods = methods.into_iter().map(|(_, method)| method);
+
+  return Ok(quote! {
+    impl #self_ty {
+      #(#methods)*
+    }
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
+    #[allow(non_camel_case_types)]
+    struct #selectors_ident;
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
+    #[allow(non_camel_case_types)]
+    #[allow(non_upper_case_globals)]
+    trait #synthesized_ident {
+      #selector_consts
+    }
+
+    impl #synthesized_ident for #selectors_ident {}
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
+    #[allow(non_camel_case_types)]
+    #[repr(C)]
+    struct #accessors_ident {
+      #fields
+    }
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
+    #[allow(non_upper_case_globals)]
+    const #accessors_ident: #accessors_ident = #accessors_ident {
+      #values
+    };
+
+    #[doc(hidden)]
+    #[allow(dead_code)]
+    #[allow(non_upper_case_globals)]
+    const #accessor_count_ident: #native_ty::usize = #count;
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate objrs_test_utils;
+
+  use super::*;
+  use crate::parse::class_attr::ClassAttr;
+  use objrs_test_utils::assert_tokens_eq;
+  use quote::ToTokens;
+  use syn::{parse2, ItemStruct};
+
+  fn make_class(tokens: TokenStream) -> Class {
+    let mut item = parse2::<ItemStruct>(tokens).unwrap();
+    let objrs_attr = item.attrs.remove(0);
+    assert!(objrs_attr.path.is_ident("objrs"));
+    let class_attr = objrs_attr.parse_args::<ClassAttr>().unwrap();
+    return Class::new(class_attr, item.into_token_stream()).unwrap();
+  }
+
+  fn attributes(class: &Class) -> Vec<String> {
+    return class
+      .properties
+      .iter()
+      .map(|property| {
+        let ivar = backing_ivar(class, property).map(|(_, ivar)| ivar.name.value());
+        return match ivar {
+          Some(ref ivar) => property.attributes(PropertyImpl::Synthesized(ivar)),
+          None => property.attributes(PropertyImpl::Dynamic),
+        };
+      })
+      .collect();
+  }
+
+  #[test]
+  fn property_attributes() {
+    let class = make_class(quote! {
+      #[objrs(class,
+              super = NSObject,
+              property(name: Option<Strong<NSString>>, copy, nonatomic),
+              property(delegate: Weak<NSObject>, weak),
+              property(count: usize, readonly, nonatomic),
+              property(enabled: bool, nonatomic, getter = "isEnabled"),
+              property(shared: Option<Strong<Foo>>, class, readonly, strong))]
+      struct Foo {
+        name: Option<Strong<NSString>>,
+        delegate: Weak<NSObject>,
+        #[objrs(ivar, name = "_enabled")]
+        enabled: bool,
+      }
+    });
+
+    let actual = attributes(&class);
+    let expected = vec![
+      ",C,N,Vname".to_string(),
+      ",W,Vdelegate".to_string(),
+      ",R,D,N".to_string(),
+      ",N,GisEnabled,V_enabled".to_string(),
+      ",R,&,D".to_string(),
+    ];
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn object_class_names() {
+    let ty: Type = parse_quote!(Option<objrs::Strong<NSString>>);
+    assert_eq!(object_class_name(&ty), Some("NSString".to_string()));
+    let ty: Type = parse_quote!(Weak<foundation::NSArray<NSString>>);
+    assert_eq!(object_class_name(&ty), Some("NSArray".to_string()));
+    let ty: Type = parse_quote!(Auto<'pool, NSString>);
+    assert_eq!(object_class_name(&ty), Some("NSString".to_string()));
+    let ty: Type = parse_quote!(Option<Strong<objrs::Id>>);
+    assert_eq!(object_class_name(&ty), None);
+    let ty: Type = parse_quote!(usize);
+    assert_eq!(object_class_name(&ty), None);
+  }
+
+  #[test]
+  fn object_property_t() {
+    let class = make_class(quote! {
+      #[objrs(class, super = NSObject, property(name: Option<Strong<NSString>>, copy, nonatomic))]
+      struct Foo {
+        name: Option<Strong<NSString>>,
+      }
+    });
+
+    let objrs_root = priv_ident("__objrs_root");
+    let actual =
+      property_t(&class.properties[0], PropertyImpl::Synthesized("name"), "Foo::name", &objrs_root);
+    let expected = quote! {
+      __objrs_root::__objrs::runtime::property_t {
+        name: {
+          #[link_section = "__TEXT,__cstring,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop.Foo::name"]
+          static PROP_NAME: [__objrs_root::__objrs::u8; 5usize] = *b"name\0";
+          &PROP_NAME as *const _ as *const _
+        },
+        attributes: {
+          #[link_section = "__TEXT,__cstring,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop_attr.Foo::name"]
+          static PROP_ATTRIBUTES: __objrs_root::__objrs::Packed3<__objrs_root::__objrs::u8, [__objrs_root::__objrs::u8; 11usize], [__objrs_root::__objrs::u8; 11usize]> = __objrs_root::__objrs::Packed3(b'T', *b"@\"NSString\"", *b",C,N,Vname\0");
+          &PROP_ATTRIBUTES as *const _ as *const _
+        },
+      },
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn scalar_property_t() {
+    let class = make_class(quote! {
+      #[objrs(class, super = NSObject, property(count: usize, readonly))]
+      struct Foo;
+    });
+
+    let objrs_root = priv_ident("__objrs_root");
+    let actual = property_t(&class.properties[0], PropertyImpl::Dynamic, "Foo::count", &objrs_root);
+    let expected = quote! {
+      __objrs_root::__objrs::runtime::property_t {
+        name: {
+          #[link_section = "__TEXT,__cstring,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop.Foo::count"]
+          static PROP_NAME: [__objrs_root::__objrs::u8; 6usize] = *b"count\0";
+          &PROP_NAME as *const _ as *const _
+        },
+        attributes: {
+          #[link_section = "__TEXT,__cstring,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop_attr.Foo::count"]
+          static PROP_ATTRIBUTES: __objrs_root::__objrs::Packed3<__objrs_root::__objrs::u8, <usize as __objrs_root::__objrs::TypeEncodingHack>::Type, [__objrs_root::__objrs::u8; 5usize]> = __objrs_root::__objrs::Packed3(b'T', <usize as __objrs_root::__objrs::TypeEncodingHack>::BYTES, *b",R,D\0");
+          &PROP_ATTRIBUTES as *const _ as *const _
+        },
+      },
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn dynamic_properties_are_not_synthesized() {
+    let class = make_class(quote! {
+      #[objrs(class,
+              super = NSObject,
+              property(count: usize, readonly),
+              property(enabled: bool, dynamic))]
+      struct Foo {
+        enabled: bool,
+      }
+    });
+
+    let actual = synthesize_accessors(&class).unwrap();
+    let expected = quote! {
+      impl Foo {}
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      struct __objrs_selectors_Foo;
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      #[allow(non_upper_case_globals)]
+      trait __objrs_synthesized_Foo {}
+
+      impl __objrs_synthesized_Foo for __objrs_selectors_Foo {}
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      #[repr(C)]
+      struct __objrs_accessors_Foo {}
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_accessors_Foo: __objrs_accessors_Foo = __objrs_accessors_Foo {};
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_accessor_count_Foo: objrs::__objrs::usize = 0;
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn implemented_selector_idents() {
+    assert_eq!(implemented_selector_ident("count").to_string(), "__objrs_sel_count");
+    assert_eq!(implemented_selector_ident("setCount:").to_string(), "__objrs_sel_setCount_c");
+    assert_eq!(implemented_selector_ident("a_c").to_string(), "__objrs_sel_a__c");
+    assert_eq!(implemented_selector_ident("a:").to_string(), "__objrs_sel_a_c");
+  }
+
+  #[test]
+  fn synthesized_accessors() {
+    let class = make_class(quote! {
+      #[objrs(class, super = NSObject, property(count: usize, readonly, nonatomic))]
+      struct Foo {
+        count: usize,
+      }
+    });
+
+    // The getter is only added to the class's method list (i.e. its array has one element) if the
+    // class's #[objrs(impl)] block doesn't define `__objrs_sel_count` (i.e. implement `count`).
+    let actual = synthesize_accessors(&class).unwrap();
+    let expected = quote! {
+      impl Foo {
+        #[doc(hidden)]
+        extern "C" fn __objrs_property_get_count(&self, _: &'static objrs::Sel) -> usize {
+          let offset: objrs::__objrs::usize = {
+            extern "C" {
+              #[link_name = "OBJC_IVAR_$_Foo.count"]
+              static IVAR_OFFSET: objrs::__objrs::usize;
+            }
+            unsafe { objrs::__objrs::core::ptr::read_volatile(&IVAR_OFFSET) }
+          };
+          unsafe {
+            let ivar: &usize = &*((self as *const Self as *const objrs::__objrs::u8).add(offset) as *const usize);
+            return *ivar;
+          }
+        }
+      }
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      struct __objrs_selectors_Foo;
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      #[allow(non_upper_case_globals)]
+      trait __objrs_synthesized_Foo {
+        const __objrs_sel_count: objrs::__objrs::bool = false;
+      }
+
+      impl __objrs_synthesized_Foo for __objrs_selectors_Foo {}
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_camel_case_types)]
+      #[repr(C)]
+      struct __objrs_accessors_Foo {
+        __objrs_property_get_count: [objrs::__objrs::runtime::method_t; (!<__objrs_selectors_Foo>::__objrs_sel_count) as objrs::__objrs::usize],
+      }
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_accessors_Foo: __objrs_accessors_Foo = __objrs_accessors_Foo {
+        __objrs_property_get_count: [objrs::__objrs::runtime::method_t {
+          name: b"count\0" as *const _ as *mut _,
+          types: {
+            const SIZE_OF_USIZE: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<objrs::__objrs::usize>();
+            const ARGS_1_LEN: objrs::__objrs::usize = 4;
+            const ARGS_1: [objrs::__objrs::u8; ARGS_1_LEN] = [b'@', b'0', b':', b'0' + SIZE_OF_USIZE as objrs::__objrs::u8];
+            struct FrameSize;
+            impl objrs::__objrs::ToAsciiHack for FrameSize {
+              const VALUE: objrs::__objrs::usize = SIZE_OF_USIZE * 2;
+            }
+            const RET_LEN: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<<usize as objrs::__objrs::TypeEncodingHack>::Type>() + <FrameSize as objrs::__objrs::ToAsciiHack>::LEN;
+            const RET: [objrs::__objrs::u8; RET_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed2(<usize as objrs::__objrs::TypeEncodingHack>::BYTES, <FrameSize as objrs::__objrs::ToAsciiHack>::STR) }.to };
+            const METHOD_TYPE_LEN: objrs::__objrs::usize = RET_LEN + ARGS_1_LEN + 1;
+            const METHOD_TYPE: [objrs::__objrs::u8; METHOD_TYPE_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed3(RET, ARGS_1, 0u8) }.to };
+            &METHOD_TYPE as *const _ as *const _
+          },
+          imp: unsafe { objrs::__objrs::TransmuteHack { from: Foo::__objrs_property_get_count as *const () }.to },
+        }; (!<__objrs_selectors_Foo>::__objrs_sel_count) as objrs::__objrs::usize],
+      };
+
+      #[doc(hidden)]
+      #[allow(dead_code)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_accessor_count_Foo: objrs::__objrs::usize = 0 + (!<__objrs_selectors_Foo>::__objrs_sel_count) as objrs::__objrs::usize;
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+}
@@ -7,16 +7,21 @@ extern crate core;
 
 // For Objective-C property attributes, see https://github.com/llvm-mirror/clang/blob/master/lib/Parse/ParseObjc.cpp
 
+use crate::class_impl::{extended_method_type, meth_var_name, method_type};
+use crate::gen::gen_property::{property_list, property_t};
 use crate::gen::gen_selector::{
   gen_instance_msg_send, gen_msg_recv_sig, gen_msg_send_sig, gen_trampoline,
 };
+use crate::parse::property_attr::PropertyImpl;
 use crate::parse::protocol_attr::Protocol;
 use crate::parse::selector_attr::Method;
-use crate::util::priv_ident;
+use crate::util::{priv_ident, require_apple_runtime, AppleOnly};
 use proc_macro::Diagnostic;
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn::{parse_quote, GenericParam, Ident, TraitItem, TraitItemMethod};
+use syn::{
+  parse_quote, GenericParam, Ident, ImplItemMethod, LitByteStr, LitStr, TraitItem, TraitItemMethod,
+};
 
 fn gen_send_recv(method: &Method, objrs_root: &Ident) -> (TraitItemMethod, TraitItemMethod) {
   let sig = gen_msg_send_sig(method.sig(), method.is_instance_method, objrs_root);
@@ -30,7 +35,8 @@ fn gen_send_recv(method: &Method, objrs_root: &Ident) -> (TraitItemMethod, Trait
     semi_token: None,
   };
 
-  let sig = gen_msg_recv_sig(method.sig(), method.is_instance_method, objrs_root);
+  let family = method.attr.family();
+  let sig = gen_msg_recv_sig(method.sig(), method.is_instance_method, family, objrs_root);
   let panic_message = format!("private objrs method `{}` called", sig.ident);
   let msg_recv = TraitItemMethod {
     attrs: msg_send.attrs.clone(),
@@ -71,8 +77,317 @@ fn transgen_and_extend(method: Method, objrs_root: &Ident, items: &mut Vec<Trait
   items.push(TraitItem::Method(recv));
 }
 
+pub fn protocol_ident(protocol_name: &str) -> Ident {
+  return Ident::new(&["__objrs_protocol_", protocol_name].concat(), Span::call_site());
+}
+
+fn protocol_name(protocol: &Protocol) -> String {
+  if let Some(ref trait_name) = protocol.trait_name {
+    return trait_name.value();
+  }
+  return protocol.item.ident.to_string();
+}
+
+// Generates a protocol method's `method_t` (which has no implementation) and extended type
+// encoding.
+fn protocol_method(
+  method: &Method,
+  protocol_name: &str,
+  objrs_root: &Ident,
+) -> (TokenStream, TokenStream) {
+  let family = method.attr.family();
+  let sig = gen_msg_recv_sig(method.sig(), method.is_instance_method, family, objrs_root);
+  let msg_recv: ImplItemMethod = parse_quote!(#sig {});
+  let method_ident = msg_recv.sig.ident.to_string();
+
+  let mut sel = method.attr.sel.value();
+  sel.push('\x00');
+
+  let symbol_id = ["@protocol(", protocol_name, ")"].concat();
+  let meth_var_name = meth_var_name(sel.as_bytes(), &symbol_id, &method_ident, objrs_root);
+  let symbol_id = LitStr::new(&symbol_id, Span::call_site()); // TODO: use def_site().
+  let meth_var_type = method_type(&msg_recv, &symbol_id, method.is_instance_method, objrs_root);
+
+  // Clang emits a separate copy of the type encoding for the extended method types (which include
+  // class names for object types, like `@"NSString"`, and block signatures). The extended encoding
+  // uses the method's declared return type, since msg_recv may return a raw pointer instead (see
+  // gen_msg_recv_sig).
+  let mut extended_method = msg_recv.clone();
+  extended_method.sig.output = method.sig().output.clone();
+  let extended_id = [&symbol_id.value(), ".extended"].concat();
+  let extended_id = LitStr::new(&extended_id, Span::call_site()); // TODO: use def_site().
+  let extended_type =
+    extended_method_type(&extended_method, &extended_id, method.is_instance_method, objrs_root);
+
+  let method_t = quote! {
+    #objrs_root::__objrs::runtime::method_t {
+      name: #meth_var_name,
+      types: #meth_var_type as *const _ as *const _,
+      imp: 0 as *mut _,
+    },
+  };
+  let extended_type = quote!(#extended_type as *const _ as *const _,);
+  return (method_t, extended_type);
+}
+
+fn protocol_method_list(
+  entries: TokenStream,
+  count: usize,
+  export_name: &str,
+  objrs_root: &Ident,
+) -> TokenStream {
+  if count == 0 {
+    return quote!(0 as *mut #objrs_root::__objrs::runtime::method_list_t);
+  }
+
+  let native_ty = quote!(#objrs_root::__objrs);
+  return quote! {{
+    #[repr(C)]
+    struct MethodList {
+      entsize_and_flags: #native_ty::u32,
+      count: #native_ty::u32,
+      methods: [#objrs_root::__objrs::runtime::method_t; #count],
+    }
+
+    #[link_section = "__DATA,__objc_const"]
+    #[export_name = #export_name]
+    static METHOD_LIST: #objrs_root::__objrs::SyncHack<MethodList> = #objrs_root::__objrs::SyncHack(MethodList {
+      entsize_and_flags: #objrs_root::__objrs::core::mem::size_of::<#objrs_root::__objrs::runtime::method_t>() as #native_ty::u32,
+      count: #count as #native_ty::u32,
+      methods: [#entries],
+    });
+
+    unsafe { #objrs_root::__objrs::TransmuteHack::<_, *mut #objrs_root::__objrs::runtime::method_list_t> { from: &METHOD_LIST }.to }
+  }};
+}
+
+// Extern protocols are declared in framework headers, and the frameworks themselves don't export a
+// definition for them (they're like C++ header-only templates), so we have to provide a symbol
+// ourselves. The placeholder is a valid (but empty) protocol with the right name, which is enough
+// for the runtime to match it against the real protocol by name. Like custom protocols, it's a weak
+// symbol, so multiple crates (or a crate that defines the real protocol) can provide it.
+// TODO: emit the protocol's full definition, like clang does. That requires the Rust code to
+// exactly match the Objective-C declaration.
+fn extern_protocol(protocol: &Protocol) -> TokenStream {
+  let protocol_name = &protocol_name(protocol);
+  let protocol_ident = protocol_ident(protocol_name);
+  let objrs_root = &protocol.objrs;
+  let native_ty = quote!(#objrs_root::__objrs);
+
+  let protocol_cstr = [protocol_name, "\x00"].concat();
+  let protocol_cstr_len = protocol_cstr.len();
+  let protocol_cstr = LitByteStr::new(protocol_cstr.as_bytes(), Span::call_site()); // TODO: use def_site().
+  let protocol_name_export_name =
+    ["\x01L_OBJC_CLASS_NAME_.__objrs_protocol.", protocol_name].concat();
+  let protocol_export_name = ["\x01l_OBJC_PROTOCOL_$_", protocol_name].concat();
+
+  return quote! {
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    #[linkage = "weak"]
+    #[link_section = "__DATA,__data"]
+    #[export_name = #protocol_export_name]
+    static #protocol_ident: #objrs_root::__objrs::SyncHack<#objrs_root::__objrs::runtime::protocol_t> = #objrs_root::__objrs::SyncHack(#objrs_root::__objrs::runtime::protocol_t {
+      isa: 0 as *mut _,
+      mangled_name: {
+        #[link_section = "__TEXT,__objc_classname,cstring_literals"]
+        #[export_name = #protocol_name_export_name]
+        static PROTOCOL_NAME: [#native_ty::u8; #protocol_cstr_len] = *#protocol_cstr;
+        &PROTOCOL_NAME
+      } as *const _ as *const _,
+      protocols: 0 as *mut _,
+      instance_methods: 0 as *mut _,
+      class_methods: 0 as *mut _,
+      optional_instance_methods: 0 as *mut _,
+      optional_class_methods: 0 as *mut _,
+      instance_properties: 0 as *mut _,
+      size: #objrs_root::__objrs::core::mem::size_of::<#objrs_root::__objrs::runtime::protocol_t>() as #native_ty::u32,
+      flags: 0,
+      extended_method_types: 0 as *mut _,
+      demangled_name: 0 as *const _,
+      class_properties: 0 as *mut _,
+    });
+  };
+}
+
+// Generates the full protocol definition for a custom protocol. Like clang, the protocol, its
+// protolist label, and its protoref are weak symbols (which ld64 coalesces).
+// TODO: clang also marks these symbols as private extern, but Rust has no way to control a
+// symbol's visibility.
+fn custom_protocol(protocol: &Protocol) -> TokenStream {
+  let objrs_root = &protocol.objrs;
+  let native_ty = quote!(#objrs_root::__objrs);
+  let protocol_name = &protocol_name(protocol);
+
+  // The extended method types are ordered the same as the method lists: required instance methods,
+  // required class methods, optional instance methods, and then optional class methods.
+  let mut extended_types = TokenStream::new();
+  let mut extended_types_count = 0usize;
+  let mut method_lists = vec![];
+  let lists = [
+    ("PROTOCOL_INSTANCE_METHODS_", &protocol.instance_methods, false),
+    ("PROTOCOL_CLASS_METHODS_", &protocol.class_methods, false),
+    ("PROTOCOL_INSTANCE_METHODS_OPT_", &protocol.instance_methods, true),
+    ("PROTOCOL_CLASS_METHODS_OPT_", &protocol.class_methods, true),
+  ];
+  for (list_name, methods, optional) in lists.iter() {
+    let mut entries = TokenStream::new();
+    let mut count = 0usize;
+    for method in methods.iter() {
+      if method.attr.optional.is_some() != *optional {
+        continue;
+      }
+      let (method_t, extended_type) = protocol_method(method, protocol_name, objrs_root);
+      entries.extend(method_t);
+      extended_types.extend(extended_type);
+      count += 1;
+    }
+    let export_name = ["\x01l_OBJC_$_", *list_name, protocol_name].concat();
+    method_lists.push(protocol_method_list(entries, count, &export_name, objrs_root));
+    extended_types_count += count;
+  }
+  let optional_class_methods = method_lists.pop();
+  let optional_instance_methods = method_lists.pop();
+  let class_methods = method_lists.pop();
+  let instance_methods = method_lists.pop();
+
+  let extended_method_types;
+  if extended_types_count == 0 {
+    extended_method_types = quote!(0 as *mut _);
+  } else {
+    let export_name = ["\x01l_OBJC_$_PROTOCOL_METHOD_TYPES_", protocol_name].concat();
+    extended_method_types = quote! {{
+      #[link_section = "__DATA,__objc_const"]
+      #[export_name = #export_name]
+      static METHOD_TYPES: #objrs_root::__objrs::SyncHack<[*const #native_ty::u8; #extended_types_count]> = #objrs_root::__objrs::SyncHack([#extended_types]);
+      unsafe { #objrs_root::__objrs::TransmuteHack::<_, *mut *const _> { from: &METHOD_TYPES }.to }
+    }};
+  }
+
+  let mut instance_entries = TokenStream::new();
+  let mut class_entries = TokenStream::new();
+  let mut instance_count = 0usize;
+  let mut class_count = 0usize;
+  for property in protocol.properties.iter() {
+    let name = property.name.to_string();
+    if property.is_class_property() {
+      let symbol_id = ["@protocol(", protocol_name, ")::+", &name].concat();
+      class_entries.extend(property_t(property, PropertyImpl::Declared, &symbol_id, objrs_root));
+      class_count += 1;
+    } else {
+      let symbol_id = ["@protocol(", protocol_name, ")::", &name].concat();
+      instance_entries.extend(property_t(property, PropertyImpl::Declared, &symbol_id, objrs_root));
+      instance_count += 1;
+    }
+  }
+  let instance_list_ident =
+    Ident::new(&["__objrs_protocol_props_", protocol_name].concat(), Span::call_site());
+  let class_list_ident =
+    Ident::new(&["__objrs_protocol_class_props_", protocol_name].concat(), Span::call_site());
+  // Clang gives protocols' property lists the same names as classes' property lists (and relies on
+  // private linkage to keep them apart). objrs can't, so the names include `.__objrs_protocol.`.
+  let instance_list = property_list(
+    &instance_list_ident,
+    &["\x01l_OBJC_$_PROP_LIST_.__objrs_protocol.", protocol_name].concat(),
+    true,
+    instance_count,
+    instance_entries,
+    objrs_root,
+  );
+  let class_list = property_list(
+    &class_list_ident,
+    &["\x01l_OBJC_$_CLASS_PROP_LIST_.__objrs_protocol.", protocol_name].concat(),
+    true,
+    class_count,
+    class_entries,
+    objrs_root,
+  );
+  let instance_properties;
+  if instance_count == 0 {
+    instance_properties = quote!(0 as *mut _);
+  } else {
+    instance_properties = quote!(&#instance_list_ident as *const _ as *mut _);
+  }
+  let class_properties;
+  if class_count == 0 {
+    class_properties = quote!(0 as *mut _);
+  } else {
+    class_properties = quote!(&#class_list_ident as *const _ as *mut _);
+  }
+
+  let protocol_cstr = [protocol_name, "\x00"].concat();
+  let protocol_cstr_len = protocol_cstr.len();
+  let protocol_cstr = LitByteStr::new(protocol_cstr.as_bytes(), Span::call_site()); // TODO: use def_site().
+  let protocol_name_export_name =
+    ["\x01L_OBJC_CLASS_NAME_.__objrs_protocol.", protocol_name].concat();
+  let protocol_export_name = ["\x01l_OBJC_PROTOCOL_$_", protocol_name].concat();
+  let label_export_name = ["\x01l_OBJC_LABEL_PROTOCOL_$_", protocol_name].concat();
+  let reference_export_name = ["\x01l_OBJC_PROTOCOL_REFERENCE_$_", protocol_name].concat();
+  let protocol_ident = protocol_ident(protocol_name);
+  let label_ident =
+    Ident::new(&["__objrs_protocol_label_", protocol_name].concat(), Span::call_site());
+  let reference_ident =
+    Ident::new(&["__objrs_protocol_ref_", protocol_name].concat(), Span::call_site());
+
+  return quote! {
+    #instance_list
+    #class_list
+
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    #[linkage = "weak"]
+    #[link_section = "__DATA,__data"]
+    #[export_name = #protocol_export_name]
+    static #protocol_ident: #objrs_root::__objrs::SyncHack<#objrs_root::__objrs::runtime::protocol_t> = #objrs_root::__objrs::SyncHack(#objrs_root::__objrs::runtime::protocol_t {
+      isa: 0 as *mut _,
+      mangled_name: {
+        #[link_section = "__TEXT,__objc_classname,cstring_literals"]
+        #[export_name = #protocol_name_export_name]
+        static PROTOCOL_NAME: [#native_ty::u8; #protocol_cstr_len] = *#protocol_cstr;
+        &PROTOCOL_NAME
+      } as *const _ as *const _,
+      // TODO: support protocols that inherit other protocols.
+      protocols: 0 as *mut _,
+      instance_methods: #instance_methods,
+      class_methods: #class_methods,
+      optional_instance_methods: #optional_instance_methods,
+      optional_class_methods: #optional_class_methods,
+      instance_properties: #instance_properties,
+      size: #objrs_root::__objrs::core::mem::size_of::<#objrs_root::__objrs::runtime::protocol_t>() as #native_ty::u32,
+      flags: 0,
+      extended_method_types: #extended_method_types,
+      demangled_name: 0 as *const _,
+      class_properties: #class_properties,
+    });
+
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    #[linkage = "weak"]
+    #[link_section = "__DATA,__objc_protolist,coalesced,no_dead_strip"]
+    #[export_name = #label_export_name]
+    #[used]
+    static #label_ident: &'static #objrs_root::__objrs::SyncHack<#objrs_root::__objrs::runtime::protocol_t> = &#protocol_ident;
+
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    #[linkage = "weak"]
+    #[link_section = "__DATA,__objc_protorefs,coalesced,no_dead_strip"]
+    #[export_name = #reference_export_name]
+    #[used]
+    static #reference_ident: &'static #objrs_root::__objrs::SyncHack<#objrs_root::__objrs::runtime::protocol_t> = &#protocol_ident;
+  };
+}
+
 pub fn transform_protocol(protocol: Protocol) -> Result<TokenStream, Diagnostic> {
   let objrs_root = &protocol.objrs;
+  let definition;
+  if protocol.force_extern {
+    definition = extern_protocol(&protocol);
+  } else {
+    require_apple_runtime(protocol.item.ident.span(), AppleOnly::Protocol)?;
+    definition = custom_protocol(&protocol);
+  }
+
   let mut id_items = TokenStream::new();
   let mut item = protocol.item;
 
@@ -103,23 +418,6 @@ pub fn transform_protocol(protocol: Protocol) -> Result<TokenStream, Diagnostic>
     transgen_and_extend(method, objrs_root, &mut item.items);
   }
 
-  // TODO: are these needed?
-  // 	.private_extern	l_OBJC_LABEL_PROTOCOL_$_Foo ## @"\01l_OBJC_LABEL_PROTOCOL_$_Foo"
-  // 	.section	__DATA,__objc_protolist,coalesced,no_dead_strip
-  // 	.globl	l_OBJC_LABEL_PROTOCOL_$_Foo
-  // 	.weak_definition	l_OBJC_LABEL_PROTOCOL_$_Foo
-  // 	.p2align	3
-  // l_OBJC_LABEL_PROTOCOL_$_Foo:
-  // 	.quad	l_OBJC_PROTOCOL_$_Foo
-
-  // 	.private_extern	l_OBJC_PROTOCOL_REFERENCE_$_Foo ## @"\01l_OBJC_PROTOCOL_REFERENCE_$_Foo"
-  // 	.section	__DATA,__objc_protorefs,coalesced,no_dead_strip
-  // 	.globl	l_OBJC_PROTOCOL_REFERENCE_$_Foo
-  // 	.weak_definition	l_OBJC_PROTOCOL_REFERENCE_$_Foo
-  // 	.p2align	3
-  // l_OBJC_PROTOCOL_REFERENCE_$_Foo:
-  // 	.quad	l_OBJC_PROTOCOL_$_Foo
-
   let ident = &item.ident;
   let (_, ty_generics, where_clause) = item.generics.split_for_impl();
 
@@ -135,6 +433,8 @@ pub fn transform_protocol(protocol: Protocol) -> Result<TokenStream, Diagnostic>
 
     #item
 
+    #definition
+
     // TODO: this is broken by https://github.com/rust-lang/rfcs/blob/master/text/0255-object-safety.md
     // unsafe impl #impl_generics #objrs_root::marker::Protocol for #ident #ty_generics #where_clause {}
 
@@ -150,3 +450,285 @@ pub fn transform_protocol(protocol: Protocol) -> Result<TokenStream, Diagnostic>
   };
   return Ok(tokens);
 }
+
+#[cfg(test)]
+mod tests {
+  extern crate objrs_test_utils;
+
+  use super::*;
+  use crate::parse::protocol_attr::ProtocolAttr;
+  use objrs_test_utils::assert_tokens_eq;
+  use quote::ToTokens;
+  use syn::{parse2, ItemTrait};
+
+  fn make_protocol(tokens: TokenStream) -> Protocol {
+    let mut item = parse2::<ItemTrait>(tokens).unwrap();
+    let objrs_attr = item.attrs.remove(0);
+    assert!(objrs_attr.path.is_ident("objrs"));
+    let protocol_attr = objrs_attr.parse_args::<ProtocolAttr>().unwrap();
+    return Protocol::new(protocol_attr, item.into_token_stream()).unwrap();
+  }
+
+  #[test]
+  fn extern_protocol_is_a_placeholder() {
+    let protocol = make_protocol(quote! {
+      #[objrs(protocol, name = "NSCopying")]
+      #[link(name = "Foundation", kind = "framework")]
+      trait Copying {}
+    });
+    assert!(protocol.force_extern);
+
+    let actual = extern_protocol(&protocol);
+    let expected = quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__data"]
+      #[export_name = "\u{1}l_OBJC_PROTOCOL_$_NSCopying"]
+      static __objrs_protocol_NSCopying: objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = objrs::__objrs::SyncHack(objrs::__objrs::runtime::protocol_t {
+        isa: 0 as *mut _,
+        mangled_name: {
+          #[link_section = "__TEXT,__objc_classname,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_CLASS_NAME_.__objrs_protocol.NSCopying"]
+          static PROTOCOL_NAME: [objrs::__objrs::u8; 10usize] = *b"NSCopying\0";
+          &PROTOCOL_NAME
+        } as *const _ as *const _,
+        protocols: 0 as *mut _,
+        instance_methods: 0 as *mut _,
+        class_methods: 0 as *mut _,
+        optional_instance_methods: 0 as *mut _,
+        optional_class_methods: 0 as *mut _,
+        instance_properties: 0 as *mut _,
+        size: objrs::__objrs::core::mem::size_of::<objrs::__objrs::runtime::protocol_t>() as objrs::__objrs::u32,
+        flags: 0,
+        extended_method_types: 0 as *mut _,
+        demangled_name: 0 as *const _,
+        class_properties: 0 as *mut _,
+      });
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn empty_custom_protocol() {
+    let protocol = make_protocol(quote! {
+      #[objrs(protocol)]
+      trait MyDelegate {}
+    });
+    assert!(!protocol.force_extern);
+
+    let actual = custom_protocol(&protocol);
+    let expected = quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      static __objrs_protocol_props_MyDelegate: () = ();
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      static __objrs_protocol_class_props_MyDelegate: () = ();
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__data"]
+      #[export_name = "\u{1}l_OBJC_PROTOCOL_$_MyDelegate"]
+      static __objrs_protocol_MyDelegate: objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = objrs::__objrs::SyncHack(objrs::__objrs::runtime::protocol_t {
+        isa: 0 as *mut _,
+        mangled_name: {
+          #[link_section = "__TEXT,__objc_classname,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_CLASS_NAME_.__objrs_protocol.MyDelegate"]
+          static PROTOCOL_NAME: [objrs::__objrs::u8; 11usize] = *b"MyDelegate\0";
+          &PROTOCOL_NAME
+        } as *const _ as *const _,
+        protocols: 0 as *mut _,
+        instance_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        class_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        optional_instance_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        optional_class_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        instance_properties: 0 as *mut _,
+        size: objrs::__objrs::core::mem::size_of::<objrs::__objrs::runtime::protocol_t>() as objrs::__objrs::u32,
+        flags: 0,
+        extended_method_types: 0 as *mut _,
+        demangled_name: 0 as *const _,
+        class_properties: 0 as *mut _,
+      });
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__objc_protolist,coalesced,no_dead_strip"]
+      #[export_name = "\u{1}l_OBJC_LABEL_PROTOCOL_$_MyDelegate"]
+      #[used]
+      static __objrs_protocol_label_MyDelegate: &'static objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = &__objrs_protocol_MyDelegate;
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__objc_protorefs,coalesced,no_dead_strip"]
+      #[export_name = "\u{1}l_OBJC_PROTOCOL_REFERENCE_$_MyDelegate"]
+      #[used]
+      static __objrs_protocol_ref_MyDelegate: &'static objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = &__objrs_protocol_MyDelegate;
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn custom_protocol_metadata() {
+    let protocol = make_protocol(quote! {
+      #[objrs(protocol, property(title: Option<Strong<NSString>>, readonly, copy, nonatomic))]
+      trait MyDelegate {
+        #[objrs(selector = "title")]
+        fn title(&self) -> Option<Strong<NSString>>;
+      }
+    });
+
+    // Protocols' property lists are weak and don't share their names with classes' property lists.
+    // The extended method types use the extended type encodings (which include class names).
+    let actual = custom_protocol(&protocol);
+    let expected = quote! {
+      #[doc(hidden)]
+      #[allow(non_camel_case_types)]
+      #[repr(C)]
+      struct __objrs_protocol_props_MyDelegate {
+        entsize_and_flags: objrs::__objrs::u32,
+        count: objrs::__objrs::u32,
+        properties: [objrs::__objrs::runtime::property_t; 1usize],
+      }
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__objc_const"]
+      #[export_name = "\u{1}l_OBJC_$_PROP_LIST_.__objrs_protocol.MyDelegate"]
+      static __objrs_protocol_props_MyDelegate: __objrs_protocol_props_MyDelegate = {
+        unsafe impl objrs::__objrs::core::marker::Sync for __objrs_protocol_props_MyDelegate {}
+        __objrs_protocol_props_MyDelegate {
+          entsize_and_flags: objrs::__objrs::core::mem::size_of::<objrs::__objrs::runtime::property_t>() as objrs::__objrs::u32,
+          count: 1usize as objrs::__objrs::u32,
+          properties: [objrs::__objrs::runtime::property_t {
+            name: {
+              #[link_section = "__TEXT,__cstring,cstring_literals"]
+              #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop.@protocol(MyDelegate)::title"]
+              static PROP_NAME: [objrs::__objrs::u8; 6usize] = *b"title\0";
+              &PROP_NAME as *const _ as *const _
+            },
+            attributes: {
+              #[link_section = "__TEXT,__cstring,cstring_literals"]
+              #[export_name = "\u{1}L_OBJC_PROP_NAME_ATTR_.__objrs_prop_attr.@protocol(MyDelegate)::title"]
+              static PROP_ATTRIBUTES: objrs::__objrs::Packed3<objrs::__objrs::u8, [objrs::__objrs::u8; 11usize], [objrs::__objrs::u8; 7usize]> = objrs::__objrs::Packed3(b'T', *b"@\"NSString\"", *b",R,C,N\0");
+              &PROP_ATTRIBUTES as *const _ as *const _
+            },
+          },],
+        }
+      };
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      static __objrs_protocol_class_props_MyDelegate: () = ();
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__data"]
+      #[export_name = "\u{1}l_OBJC_PROTOCOL_$_MyDelegate"]
+      static __objrs_protocol_MyDelegate: objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = objrs::__objrs::SyncHack(objrs::__objrs::runtime::protocol_t {
+        isa: 0 as *mut _,
+        mangled_name: {
+          #[link_section = "__TEXT,__objc_classname,cstring_literals"]
+          #[export_name = "\u{1}L_OBJC_CLASS_NAME_.__objrs_protocol.MyDelegate"]
+          static PROTOCOL_NAME: [objrs::__objrs::u8; 11usize] = *b"MyDelegate\0";
+          &PROTOCOL_NAME
+        } as *const _ as *const _,
+        protocols: 0 as *mut _,
+        instance_methods: {
+          #[repr(C)]
+          struct MethodList {
+            entsize_and_flags: objrs::__objrs::u32,
+            count: objrs::__objrs::u32,
+            methods: [objrs::__objrs::runtime::method_t; 1usize],
+          }
+          #[link_section = "__DATA,__objc_const"]
+          #[export_name = "\u{1}l_OBJC_$_PROTOCOL_INSTANCE_METHODS_MyDelegate"]
+          static METHOD_LIST: objrs::__objrs::SyncHack<MethodList> = objrs::__objrs::SyncHack(MethodList {
+            entsize_and_flags: objrs::__objrs::core::mem::size_of::<objrs::__objrs::runtime::method_t>() as objrs::__objrs::u32,
+            count: 1usize as objrs::__objrs::u32,
+            methods: [objrs::__objrs::runtime::method_t {
+              name: {
+                #[link_section = "__TEXT,__objc_methname,cstring_literals"]
+                #[export_name = "\u{1}L_OBJC_METH_VAR_NAME_.__objrs_meth.@protocol(MyDelegate)::__objrs_msg_recv_title"]
+                static METH_VAR_NAME: [objrs::__objrs::u8; 6usize] = *b"title\0";
+                unsafe { objrs::__objrs::TransmuteHack::<_, *mut objrs::Sel> { from: &METH_VAR_NAME }.to }
+              },
+              types: {
+                const SIZE_OF_USIZE: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<objrs::__objrs::usize>();
+                const ARGS_1_LEN: objrs::__objrs::usize = 4;
+                const ARGS_1: [objrs::__objrs::u8; ARGS_1_LEN] = [b'@', b'0', b':', b'0' + SIZE_OF_USIZE as objrs::__objrs::u8];
+                struct FrameSize;
+                impl objrs::__objrs::ToAsciiHack for FrameSize {
+                  const VALUE: objrs::__objrs::usize = SIZE_OF_USIZE * 2;
+                }
+                const RET_LEN: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<<*mut objrs::__objrs::runtime::Id as objrs::__objrs::TypeEncodingHack>::Type>() + <FrameSize as objrs::__objrs::ToAsciiHack>::LEN;
+                const RET: [objrs::__objrs::u8; RET_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed2(<*mut objrs::__objrs::runtime::Id as objrs::__objrs::TypeEncodingHack>::BYTES, <FrameSize as objrs::__objrs::ToAsciiHack>::STR) }.to };
+                const METHOD_TYPE_LEN: objrs::__objrs::usize = RET_LEN + ARGS_1_LEN + 1;
+                const METHOD_TYPE: [objrs::__objrs::u8; METHOD_TYPE_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed3(RET, ARGS_1, 0u8) }.to };
+                #[link_section = "__TEXT,__objc_methtype,cstring_literals"]
+                #[export_name = "\u{1}L_OBJC_METH_VAR_TYPE_.__objrs_meth.@protocol(MyDelegate)::__objrs_msg_recv_title"]
+                static METH_VAR_TYPE: [objrs::__objrs::u8; METHOD_TYPE_LEN] = METHOD_TYPE;
+                &METH_VAR_TYPE
+              } as *const _ as *const _,
+              imp: 0 as *mut _,
+            },],
+          });
+          unsafe { objrs::__objrs::TransmuteHack::<_, *mut objrs::__objrs::runtime::method_list_t> { from: &METHOD_LIST }.to }
+        },
+        class_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        optional_instance_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        optional_class_methods: 0 as *mut objrs::__objrs::runtime::method_list_t,
+        instance_properties: &__objrs_protocol_props_MyDelegate as *const _ as *mut _,
+        size: objrs::__objrs::core::mem::size_of::<objrs::__objrs::runtime::protocol_t>() as objrs::__objrs::u32,
+        flags: 0,
+        extended_method_types: {
+          #[link_section = "__DATA,__objc_const"]
+          #[export_name = "\u{1}l_OBJC_$_PROTOCOL_METHOD_TYPES_MyDelegate"]
+          static METHOD_TYPES: objrs::__objrs::SyncHack<[*const objrs::__objrs::u8; 1usize]> = objrs::__objrs::SyncHack([{
+            const SIZE_OF_USIZE: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<objrs::__objrs::usize>();
+            const ARGS_1_LEN: objrs::__objrs::usize = 4;
+            const ARGS_1: [objrs::__objrs::u8; ARGS_1_LEN] = [b'@', b'0', b':', b'0' + SIZE_OF_USIZE as objrs::__objrs::u8];
+            struct FrameSize;
+            impl objrs::__objrs::ToAsciiHack for FrameSize {
+              const VALUE: objrs::__objrs::usize = SIZE_OF_USIZE * 2;
+            }
+            const RET_LEN: objrs::__objrs::usize = objrs::__objrs::core::mem::size_of::<<Option<Strong<NSString> > as objrs::__objrs::ExtendedTypeEncodingHack>::Type>() + <FrameSize as objrs::__objrs::ToAsciiHack>::LEN;
+            const RET: [objrs::__objrs::u8; RET_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed2(<Option<Strong<NSString> > as objrs::__objrs::ExtendedTypeEncodingHack>::BYTES, <FrameSize as objrs::__objrs::ToAsciiHack>::STR) }.to };
+            const METHOD_TYPE_LEN: objrs::__objrs::usize = RET_LEN + ARGS_1_LEN + 1;
+            const METHOD_TYPE: [objrs::__objrs::u8; METHOD_TYPE_LEN] = unsafe { objrs::__objrs::TransmuteHack { from: objrs::__objrs::Packed3(RET, ARGS_1, 0u8) }.to };
+            #[link_section = "__TEXT,__objc_methtype,cstring_literals"]
+            #[export_name = "\u{1}L_OBJC_METH_VAR_TYPE_.__objrs_meth.@protocol(MyDelegate).extended::__objrs_msg_recv_title"]
+            static METH_VAR_TYPE: [objrs::__objrs::u8; METHOD_TYPE_LEN] = METHOD_TYPE;
+            &METH_VAR_TYPE
+          } as *const _ as *const _,]);
+          unsafe { objrs::__objrs::TransmuteHack::<_, *mut *const _> { from: &METHOD_TYPES }.to }
+        },
+        demangled_name: 0 as *const _,
+        class_properties: 0 as *mut _,
+      });
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__objc_protolist,coalesced,no_dead_strip"]
+      #[export_name = "\u{1}l_OBJC_LABEL_PROTOCOL_$_MyDelegate"]
+      #[used]
+      static __objrs_protocol_label_MyDelegate: &'static objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = &__objrs_protocol_MyDelegate;
+
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      #[linkage = "weak"]
+      #[link_section = "__DATA,__objc_protorefs,coalesced,no_dead_strip"]
+      #[export_name = "\u{1}l_OBJC_PROTOCOL_REFERENCE_$_MyDelegate"]
+      #[used]
+      static __objrs_protocol_ref_MyDelegate: &'static objrs::__objrs::SyncHack<objrs::__objrs::runtime::protocol_t> = &__objrs_protocol_MyDelegate;
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+}
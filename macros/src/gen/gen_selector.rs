@@ -14,6 +14,8 @@ use crate::gen::class_ref::{
 };
 use crate::gen::ivar::transform_ivars;
 use crate::gen::sel_ref::gen_sel_ref;
+use crate::parse::catch_panic::CatchPanic;
+use crate::parse::method_family::{is_pool_arg, returns_autoreleased_owned, MethodFamily};
 use crate::parse::sel_ref_attr::SelRef;
 use crate::parse::selector_attr::Method;
 use crate::util::{priv_ident, priv_ident_at, DrainExt};
@@ -55,11 +57,9 @@ fn make_extern_c_abi(abi: &mut Option<Abi>) {
 pub fn gen_msg_recv_sig(
   sig: &Signature,
   is_instance_method: bool,
+  family: MethodFamily,
   objrs_root: &Ident,
 ) -> Signature {
-  // TODO: let objc_method_family to be explicitly specified?
-  // TODO: take the method family into account.
-
   let mut sig = sig.clone();
   sig.ident = priv_ident_at(
     &["__objrs_msg_recv_", sig.ident.to_string().as_ref()].concat(),
@@ -67,6 +67,9 @@ pub fn gen_msg_recv_sig(
   );
   make_extern_c_abi(&mut sig.abi);
 
+  // Objective-C doesn't pass the autorelease pool (see parse::method_family::pool_arg).
+  sig.inputs = sig.inputs.into_iter().filter(|arg| !is_pool_arg(arg)).collect();
+
   if !is_instance_method {
     sig
       .inputs
@@ -75,6 +78,12 @@ pub fn gen_msg_recv_sig(
   // TODO: consider allowing the selector to be accessed like in Objective-C (via _cmd).
   sig.inputs.insert(1, parse_quote!(_: &'static #objrs_root::Sel));
 
+  // The selector returns an autoreleased (+0) object, but the method returns an owned one. The
+  // implementation returns a raw pointer from objc_autoreleaseReturnValue instead (see gen_msg_recv).
+  if returns_autoreleased_owned(family, &sig.output) {
+    sig.output = parse_quote!(-> *mut #objrs_root::__objrs::runtime::Id);
+  }
+
   return sig;
 }
 
@@ -93,22 +102,24 @@ pub fn gen_msg_recv(
 
   let is_instance_method = method.is_instance_method;
   let selector = &method.attr.sel;
+  let family = method.attr.family();
+  let catch_panic = method.attr.catch_panic();
+  let output = &method.sig().output;
 
   let mut method = ImplItemMethod {
     attrs: method.attrs().clone(),
     vis: Visibility::Inherited,
     defaultness: None,
-    sig: gen_msg_recv_sig(method.sig(), is_instance_method, objrs_root),
+    sig: gen_msg_recv_sig(method.sig(), is_instance_method, family, objrs_root),
     block: method.block().cloned().unwrap_or_else(|| parse_quote!({})),
   };
 
   filter_out_attrs(&mut method.attrs, &["doc", "export_name"]);
   method.attrs.push(parse_quote!(#[doc(hidden)]));
 
-  // Set the span of the export name to the selector string so it's clear where the problem is if
-  // there are duplicate symbol conflicts.
-  let export_name = &[
-    if is_instance_method { "\x01-[" } else { "\x01+[" },
+  // The method's name, e.g. "-[ClassName(CategoryName) doFoo:withBar:]".
+  let method_name = &[
+    if is_instance_method { "-[" } else { "+[" },
     class_name,
     if category.is_some() { "(" } else { "" },
     category.unwrap_or(""),
@@ -117,16 +128,137 @@ pub fn gen_msg_recv(
     "]",
   ]
   .concat();
-  let export_name = LitStr::new(export_name, selector.span());
+
+  // Set the span of the export name to the selector string so it's clear where the problem is if
+  // there are duplicate symbol conflicts.
+  let export_name = LitStr::new(&["\x01", method_name].concat(), selector.span());
   method.attrs.push(parse_quote!(#[export_name = #export_name]));
 
   if is_instance_method {
     transform_ivars(&mut method, objrs_root)?;
   }
 
+  // Call the body through a function that catches panics (so they don't unwind into Objective-C).
+  let catch_panic_fn = match catch_panic {
+    CatchPanic::None => None,
+    CatchPanic::Abort => Some(priv_ident("abort_on_panic")),
+    CatchPanic::Exception => Some(priv_ident("throw_on_panic")),
+  };
+  if let Some(catch_panic_fn) = catch_panic_fn {
+    let block = &method.block;
+    let ty = match output {
+      ReturnType::Type(_, ref ty) => ty.as_ref().clone(),
+      ReturnType::Default => parse_quote!(()),
+    };
+    let method_name = LitStr::new(method_name, selector.span());
+    method.block = parse_quote!({
+      #objrs_root::__objrs_catch_panic_requires_std!();
+      return #objrs_root::__objrs::#catch_panic_fn(#method_name, || -> #ty #block);
+    });
+  }
+  // The exception is thrown from (and so unwinds out of) the msg_recv function.
+  if catch_panic == CatchPanic::Exception {
+    method.attrs.push(parse_quote!(#[unwind(allowed)]));
+  }
+
+  // Return the owned object with objc_autoreleaseReturnValue so that ARC callers can skip the
+  // autorelease pool.
+  if returns_autoreleased_owned(family, output) {
+    let block = &method.block;
+    let ty = match output {
+      ReturnType::Type(_, ref ty) => ty,
+      ReturnType::Default => unreachable!("BUG: expected a return type"),
+    };
+    method.block = parse_quote!({
+      let ret: #ty = (|| -> #ty #block)();
+      #[allow(unused_unsafe)]
+      return unsafe { #objrs_root::__objrs::autorelease_return_value(ret) };
+    });
+  }
+
   return Ok(method);
 }
 
+// Generates the method's type encoding (e.g. "v24@0:8@16") from the `msg_recv` signature (i.e. the
+// signature returned by gen_msg_recv_sig, which includes the receiver and selector arguments). The
+// result is a sequence of items that defines `METHOD_TYPE_LEN` and a NUL-terminated
+// `METHOD_TYPE: [u8; METHOD_TYPE_LEN]`, and should be placed in a block.
+//
+// The encoding follows clang's ASTContext::getObjCEncodingForMethodDecl: the return type, the total
+// size of the arguments, and then each argument's type and frame offset. The receiver is encoded as
+// "@" (or "#" for class methods) at offset 0 and the selector as ":" at offset sizeof(void*).
+// Integer arguments smaller than an int are promoted to the size of an int.
+pub fn gen_method_type_encoding(
+  sig: &Signature,
+  is_instance_method: bool,
+  extended: bool,
+  objrs_root: &Ident,
+) -> TokenStream {
+  assert!(sig.inputs.len() >= 2, "BUG: selector methods should always have >= 2 arguments");
+
+  let native_ty = quote!(#objrs_root::__objrs);
+  // Extended type encodings (which protocols use) include the class names of object types.
+  let hack = if extended { quote!(ExtendedTypeEncodingHack) } else { quote!(TypeEncodingHack) };
+  let receiver_encoding = if is_instance_method { quote!(b'@') } else { quote!(b'#') };
+
+  let mut prev_args = priv_ident("ARGS_1");
+  let mut prev_args_len = priv_ident("ARGS_1_LEN");
+  let mut tokens = quote! {
+    const SIZE_OF_USIZE: #native_ty::usize = #native_ty::core::mem::size_of::<#native_ty::usize>();
+    const #prev_args_len: #native_ty::usize = 4;
+    const #prev_args: [#native_ty::u8; #prev_args_len] = [#receiver_encoding, b'0', b':', b'0' + SIZE_OF_USIZE as #native_ty::u8];
+  };
+
+  let mut frame_size = quote!(SIZE_OF_USIZE * 2);
+  for (i, input) in sig.inputs.iter().enumerate().skip(2) {
+    let ty = match input {
+      FnArg::Typed(ref pat_ty) => &pat_ty.ty,
+      _ => panic!("BUG: unexpected argument type"),
+    };
+
+    let args = priv_ident(&format!("ARGS_{}", i));
+    let args_len = priv_ident(&format!("ARGS_{}_LEN", i));
+    let frame_offset = priv_ident(&format!("FrameOffset{}", i));
+    tokens.extend(quote! {
+      struct #frame_offset;
+      impl #native_ty::ToAsciiHack for #frame_offset { const VALUE: #native_ty::usize = #frame_size; }
+      const #args_len: #native_ty::usize = #prev_args_len + #native_ty::core::mem::size_of::<<#ty as #native_ty::#hack>::Type>() + <#frame_offset as #native_ty::ToAsciiHack>::LEN;
+      const #args: [#native_ty::u8; #args_len] = unsafe { #native_ty::TransmuteHack { from: #native_ty::Packed3(#prev_args, <#ty as #native_ty::#hack>::BYTES, <#frame_offset as #native_ty::ToAsciiHack>::STR) }.to };
+    });
+
+    frame_size = quote! {
+      <#frame_offset as #native_ty::ToAsciiHack>::VALUE + #native_ty::promoted_arg_size(#native_ty::core::mem::size_of::<#ty>(), unsafe { #native_ty::TransmuteHack::<_, #native_ty::u8> { from: <#ty as #native_ty::#hack>::BYTES }.to })
+    };
+    prev_args = args;
+    prev_args_len = args_len;
+  }
+
+  let ret_encoding;
+  let ret_encoding_len;
+  match sig.output {
+    ReturnType::Default => {
+      ret_encoding = quote!([b'v']);
+      ret_encoding_len = quote!(1);
+    }
+    ReturnType::Type(_, ref ty) => {
+      ret_encoding = quote!(<#ty as #native_ty::#hack>::BYTES);
+      ret_encoding_len =
+        quote!(#native_ty::core::mem::size_of::<<#ty as #native_ty::#hack>::Type>());
+    }
+  }
+
+  tokens.extend(quote! {
+    struct FrameSize;
+    impl #native_ty::ToAsciiHack for FrameSize { const VALUE: #native_ty::usize = #frame_size; }
+    const RET_LEN: #native_ty::usize = #ret_encoding_len + <FrameSize as #native_ty::ToAsciiHack>::LEN;
+    const RET: [#native_ty::u8; RET_LEN] = unsafe { #native_ty::TransmuteHack { from: #native_ty::Packed2(#ret_encoding, <FrameSize as #native_ty::ToAsciiHack>::STR) }.to };
+    const METHOD_TYPE_LEN: #native_ty::usize = RET_LEN + #prev_args_len + 1;
+    const METHOD_TYPE: [#native_ty::u8; METHOD_TYPE_LEN] = unsafe { #native_ty::TransmuteHack { from: #native_ty::Packed3(RET, #prev_args, 0u8) }.to };
+  });
+
+  return tokens;
+}
+
 fn sel_ref_value(sel: LitStr, objrs_root: &Ident) -> Result<TokenStream, Diagnostic> {
   let sel_ref_ident = priv_ident("SEL_REF");
   let sel_string = sel.value();
@@ -135,6 +267,7 @@ fn sel_ref_value(sel: LitStr, objrs_root: &Ident) -> Result<TokenStream, Diagnos
     sel_string: sel_string,
     ident: sel_ref_ident.clone(),
     u8_ty: parse_quote!(#objrs_root::__objrs::u8),
+    objrs_root: quote!(#objrs_root),
   })?;
 
   return Ok(quote! {{
@@ -198,9 +331,6 @@ pub fn gen_msg_send_sig(
   is_instance_method: bool,
   objrs_root: &Ident,
 ) -> Signature {
-  // TODO: let objc_method_family to be explicitly specified?
-  // TODO: take the method family into account.
-
   let mut sig = sig.clone();
   sig.ident = priv_ident_at(
     &["__objrs_msg_send_", sig.ident.to_string().as_ref()].concat(),
@@ -208,6 +338,9 @@ pub fn gen_msg_send_sig(
   );
   make_extern_c_abi(&mut sig.abi);
 
+  // Objective-C doesn't pass the autorelease pool (see parse::method_family::pool_arg).
+  sig.inputs = sig.inputs.into_iter().filter(|arg| !is_pool_arg(arg)).collect();
+
   if !is_instance_method {
     // Inject a parameter that is compatible with *mut objrs::Class.
     sig.inputs.insert(0, parse_quote!(_: #objrs_root::__objrs::UninitPtr));
@@ -228,6 +361,7 @@ pub fn gen_msg_send(
   let call_super = method.attr.call_super;
   let is_instance_method = method.is_instance_method;
   let is_generic = is_generic_class || method.is_generic;
+  let claim_autoreleased = returns_autoreleased_owned(method.attr.family(), &method.sig().output);
   let mut method = ImplItemMethod {
     attrs: method.attrs().clone(),
     vis: method.vis().cloned().unwrap_or(Visibility::Inherited),
@@ -327,15 +461,29 @@ pub fn gen_msg_send(
     }};
   }
 
+  // If the selector returns an autoreleased (+0) object but the method returns an owned one, claim
+  // the result with objc_retainAutoreleasedReturnValue.
+  let send_output;
+  let send_result;
+  if claim_autoreleased {
+    send_output = quote!(-> *mut #objrs_root::__objrs::runtime::Id);
+    send_result = quote! {
+      #objrs_root::__objrs::retain_autoreleased_return_value(msg_send(this, sel as *const _, #tail_arg_values))
+    };
+  } else {
+    send_output = quote!(#output);
+    send_result = quote!(msg_send(this, sel as *const _, #tail_arg_values));
+  }
+
   let fn_body = quote! {
     let (this, sel) = #ref_hack;
 
     let msg_send: unsafe extern "C" fn(#self_arg_type,
                                        *const #objrs_root::Sel,
-                                       #tail_arg_types) #output;
+                                       #tail_arg_types) #send_output;
     msg_send = unsafe { #objrs_root::__objrs::core::mem::transmute(#msg_send as *const ()) };
 
-    return unsafe { msg_send(this, sel as *const _, #tail_arg_values) };
+    return unsafe { #send_result };
   };
   method.block.stmts.push(Stmt::Item(Item::Verbatim(fn_body)));
 
@@ -997,4 +1145,122 @@ mod tests {
     };
     assert_tokens_eq!(msg_recv, expected);
   }
+
+  #[test]
+  fn gen_msg_recv_autorelease_return() {
+    let method = make_method(quote! {
+      #[objrs(selector = "description")]
+      fn description(&self) -> Option<Strong<NSString>> {
+        return None;
+      }
+    });
+    let msg_recv = gen_msg_recv(&method, "ClassName", None, &parse_quote!(__objrs_root)).unwrap();
+
+    let expected = quote! {
+      #[doc(hidden)]
+      #[export_name = "\u{1}-[ClassName description]"]
+      extern "C" fn __objrs_msg_recv_description(
+        &self,
+        _: &'static __objrs_root::Sel
+      ) -> *mut __objrs_root::__objrs::runtime::Id {
+        let ret: Option<Strong<NSString> > = (| | -> Option<Strong<NSString> > {
+          return None;
+        })();
+        #[allow(unused_unsafe)]
+        return unsafe { __objrs_root::__objrs::autorelease_return_value(ret) };
+      }
+    };
+    assert_tokens_eq!(msg_recv, expected);
+  }
+
+  #[test]
+  fn gen_msg_recv_catch_panic() {
+    let method = make_method(quote! {
+      #[objrs(selector = "countForKey:", catch_panic = exception)]
+      fn count(key: u32) -> usize {
+        return lookup(key);
+      }
+    });
+    let msg_recv =
+      gen_msg_recv(&method, "ClassName", Some("Cat"), &parse_quote!(__objrs_root)).unwrap();
+
+    let expected = quote! {
+      #[doc(hidden)]
+      #[export_name = "\u{1}+[ClassName(Cat) countForKey:]"]
+      #[unwind(allowed)]
+      extern "C" fn __objrs_msg_recv_count(
+        _: __objrs_root::__objrs::core::ptr::NonNull<__objrs_root::Class>,
+        _: &'static __objrs_root::Sel,
+        key: u32
+      ) -> usize {
+        __objrs_root::__objrs_catch_panic_requires_std!();
+        return __objrs_root::__objrs::throw_on_panic("+[ClassName(Cat) countForKey:]", | | -> usize {
+          return lookup(key);
+        });
+      }
+    };
+    assert_tokens_eq!(msg_recv, expected);
+
+    let method = make_method(quote! {
+      #[objrs(selector = "description", catch_panic)]
+      fn description(&self) -> Option<Strong<NSString>> {
+        return None;
+      }
+    });
+    let msg_recv = gen_msg_recv(&method, "ClassName", None, &parse_quote!(__objrs_root)).unwrap();
+
+    let expected = quote! {
+      #[doc(hidden)]
+      #[export_name = "\u{1}-[ClassName description]"]
+      extern "C" fn __objrs_msg_recv_description(
+        &self,
+        _: &'static __objrs_root::Sel
+      ) -> *mut __objrs_root::__objrs::runtime::Id {
+        let ret: Option<Strong<NSString> > = (| | -> Option<Strong<NSString> > {
+          __objrs_root::__objrs_catch_panic_requires_std!();
+          return __objrs_root::__objrs::abort_on_panic(
+            "-[ClassName description]",
+            | | -> Option<Strong<NSString> > {
+              return None;
+            }
+          );
+        })();
+        #[allow(unused_unsafe)]
+        return unsafe { __objrs_root::__objrs::autorelease_return_value(ret) };
+      }
+    };
+    assert_tokens_eq!(msg_recv, expected);
+  }
+
+  #[test]
+  fn method_type_encoding() {
+    let sig: Signature = parse_quote! {
+      extern "C" fn __objrs_msg_recv_foo_bar(&self, _: &'static __objrs_root::Sel, arg1: u8, arg2: f64) -> bool
+    };
+    let actual = gen_method_type_encoding(&sig, true, false, &parse_quote!(__objrs_root));
+
+    let expected = quote! {
+      const SIZE_OF_USIZE: __objrs_root::__objrs::usize = __objrs_root::__objrs::core::mem::size_of::<__objrs_root::__objrs::usize>();
+      const ARGS_1_LEN: __objrs_root::__objrs::usize = 4;
+      const ARGS_1: [__objrs_root::__objrs::u8; ARGS_1_LEN] = [b'@', b'0', b':', b'0' + SIZE_OF_USIZE as __objrs_root::__objrs::u8];
+
+      struct FrameOffset2;
+      impl __objrs_root::__objrs::ToAsciiHack for FrameOffset2 { const VALUE: __objrs_root::__objrs::usize = SIZE_OF_USIZE * 2; }
+      const ARGS_2_LEN: __objrs_root::__objrs::usize = ARGS_1_LEN + __objrs_root::__objrs::core::mem::size_of::<<u8 as __objrs_root::__objrs::TypeEncodingHack>::Type>() + <FrameOffset2 as __objrs_root::__objrs::ToAsciiHack>::LEN;
+      const ARGS_2: [__objrs_root::__objrs::u8; ARGS_2_LEN] = unsafe { __objrs_root::__objrs::TransmuteHack { from: __objrs_root::__objrs::Packed3(ARGS_1, <u8 as __objrs_root::__objrs::TypeEncodingHack>::BYTES, <FrameOffset2 as __objrs_root::__objrs::ToAsciiHack>::STR) }.to };
+
+      struct FrameOffset3;
+      impl __objrs_root::__objrs::ToAsciiHack for FrameOffset3 { const VALUE: __objrs_root::__objrs::usize = <FrameOffset2 as __objrs_root::__objrs::ToAsciiHack>::VALUE + __objrs_root::__objrs::promoted_arg_size(__objrs_root::__objrs::core::mem::size_of::<u8>(), unsafe { __objrs_root::__objrs::TransmuteHack::<_, __objrs_root::__objrs::u8> { from: <u8 as __objrs_root::__objrs::TypeEncodingHack>::BYTES }.to }); }
+      const ARGS_3_LEN: __objrs_root::__objrs::usize = ARGS_2_LEN + __objrs_root::__objrs::core::mem::size_of::<<f64 as __objrs_root::__objrs::TypeEncodingHack>::Type>() + <FrameOffset3 as __objrs_root::__objrs::ToAsciiHack>::LEN;
+      const ARGS_3: [__objrs_root::__objrs::u8; ARGS_3_LEN] = unsafe { __objrs_root::__objrs::TransmuteHack { from: __objrs_root::__objrs::Packed3(ARGS_2, <f64 as __objrs_root::__objrs::TypeEncodingHack>::BYTES, <FrameOffset3 as __objrs_root::__objrs::ToAsciiHack>::STR) }.to };
+
+      struct FrameSize;
+      impl __objrs_root::__objrs::ToAsciiHack for FrameSize { const VALUE: __objrs_root::__objrs::usize = <FrameOffset3 as __objrs_root::__objrs::ToAsciiHack>::VALUE + __objrs_root::__objrs::promoted_arg_size(__objrs_root::__objrs::core::mem::size_of::<f64>(), unsafe { __objrs_root::__objrs::TransmuteHack::<_, __objrs_root::__objrs::u8> { from: <f64 as __objrs_root::__objrs::TypeEncodingHack>::BYTES }.to }); }
+      const RET_LEN: __objrs_root::__objrs::usize = __objrs_root::__objrs::core::mem::size_of::<<bool as __objrs_root::__objrs::TypeEncodingHack>::Type>() + <FrameSize as __objrs_root::__objrs::ToAsciiHack>::LEN;
+      const RET: [__objrs_root::__objrs::u8; RET_LEN] = unsafe { __objrs_root::__objrs::TransmuteHack { from: __objrs_root::__objrs::Packed2(<bool as __objrs_root::__objrs::TypeEncodingHack>::BYTES, <FrameSize as __objrs_root::__objrs::ToAsciiHack>::STR) }.to };
+      const METHOD_TYPE_LEN: __objrs_root::__objrs::usize = RET_LEN + ARGS_3_LEN + 1;
+      const METHOD_TYPE: [__objrs_root::__objrs::u8; METHOD_TYPE_LEN] = unsafe { __objrs_root::__objrs::TransmuteHack { from: __objrs_root::__objrs::Packed3(RET, ARGS_3, 0u8) }.to };
+    };
+    assert_tokens_eq!(actual, expected);
+  }
 }
@@ -0,0 +1,162 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use syn::{Ident, ItemStruct, Type};
+
+// Generates a `*const u8` constant for a class_ro_t's ivar layout. The layout itself is computed by
+// `__objrs::ivar_layout` (at compile time), since the macro doesn't know the ivars' offsets or
+// sizes. Ivars are included in the layout if `<T as #layout_trait>::VALUE` is true (i.e., `IsStrong`
+// for the ivar layout and `IsWeak` for the weak ivar layout). The constant is nil if no ivars are
+// included.
+//
+// `original_item` must be the class's ivar struct (with the same field order as `fields`).
+pub fn ivar_layout(
+  ident: &Ident,
+  layout_trait: TokenStream,
+  class_ty: &Ident,
+  original_item: &ItemStruct,
+  fields: &[(TokenTree, Type)],
+  objrs_root: &Ident,
+) -> TokenStream {
+  let native_ty = quote!(#objrs_root::__objrs);
+  if fields.is_empty() {
+    return quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      const #ident: *const #native_ty::u8 = 0 as *const _;
+    };
+  }
+
+  let original_item_ident = &original_item.ident;
+  let mut entries = TokenStream::new();
+  for (field_ident, field_ty) in fields {
+    entries.extend(quote! {
+      (
+        INSTANCE_START + unsafe {
+          extern crate std;
+          let mut uninit = std::mem::MaybeUninit::<#original_item_ident>::uninit();
+          let base_ptr: *mut #original_item_ident = &raw mut uninit as *mut #original_item_ident;
+          let field_ptr = &raw mut (*base_ptr).#field_ident as *mut u8;
+          let offset = field_ptr.offset_from(base_ptr as *mut u8);
+          offset as usize
+        },
+        #native_ty::core::mem::size_of::<#field_ty>(),
+        <#field_ty as #layout_trait>::VALUE,
+      ),
+    });
+  }
+
+  let count = fields.len();
+  return quote! {
+    #[doc(hidden)]
+    #[allow(non_upper_case_globals)]
+    const #ident: *const #native_ty::u8 = {
+      #original_item
+      const INSTANCE_START: #native_ty::usize = <#class_ty as #native_ty::runtime::__objrs::Class>::INSTANCE_START;
+      const IVARS: [#native_ty::IvarLayoutEntry; #count] = [#entries];
+      const LEN: #native_ty::usize = #native_ty::ivar_layout::<0>(INSTANCE_START, &IVARS).len;
+      const LAYOUT: [#native_ty::u8; LEN + 1] = #native_ty::ivar_layout::<{ LEN + 1 }>(INSTANCE_START, &IVARS).bytes;
+      [&LAYOUT as *const _ as *const #native_ty::u8, 0 as *const _][(LEN == 0) as #native_ty::usize]
+    };
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate objrs_test_utils;
+
+  use super::*;
+  use objrs_test_utils::assert_tokens_eq;
+  use proc_macro2::{Literal, Span};
+  use syn::parse_quote;
+
+  #[test]
+  fn no_fields() {
+    let original_item: ItemStruct = parse_quote!(struct __objrs_original_Foo;);
+    let actual = ivar_layout(
+      &parse_quote!(__objrs_weak_ivar_layout_Foo),
+      quote!(__objrs_root::__objrs::IsWeak),
+      &parse_quote!(Foo),
+      &original_item,
+      &[],
+      &Ident::new("__objrs_root", Span::call_site()),
+    );
+    let expected = quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_weak_ivar_layout_Foo: *const __objrs_root::__objrs::u8 = 0 as *const _;
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn fields() {
+    let original_item: ItemStruct = parse_quote! {
+      struct __objrs_original_Foo {
+        weak: Weak<Bar>,
+        count: usize,
+      }
+    };
+    let fields = [
+      (TokenTree::from(Ident::new("weak", Span::call_site())), parse_quote!(Weak<Bar>)),
+      (TokenTree::from(Literal::usize_unsuffixed(1)), parse_quote!(usize)),
+    ];
+    let actual = ivar_layout(
+      &parse_quote!(__objrs_weak_ivar_layout_Foo),
+      quote!(__objrs_root::__objrs::IsWeak),
+      &parse_quote!(Foo),
+      &original_item,
+      &fields,
+      &Ident::new("__objrs_root", Span::call_site()),
+    );
+    let expected = quote! {
+      #[doc(hidden)]
+      #[allow(non_upper_case_globals)]
+      const __objrs_weak_ivar_layout_Foo: *const __objrs_root::__objrs::u8 = {
+        struct __objrs_original_Foo {
+          weak: Weak<Bar>,
+          count: usize,
+        }
+        const INSTANCE_START: __objrs_root::__objrs::usize = <Foo as __objrs_root::__objrs::runtime::__objrs::Class>::INSTANCE_START;
+        const IVARS: [__objrs_root::__objrs::IvarLayoutEntry; 2usize] = [
+          (
+            INSTANCE_START + unsafe {
+              extern crate std;
+              let mut uninit = std::mem::MaybeUninit::<__objrs_original_Foo>::uninit();
+              let base_ptr: *mut __objrs_original_Foo = &raw mut uninit as *mut __objrs_original_Foo;
+              let field_ptr = &raw mut (*base_ptr).weak as *mut u8;
+              let offset = field_ptr.offset_from(base_ptr as *mut u8);
+              offset as usize
+            },
+            __objrs_root::__objrs::core::mem::size_of::<Weak<Bar> >(),
+            <Weak<Bar> as __objrs_root::__objrs::IsWeak>::VALUE,
+          ),
+          (
+            INSTANCE_START + unsafe {
+              extern crate std;
+              let mut uninit = std::mem::MaybeUninit::<__objrs_original_Foo>::uninit();
+              let base_ptr: *mut __objrs_original_Foo = &raw mut uninit as *mut __objrs_original_Foo;
+              let field_ptr = &raw mut (*base_ptr).1 as *mut u8;
+              let offset = field_ptr.offset_from(base_ptr as *mut u8);
+              offset as usize
+            },
+            __objrs_root::__objrs::core::mem::size_of::<usize>(),
+            <usize as __objrs_root::__objrs::IsWeak>::VALUE,
+          ),
+        ];
+        const LEN: __objrs_root::__objrs::usize = __objrs_root::__objrs::ivar_layout::<0>(INSTANCE_START, &IVARS).len;
+        const LAYOUT: [__objrs_root::__objrs::u8; LEN + 1] = __objrs_root::__objrs::ivar_layout::<{ LEN + 1 }>(INSTANCE_START, &IVARS).bytes;
+        [&LAYOUT as *const _ as *const __objrs_root::__objrs::u8, 0 as *const _][(LEN == 0) as __objrs_root::__objrs::usize]
+      };
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+}
@@ -7,7 +7,11 @@ pub mod class_ref;
 mod field_offsets;
 pub mod gen_class;
 mod gen_impl;
+pub mod gen_property;
 pub mod gen_protocol;
 pub mod gen_selector;
 pub mod ivar;
+pub mod ivar_layout;
+pub mod protocol_list;
 pub mod sel_ref;
+pub mod type_encoding;
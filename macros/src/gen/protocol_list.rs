@@ -0,0 +1,175 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Each `impl Protocol for Class` block is expanded by a separate macro invocation, and they can't
+// see each other, so no single invocation can emit a class's complete protocol_list_t. Instead:
+//
+// - For custom classes, #[objrs(class)] defines an (initially empty) `ClassProtocolList` that the
+//   class's class_ro_t's base_protocols points to, and each impl block adds its protocol to that
+//   list from a constructor in `__DATA,__mod_init_func`. The runtime only reads base_protocols
+//   through the pointer when it's queried (e.g., by `conformsToProtocol:`), and an image's
+//   constructors run before any code that can query its classes.
+// - Extern classes' protocol lists can't be modified, so the impl block's category gets a
+//   one-entry protocol list instead (like clang does for `@interface Class (Category) <Protocol>`).
+//   The runtime merges a category's protocols into its class's protocol list when it attaches the
+//   category.
+//
+// Both are emitted for every impl block. The category's list is only used if the class is extern,
+// and the constructor does nothing if the class doesn't have a `ClassProtocolList`.
+
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use crate::util::priv_ident;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Ident, Type};
+
+// Generates the `protocols` value for a category of `class_name` (whose type is `self_ty`) that
+// adopts `protocol_name`. It's null unless the class is extern.
+pub fn category_protocols(
+  class_name: &str,
+  category_name: &str,
+  protocol_name: &str,
+  self_ty: &Type,
+  objrs_root: &Ident,
+) -> TokenStream {
+  let native_ty = quote!(#objrs_root::__objrs);
+  let protocol_link_name = ["\x01l_OBJC_PROTOCOL_$_", protocol_name].concat();
+  let list_export_name =
+    ["\x01l_OBJC_CATEGORY_PROTOCOLS_$_", class_name, "_$_", category_name].concat();
+  let list_ident = priv_ident("PROTOCOL_LIST");
+
+  return quote! {{
+    #[repr(C)]
+    struct ProtocolList {
+      count: #native_ty::usize,
+      protocols: [*const #objrs_root::__objrs::runtime::protocol_t; 1],
+      null: #native_ty::usize,
+    }
+
+    #[link_section = "__DATA,__objc_const"]
+    #[export_name = #list_export_name]
+    static #list_ident: #objrs_root::__objrs::SyncHack<ProtocolList> = #objrs_root::__objrs::SyncHack(ProtocolList {
+      count: 1,
+      protocols: [
+        {
+          // The protocol is defined by the #[objrs(protocol)] macro.
+          extern "C" {
+            #[link_name = #protocol_link_name]
+            static PROTOCOL: #objrs_root::__objrs::runtime::protocol_t;
+          }
+          unsafe { &PROTOCOL as *const _ }
+        }
+      ],
+      null: 0,
+    });
+
+    const IS_EXTERN: #native_ty::bool = <#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::IS_EXTERN;
+    unsafe { #objrs_root::__objrs::TransmuteHack::<_, *mut #objrs_root::__objrs::runtime::protocol_list_t> { from: [&#list_ident as *const _, 0 as *const _][!IS_EXTERN as #native_ty::usize] }.to }
+  }};
+}
+
+// Generates a constructor that adds `protocol_name` to the protocol list of the custom class whose
+// type is `self_ty`.
+pub fn class_protocols(protocol_name: &str, self_ty: &Type, objrs_root: &Ident) -> TokenStream {
+  let protocol_link_name = ["\x01l_OBJC_PROTOCOL_$_", protocol_name].concat();
+  let ident = priv_ident("ADD_PROTOCOL");
+
+  return quote! {
+    #[link_section = "__DATA,__mod_init_func,mod_init_funcs"]
+    #[used]
+    static #ident: extern "C" fn() = {
+      extern "C" fn add_protocol() {
+        // The protocol is defined by the #[objrs(protocol)] macro.
+        extern "C" {
+          #[link_name = #protocol_link_name]
+          static PROTOCOL: #objrs_root::__objrs::runtime::protocol_t;
+        }
+        if let #objrs_root::__objrs::core::option::Option::Some(list) = <#self_ty as #objrs_root::__objrs::runtime::__objrs::Class>::protocol_list() {
+          unsafe { list.add(&PROTOCOL) };
+        }
+      }
+      add_protocol
+    };
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate objrs_test_utils;
+
+  use super::*;
+  use objrs_test_utils::assert_tokens_eq;
+  use syn::parse_quote;
+
+  #[test]
+  fn extern_class_category_protocols() {
+    let objrs_root = priv_ident("__objrs_root");
+    let self_ty = parse_quote!(NSString);
+    let actual = category_protocols(
+      "NSString",
+      "__objrs_category_MyProtocol_abc",
+      "MyProtocol",
+      &self_ty,
+      &objrs_root,
+    );
+    let list_ident = priv_ident("PROTOCOL_LIST");
+    let expected = quote! {{
+      #[repr(C)]
+      struct ProtocolList {
+        count: __objrs_root::__objrs::usize,
+        protocols: [*const __objrs_root::__objrs::runtime::protocol_t; 1],
+        null: __objrs_root::__objrs::usize,
+      }
+
+      #[link_section = "__DATA,__objc_const"]
+      #[export_name = "\u{1}l_OBJC_CATEGORY_PROTOCOLS_$_NSString_$___objrs_category_MyProtocol_abc"]
+      static #list_ident: __objrs_root::__objrs::SyncHack<ProtocolList> = __objrs_root::__objrs::SyncHack(ProtocolList {
+        count: 1,
+        protocols: [
+          {
+            extern "C" {
+              #[link_name = "\u{1}l_OBJC_PROTOCOL_$_MyProtocol"]
+              static PROTOCOL: __objrs_root::__objrs::runtime::protocol_t;
+            }
+            unsafe { &PROTOCOL as *const _ }
+          }
+        ],
+        null: 0,
+      });
+
+      const IS_EXTERN: __objrs_root::__objrs::bool = <NSString as __objrs_root::__objrs::runtime::__objrs::Class>::IS_EXTERN;
+      unsafe { __objrs_root::__objrs::TransmuteHack::<_, *mut __objrs_root::__objrs::runtime::protocol_list_t> { from: [&#list_ident as *const _, 0 as *const _][!IS_EXTERN as __objrs_root::__objrs::usize] }.to }
+    }};
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn custom_class_protocols() {
+    let objrs_root = priv_ident("__objrs_root");
+    let self_ty = parse_quote!(AppDelegate);
+    let actual = class_protocols("NSApplicationDelegate", &self_ty, &objrs_root);
+    let ident = priv_ident("ADD_PROTOCOL");
+    let expected = quote! {
+      #[link_section = "__DATA,__mod_init_func,mod_init_funcs"]
+      #[used]
+      static #ident: extern "C" fn() = {
+        extern "C" fn add_protocol() {
+          extern "C" {
+            #[link_name = "\u{1}l_OBJC_PROTOCOL_$_NSApplicationDelegate"]
+            static PROTOCOL: __objrs_root::__objrs::runtime::protocol_t;
+          }
+          if let __objrs_root::__objrs::core::option::Option::Some(list) = <AppDelegate as __objrs_root::__objrs::runtime::__objrs::Class>::protocol_list() {
+            unsafe { list.add(&PROTOCOL) };
+          }
+        }
+        add_protocol
+      };
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+}
@@ -16,7 +16,14 @@ use proc_macro2::TokenStream;
 use quote::quote;
 use syn::LitByteStr;
 
-pub fn gen_sel_ref(mut sel_ref: SelRef) -> Result<TokenStream, Diagnostic> {
+pub fn gen_sel_ref(sel_ref: SelRef) -> Result<TokenStream, Diagnostic> {
+  if cfg!(feature = "runtime-gnustep") {
+    return Ok(gnustep_sel_ref(sel_ref));
+  }
+  return Ok(apple_sel_ref(sel_ref));
+}
+
+fn apple_sel_ref(mut sel_ref: SelRef) -> TokenStream {
   let random_id = &RandomIdentifier::new();
   let meth_name_export_name =
     ["\x01L_OBJC_METH_VAR_NAME_.__objrs_meth.", random_id, ".", &sel_ref.sel_string].concat();
@@ -29,7 +36,7 @@ pub fn gen_sel_ref(mut sel_ref: SelRef) -> Result<TokenStream, Diagnostic> {
 
   let ident = sel_ref.ident;
   let u8_ty = sel_ref.u8_ty;
-  return Ok(quote! {
+  return quote! {
     #[link_section = "__DATA,__objc_selrefs,literal_pointers,no_dead_strip"]
     #[export_name = #sel_ref_export_name]
     static #ident: &'static [#u8_ty; #len] = {
@@ -38,7 +45,40 @@ pub fn gen_sel_ref(mut sel_ref: SelRef) -> Result<TokenStream, Diagnostic> {
       static METH_NAME: [#u8_ty; #len] = * #selector;
       &METH_NAME
     };
-  });
+  };
+}
+
+// libobjc2's v2 ABI doesn't have selector references. Instead, a SEL is the address of a selector
+// structure (`{ const char *name; const char *types; }`) in the `__objc_selectors` section, which the
+// runtime registers (and uniques) when the image is loaded. The static still has the same type as
+// the Apple runtime's selector reference so that the code using it doesn't need to change, but it
+// must only be used as a SEL (and never dereferenced).
+fn gnustep_sel_ref(mut sel_ref: SelRef) -> TokenStream {
+  sel_ref.sel_string.push('\x00');
+  let len = sel_ref.sel_string.len();
+  let selector = LitByteStr::new(sel_ref.sel_string.as_bytes(), sel_ref.sel.span());
+
+  let ident = sel_ref.ident;
+  let u8_ty = sel_ref.u8_ty;
+  let objrs_root = sel_ref.objrs_root;
+  let option = quote!(#objrs_root::__objrs::core::option::Option);
+  return quote! {
+    static #ident: &'static [#u8_ty; #len] = {
+      static METH_NAME: [#u8_ty; #len] = * #selector;
+
+      // The name and (untyped, i.e. null) types. The runtime writes to this when it uniques the
+      // selector, so it must not be placed in read-only memory.
+      #[link_section = "__objc_selectors"]
+      #[used]
+      static mut SELECTOR: [#option<&'static #u8_ty>; 2] = [#option::Some(&METH_NAME[0]), #option::None];
+
+      union SelectorHack {
+        selector: &'static [#option<&'static #u8_ty>; 2],
+        sel_ref: &'static [#u8_ty; #len],
+      }
+      unsafe { SelectorHack { selector: &SELECTOR }.sel_ref }
+    };
+  };
 }
 
 #[cfg(test)]
@@ -56,12 +96,13 @@ mod tests {
       sel_string: String::from(sel),
       ident: parse_quote!(SEL_REF),
       u8_ty: parse_quote!(__objrs_root::__objrs::u8),
+      objrs_root: quote!(__objrs_root),
     };
   }
 
   #[test]
   fn sel_ref() {
-    let actual = gen_sel_ref(default_sel()).unwrap();
+    let actual = apple_sel_ref(default_sel());
 
     let expected = quote! {
       #[link_section = "__DATA,__objc_selrefs,literal_pointers,no_dead_strip"]
@@ -75,4 +116,25 @@ mod tests {
     };
     assert_tokens_eq!(actual, expected);
   }
+  #[test]
+  fn gnustep_sel_ref() {
+    let actual = super::gnustep_sel_ref(default_sel());
+
+    let expected = quote! {
+      static SEL_REF: &'static [__objrs_root::__objrs::u8; 7usize] = {
+        static METH_NAME: [__objrs_root::__objrs::u8; 7usize] = *b"theSel\0";
+
+        #[link_section = "__objc_selectors"]
+        #[used]
+        static mut SELECTOR: [__objrs_root::__objrs::core::option::Option<&'static __objrs_root::__objrs::u8>; 2] = [__objrs_root::__objrs::core::option::Option::Some(&METH_NAME[0]), __objrs_root::__objrs::core::option::Option::None];
+
+        union SelectorHack {
+          selector: &'static [__objrs_root::__objrs::core::option::Option<&'static __objrs_root::__objrs::u8>; 2],
+          sel_ref: &'static [__objrs_root::__objrs::u8; 7usize],
+        }
+        unsafe { SelectorHack { selector: &SELECTOR }.sel_ref }
+      };
+    };
+    assert_tokens_eq!(actual, expected);
+  }
 }
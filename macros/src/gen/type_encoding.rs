@@ -0,0 +1,137 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate quote;
+extern crate syn;
+
+use crate::parse::type_encoding_attr::{Aggregate, DeriveTypeEncoding};
+use proc_macro::Diagnostic;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, LitByte, LitByteStr};
+
+// Generates the TypeEncoding impl for a #[repr(C)] struct or union. The encoding is the same as
+// clang's: "{Name=" (or "(Name=" for unions), followed by each field's component encoding, followed
+// by "}" (or ")").
+pub fn derive_type_encoding(derive: DeriveTypeEncoding) -> Result<TokenStream, Diagnostic> {
+  let objrs_root = &derive.objrs;
+  let native_ty = quote!(#objrs_root::__objrs);
+  let span = derive.encoding_name.span();
+
+  let (open, close) = match derive.aggregate {
+    Aggregate::Struct => ("{", b'}'),
+    Aggregate::Union => ("(", b')'),
+  };
+  let prefix = [open, &derive.encoding_name.value(), "="].concat();
+  let prefix_len = prefix.len();
+  let prefix = LitByteStr::new(prefix.as_bytes(), span);
+  let close = LitByte::new(close, span);
+
+  // The fields' encodings are nested in Packed2 pairs (rather than a tuple) so that there's no limit
+  // on the number of fields.
+  let mut fields_ty = quote!([#native_ty::u8; 0]);
+  let mut fields_bytes = quote!([]);
+  for ty in derive.fields.iter().rev() {
+    fields_ty =
+      quote!(#native_ty::Packed2<<#ty as #objrs_root::ComponentTypeEncoding>::Type, #fields_ty>);
+    fields_bytes = quote!(#native_ty::Packed2(<#ty as #objrs_root::ComponentTypeEncoding>::BYTES, #fields_bytes));
+  }
+
+  let mut generics = derive.generics;
+  if !generics.params.is_empty() {
+    let where_clause = generics.make_where_clause();
+    for ty in derive.fields.iter() {
+      where_clause.predicates.push(parse_quote!(#ty: #objrs_root::ComponentTypeEncoding));
+    }
+  }
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+  let ident = &derive.ident;
+  return Ok(quote! {
+    unsafe impl #impl_generics #objrs_root::TypeEncoding for #ident #ty_generics #where_clause {
+      type Type = #native_ty::Packed3<[#native_ty::u8; #prefix_len], #fields_ty, #native_ty::u8>;
+      const BYTES: Self::Type = #native_ty::Packed3(*#prefix, #fields_bytes, #close);
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  extern crate objrs_test_utils;
+
+  use super::*;
+  use crate::parse::type_encoding_attr::TypeEncodingAttr;
+  use objrs_test_utils::assert_tokens_eq;
+  use syn::parse2;
+
+  #[test]
+  fn derive_struct() {
+    let input = quote! {
+      #[repr(C)]
+      struct CGPoint {
+        x: f64,
+        y: f64,
+      }
+    };
+    let actual = derive_type_encoding(DeriveTypeEncoding::new(input).ok().unwrap()).ok().unwrap();
+    let expected = quote! {
+      unsafe impl objrs::TypeEncoding for CGPoint {
+        type Type = objrs::__objrs::Packed3<
+          [objrs::__objrs::u8; 9usize],
+          objrs::__objrs::Packed2<<f64 as objrs::ComponentTypeEncoding>::Type, objrs::__objrs::Packed2<<f64 as objrs::ComponentTypeEncoding>::Type, [objrs::__objrs::u8; 0]> >,
+          objrs::__objrs::u8
+        >;
+        const BYTES: Self::Type = objrs::__objrs::Packed3(
+          *b"{CGPoint=",
+          objrs::__objrs::Packed2(<f64 as objrs::ComponentTypeEncoding>::BYTES, objrs::__objrs::Packed2(<f64 as objrs::ComponentTypeEncoding>::BYTES, [])),
+          125u8
+        );
+      }
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn derive_union_with_name() {
+    let input = quote! {
+      #[objrs(encoding_name = "_Value", objrs = __objrs_root)]
+      #[repr(C)]
+      union Value<T: Copy> {
+        i: i32,
+        t: T,
+      }
+    };
+    let actual = derive_type_encoding(DeriveTypeEncoding::new(input).ok().unwrap()).ok().unwrap();
+    let expected = quote! {
+      unsafe impl<T: Copy> __objrs_root::TypeEncoding for Value<T>
+      where
+        i32: __objrs_root::ComponentTypeEncoding,
+        T: __objrs_root::ComponentTypeEncoding
+      {
+        type Type = __objrs_root::__objrs::Packed3<
+          [__objrs_root::__objrs::u8; 8usize],
+          __objrs_root::__objrs::Packed2<<i32 as __objrs_root::ComponentTypeEncoding>::Type, __objrs_root::__objrs::Packed2<<T as __objrs_root::ComponentTypeEncoding>::Type, [__objrs_root::__objrs::u8; 0]> >,
+          __objrs_root::__objrs::u8
+        >;
+        const BYTES: Self::Type = __objrs_root::__objrs::Packed3(
+          *b"(_Value=",
+          __objrs_root::__objrs::Packed2(<i32 as __objrs_root::ComponentTypeEncoding>::BYTES, __objrs_root::__objrs::Packed2(<T as __objrs_root::ComponentTypeEncoding>::BYTES, [])),
+          41u8
+        );
+      }
+    };
+    assert_tokens_eq!(actual, expected);
+  }
+
+  #[test]
+  fn objrs_attr_only_accepts_encoding_name() {
+    let attr: TypeEncodingAttr = parse2(quote!((encoding_name = "CGPoint"))).unwrap();
+    assert_eq!(attr.encoding_name.unwrap().value(), "CGPoint");
+    assert!(parse2::<TypeEncodingAttr>(quote!((name = "CGPoint"))).is_err());
+    assert!(parse2::<TypeEncodingAttr>(quote!((class, super = NSObject))).is_err());
+  }
+}
@@ -16,11 +16,13 @@ use crate::class_impl::transform_impl;
 use crate::gen::class_ref::gen_class_ref;
 use crate::gen::gen_protocol::transform_protocol;
 use crate::gen::sel_ref::gen_sel_ref;
+use crate::gen::type_encoding::derive_type_encoding;
 use crate::parse::class_attr::{Class, ClassAttr};
 use crate::parse::class_ref_attr::ClassRef;
 use crate::parse::impl_attr::ImplAttr;
 use crate::parse::protocol_attr::{Protocol, ProtocolAttr};
 use crate::parse::sel_ref_attr::SelRef;
+use crate::parse::type_encoding_attr::DeriveTypeEncoding;
 use proc_macro::Diagnostic;
 use proc_macro2::TokenStream;
 use syn::parse::{Parse, ParseStream};
@@ -52,7 +54,7 @@ impl ObjrsAttr {
 
 impl Parse for ObjrsAttr {
   fn parse(input: ParseStream) -> syn::parse::Result<Self> {
-    use crate::parse::attr::{class, ivar, protocol, selector};
+    use crate::parse::attr::{class, encoding_name, ivar, protocol, selector};
     use syn::token::Impl;
 
     let lookahead = input.lookahead1();
@@ -72,6 +74,11 @@ impl Parse for ObjrsAttr {
         input.cursor().span(),
         "attribute must be enclosed in a struct item with a #[objrs(class)] attribute",
       ));
+    } else if lookahead.peek(encoding_name) {
+      return Err(syn::parse::Error::new(
+        input.cursor().span(),
+        "attribute must follow a #[derive(TypeEncoding)] attribute",
+      ));
     }
     return Err(lookahead.error());
   }
@@ -138,6 +145,18 @@ pub fn class_ref(
   }
 }
 
+#[proc_macro_derive(TypeEncoding, attributes(objrs))]
+pub fn type_encoding(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+  let input: TokenStream = input.into();
+  match DeriveTypeEncoding::new(input).and_then(derive_type_encoding) {
+    Ok(stream) => return stream.into(),
+    Err(diagnostic) => {
+      diagnostic.emit();
+      return proc_macro::TokenStream::new();
+    }
+  }
+}
+
 // TODO: decide whether or not to stablize this. If so, maybe parse the #[objrs()] macros and
 // transform them to make sure they explicitly say `extern` so users don't have to repeat that.
 // #[proc_macro]
@@ -89,15 +89,19 @@ impl_token_key!(syn::token::Type);
 
 impl_custom_key!(assign);
 impl_custom_key!(atomic);
+impl_custom_key!(catch_panic);
 impl_custom_key!(category_name);
 impl_custom_key!(class);
 impl_custom_key!(class_name);
 impl_custom_key!(copy);
 impl_custom_key!(default);
+impl_custom_key!(dynamic);
+impl_custom_key!(encoding_name);
 impl_custom_key!(getter);
 impl_custom_key!(id_ident);
 impl_custom_key!(instance);
 impl_custom_key!(ivar);
+impl_custom_key!(method_family);
 impl_custom_key!(name);
 impl_custom_key!(no_impl);
 impl_custom_key!(nonatomic);
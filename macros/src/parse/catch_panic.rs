@@ -0,0 +1,85 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+extern crate proc_macro2;
+extern crate syn;
+
+use crate::parse::attr::Value;
+use proc_macro2::Span;
+use syn::parse::ParseStream;
+use syn::Ident;
+
+// What a method implemented in Rust does if its body panics. Panics must not unwind into the
+// Objective-C code that called the method.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CatchPanic {
+  // The panic isn't caught.
+  None,
+  // The process aborts (after printing the method and the panic message).
+  Abort,
+  // The panic is converted into an NSException, which is thrown to the caller.
+  Exception,
+}
+
+impl CatchPanic {
+  // The default for methods without a `catch_panic` parameter, which is set crate-wide by the
+  // `catch-panic` feature. `Exception` is never the default, since it requires the crate that
+  // defines the method to enable `unwind_attributes`.
+  pub fn crate_default() -> CatchPanic {
+    if cfg!(feature = "catch-panic") {
+      return CatchPanic::Abort;
+    }
+    return CatchPanic::None;
+  }
+
+  // Parses the value of a `catch_panic = ...` parameter.
+  pub fn from_ident(ident: &Ident) -> syn::parse::Result<CatchPanic> {
+    if ident == "none" {
+      return Ok(CatchPanic::None);
+    }
+    if ident == "abort" {
+      return Ok(CatchPanic::Abort);
+    }
+    if ident == "exception" {
+      return Ok(CatchPanic::Exception);
+    }
+    return Err(syn::parse::Error::new(
+      ident.span(),
+      "expected one of `abort`, `exception`, or `none`",
+    ));
+  }
+}
+
+// `catch_panic` on its own means `catch_panic = abort`.
+impl Value for CatchPanic {
+  fn parse(input: ParseStream, _: Span) -> syn::parse::Result<Self> {
+    if !input.peek(syn::token::Eq) {
+      return Ok(CatchPanic::Abort);
+    }
+    let _: syn::token::Eq = input.parse()?;
+    let ident: Ident = input.parse()?;
+    return CatchPanic::from_ident(&ident);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use syn::parse::Parser;
+
+  fn parse(tokens: proc_macro2::TokenStream) -> syn::parse::Result<CatchPanic> {
+    let parser = |input: ParseStream| <CatchPanic as Value>::parse(input, Span::call_site());
+    return parser.parse2(tokens);
+  }
+
+  #[test]
+  fn values() {
+    assert_eq!(parse(quote::quote!()).unwrap(), CatchPanic::Abort);
+    assert_eq!(parse(quote::quote!(= abort)).unwrap(), CatchPanic::Abort);
+    assert_eq!(parse(quote::quote!(= exception)).unwrap(), CatchPanic::Exception);
+    assert_eq!(parse(quote::quote!(= none)).unwrap(), CatchPanic::None);
+    assert!(parse(quote::quote!(= unwind)).is_err());
+  }
+}
@@ -21,6 +21,7 @@
 //!         [name = "ExportName",]
 //!         super = IDENT
 //!         [, root_class = "ExportName"]
+//!         [, property(...)]*
 //!         [, extern]
 //!         [, objrs = IDENT][,])]
 //! struct Bar;
@@ -47,6 +48,16 @@
 //! - `root_class`. Required (unless `super = ...` is used). One of `root_class` or `super = ...`
 //!   must be specified. In practice this is only useful for `NSObject`.
 //! - `super = TYPE`. Required (unless `root_class` is used). The class's super type.
+//! - `property(...)`. Optional (repeated). Declares an Objective-C property using the same syntax
+//!   as `#[objrs(protocol)]` properties. Only custom (non-extern) classes may declare properties.
+//!   If the struct has a field with the same name as the property, the field is used as the
+//!   property's backing ivar and objrs synthesizes the getter (and setter, unless the property is
+//!   `readonly`) in the class's method list. Like clang, objrs doesn't synthesize an accessor that
+//!   the class's `#[objrs(impl)]` block implements itself. If the property is marked `dynamic` or
+//!   the struct has no field with the property's name, the property is treated as `@dynamic` and
+//!   you must implement the accessors yourself. Properties without `copy`, `strong`, `retain`, or `weak` are `assign`
+//!   properties. `copy`, `strong`, and `retain` properties must be backed by an
+//!   `Option<Strong<T>>` ivar, and `weak` properties must be backed by a `Weak<T>` ivar.
 //! - `extern`. Optional. If this is omitted, objrs will treat the class as an external class only
 //!   if a `#[link(...)]` attribute is present. An external class is one provided by an external
 //!   framework (e.g. `NSArray`, `UIApplication`, etc.). If you do not specify a `#[link(...)]`
@@ -91,17 +102,31 @@
 //! }
 //! ```
 //!
+//! Properties are declared in the class attribute. Here `name`'s getter and setter are synthesized
+//! and `count`'s getter must be implemented in an `#[objrs(impl)]` block:
+//!
+//! ```ignore
+//! #[objrs(class,
+//!         super = NSObject,
+//!         property(name: Option<Strong<NSString>>, copy, nonatomic),
+//!         property(count: usize, readonly, nonatomic))]
+//! struct Person {
+//!   name: Option<Strong<NSString>>,
+//! }
+//! ```
+//!
 //! # Syntax
 //!
 //! Building on [Rust's syntax and EBNF dialect](https://doc.rust-lang.org/grammar.html):
 //!
 //! ```text
-//! objrs_class: '#' '[' "objrs" '(' "class" name? root_or_super? extern? objrs? ','? ')' ']'
+//! objrs_class: '#' '[' "objrs" '(' "class" name? root_or_super? property* extern? objrs? ','? ')' ']'
 //! name: ',' "name" '=' string_lit
 //! root_or_super: ',' ["root_class" | [super root_class?]]
 //! super: 'super' [['=' type_path] | ['(' super_params ','? ')']]
 //! super_params: "name" '=' string_lit ',' "type" '=' type_path
 //! root_class: ',' "root_class" '=' string_lit
+//! property: ',' "property" '(' ident ':' type attributes ','? ')'
 //! extern: ',' "extern"
 //! objrs: ',' "objrs" '=' ident
 //! ```
@@ -111,6 +136,7 @@ extern crate syn;
 
 use crate::parse::attr::take_objrs_attr;
 use crate::parse::ivar_attr::IvarAttr;
+use crate::parse::property_attr::PropertyAttr;
 use crate::parse::util::objrs_root;
 use crate::util::link_attribute;
 use proc_macro::Diagnostic;
@@ -125,6 +151,7 @@ pub struct ClassAttr {
   pub super_class: Option<TypePath>,
   pub force_extern: bool,
   pub root_class_name: Option<LitStr>, // Only needed due to the lack of associated extern statics.
+  pub properties: Vec<PropertyAttr>,
   pub objrs: Option<Ident>,
 }
 
@@ -170,7 +197,7 @@ impl crate::parse::attr::Value for SuperAttr {
 
 impl Parse for ClassAttr {
   fn parse(input: ParseStream) -> syn::parse::Result<Self> {
-    use crate::parse::attr::{class, name, objrs, root_class, KV};
+    use crate::parse::attr::{class, name, objrs, property, root_class, KV};
 
     let mut kv = KV::new(input);
     kv.parse::<class, _>()?;
@@ -187,6 +214,10 @@ impl Parse for ClassAttr {
     } else {
       root_class_name = kv.parse::<root_class, _>()?;
     }
+    let mut properties = vec![];
+    while let Some(property) = kv.parse::<property, _>()? {
+      properties.push(property);
+    }
     let force_extern: Option<()> = kv.parse::<syn::token::Extern, _>()?;
     let objrs: Option<Ident> = kv.parse::<objrs, _>()?;
     kv.eof()?;
@@ -196,6 +227,7 @@ impl Parse for ClassAttr {
       super_class: super_class,
       force_extern: force_extern.is_some(),
       root_class_name: root_class_name,
+      properties: properties,
       objrs: objrs,
     });
   }
@@ -246,6 +278,7 @@ pub struct Class {
   pub root_class_name: LitStr, // TODO: maybe just make this a String...
   pub item: ItemStruct,
   pub ivars: Vec<Ivar>,
+  pub properties: Vec<PropertyAttr>,
 }
 
 impl Class {
@@ -265,6 +298,18 @@ impl Class {
     }
 
     let force_extern = attr.force_extern || link_attribute(&item.attrs).is_some();
+    if force_extern {
+      if let Some(property) = attr.properties.first() {
+        return Err(
+          property
+            .name
+            .span()
+            .unstable()
+            .error("extern classes cannot declare properties")
+            .note("properties are only supported for custom classes"),
+        );
+      }
+    }
 
     let class_name =
       attr.class_name.unwrap_or_else(|| LitStr::new(&item.ident.to_string(), item.ident.span()));
@@ -300,6 +345,7 @@ impl Class {
       root_class_name: root_class_name,
       item: item,
       ivars: ivars,
+      properties: attr.properties,
     });
   }
 }
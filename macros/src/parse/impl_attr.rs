@@ -89,6 +89,9 @@
 //! }
 //! ```
 //!
+//! Each `impl Protocol for Class` block is emitted as a category that adopts the protocol, so
+//! `conformsToProtocol:` returns `YES` for the protocol (for both custom and extern classes).
+//!
 //! # Syntax
 //!
 //! Building on [Rust's syntax and EBNF dialect](https://doc.rust-lang.org/grammar.html):
@@ -0,0 +1,380 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// See https://clang.llvm.org/docs/AutomaticReferenceCounting.html#method-families
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+
+use proc_macro::Diagnostic;
+use syn::spanned::Spanned;
+use syn::{
+  FnArg, GenericArgument, Ident, Lifetime, LitStr, PathArguments, ReturnType, Signature, Type,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MethodFamily {
+  None,
+  Alloc,
+  Copy,
+  Init,
+  MutableCopy,
+  New,
+}
+
+const FAMILIES: [MethodFamily; 5] = [
+  MethodFamily::Alloc,
+  MethodFamily::Copy,
+  MethodFamily::Init,
+  MethodFamily::MutableCopy,
+  MethodFamily::New,
+];
+
+impl MethodFamily {
+  // Classifies a selector the same way clang does: a selector is in a family if its first word
+  // (ignoring leading underscores) is the family's name. The first word ends at the first character
+  // that isn't a lowercase letter, so "newFoo" and "copy:" are in the new and copy families, but
+  // "newspaper" and "copying" are not.
+  pub fn from_selector(sel: &str) -> MethodFamily {
+    let sel = sel.trim_start_matches('_');
+    for &family in FAMILIES.iter() {
+      let name = family.name();
+      if let Some(rest) = sel.strip_prefix(name) {
+        match rest.chars().next() {
+          Some(c) if c.is_ascii_lowercase() => continue,
+          _ => return family,
+        }
+      }
+    }
+    return MethodFamily::None;
+  }
+
+  // Parses the value of a `method_family = ...` parameter. These are the same names that clang's
+  // `objc_method_family` attribute uses.
+  pub fn from_ident(ident: &Ident) -> syn::parse::Result<MethodFamily> {
+    if ident == "none" {
+      return Ok(MethodFamily::None);
+    }
+    for &family in FAMILIES.iter() {
+      if ident == family.name() {
+        return Ok(family);
+      }
+    }
+    return Err(syn::parse::Error::new(
+      ident.span(),
+      "expected one of `alloc`, `copy`, `init`, `mutableCopy`, `new`, or `none`",
+    ));
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      MethodFamily::None => return "none",
+      MethodFamily::Alloc => return "alloc",
+      MethodFamily::Copy => return "copy",
+      MethodFamily::Init => return "init",
+      MethodFamily::MutableCopy => return "mutableCopy",
+      MethodFamily::New => return "new",
+    }
+  }
+
+  // Methods in every family except `none` return a retained (+1) object.
+  pub fn returns_retained(self) -> bool {
+    return self != MethodFamily::None;
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ownership {
+  Owned,
+  Unowned,
+  Unknown,
+}
+
+// Syntactically determines whether a return type owns the object it refers to. `Strong<T>` and
+// `Alloc<T>` are owned, and `Auto<'_, T>` and references are unowned. Everything else (including
+// raw pointers and type aliases) is unknown.
+fn ownership(ty: &Type) -> Ownership {
+  let path = match ty {
+    Type::Reference(_) => return Ownership::Unowned,
+    Type::Paren(ref inner) => return ownership(inner.elem.as_ref()),
+    Type::Group(ref inner) => return ownership(inner.elem.as_ref()),
+    Type::Path(ref type_path) if type_path.qself.is_none() => &type_path.path,
+    _ => return Ownership::Unknown,
+  };
+  let last_segment = match path.segments.last() {
+    Some(last_segment) => last_segment,
+    None => return Ownership::Unknown,
+  };
+  let ident = &last_segment.ident;
+  if ident == "Strong" || ident == "Alloc" {
+    return Ownership::Owned;
+  }
+  if ident == "Auto" {
+    return Ownership::Unowned;
+  }
+  if ident == "Option" {
+    if let PathArguments::AngleBracketed(ref arguments) = last_segment.arguments {
+      if let Some(GenericArgument::Type(ref inner)) = arguments.args.first() {
+        return ownership(inner);
+      }
+    }
+  }
+  return Ownership::Unknown;
+}
+
+// Returns true if the method returns an owned object (e.g. `Strong<T>`) even though the selector
+// returns an unretained (+0) object. objrs bridges the two with the same functions that ARC uses:
+// message sends claim the result with `objc_retainAutoreleasedReturnValue`, and method
+// implementations return their result with `objc_autoreleaseReturnValue`. ARC callers of objrs
+// methods skip the autorelease pool. objrs's own message sends don't: the runtime only skips the
+// pool if the caller's send is immediately followed by a marker instruction (`mov x29, x29` on
+// arm64, or `movq %rax, %rdi` and the call on x86_64), which Rust can't guarantee, so the result is
+// autoreleased and then retained.
+pub fn returns_autoreleased_owned(family: MethodFamily, output: &ReturnType) -> bool {
+  let ty = match output {
+    ReturnType::Default => return false,
+    ReturnType::Type(_, ref ty) => ty.as_ref(),
+  };
+  return !family.returns_retained() && ownership(ty) == Ownership::Owned;
+}
+
+// Returns a warning if the method's return type disagrees with its method family about who owns the
+// returned object. Only unowned returns from retaining (+1) families are suspicious. Owned returns
+// (e.g. `Strong<T>`) from +0 families don't get a warning: they're correct, since the message send
+// retains the result (see `returns_autoreleased_owned`), so warning about them would only be noise.
+pub fn ownership_warning(
+  family: MethodFamily,
+  sel: &LitStr,
+  output: &ReturnType,
+) -> Option<Diagnostic> {
+  let ty = match output {
+    ReturnType::Default => return None,
+    ReturnType::Type(_, ref ty) => ty.as_ref(),
+  };
+  match (family.returns_retained(), ownership(ty)) {
+    (true, Ownership::Unowned) => {
+      let message = format!(
+        "selector \"{}\" is in the `{}` method family, so it returns a retained (+1) object",
+        sel.value(),
+        family.name()
+      );
+      return Some(
+        ty.span()
+          .unstable()
+          .warning(message)
+          .note("use `Strong<T>` as the return type, or use `method_family = none` if the selector doesn't follow the naming convention"),
+      );
+    }
+    _ => return None,
+  }
+}
+
+// If `ty` is an `&AutoreleasePool`, returns the reference's lifetime (`None` if it's elided).
+fn pool_lifetime(ty: &Type) -> Option<Option<&Lifetime>> {
+  let reference = match ty {
+    Type::Reference(ref reference) if reference.mutability.is_none() => reference,
+    Type::Paren(ref inner) => return pool_lifetime(inner.elem.as_ref()),
+    Type::Group(ref inner) => return pool_lifetime(inner.elem.as_ref()),
+    _ => return None,
+  };
+  match *reference.elem {
+    Type::Path(ref type_path) if type_path.qself.is_none() => {
+      match type_path.path.segments.last() {
+        Some(segment) if segment.ident == "AutoreleasePool" => {
+          return Some(reference.lifetime.as_ref());
+        }
+        _ => return None,
+      }
+    }
+    _ => return None,
+  }
+}
+
+// If `ty` is an `Auto<'pool, T>` (or an `Option` of one), returns its lifetime argument (`None` if
+// it's elided).
+fn auto_lifetime(ty: &Type) -> Option<Option<&Lifetime>> {
+  let last_segment = match ty {
+    Type::Paren(ref inner) => return auto_lifetime(inner.elem.as_ref()),
+    Type::Group(ref inner) => return auto_lifetime(inner.elem.as_ref()),
+    Type::Path(ref type_path) if type_path.qself.is_none() => type_path.path.segments.last()?,
+    _ => return None,
+  };
+  let arguments = match last_segment.arguments {
+    PathArguments::AngleBracketed(ref arguments) => Some(arguments),
+    _ => None,
+  };
+  if last_segment.ident == "Auto" {
+    match arguments.and_then(|arguments| arguments.args.first()) {
+      Some(GenericArgument::Lifetime(ref lifetime)) if lifetime.ident != "_" => {
+        return Some(Some(lifetime));
+      }
+      _ => return Some(None),
+    }
+  }
+  if last_segment.ident == "Option" {
+    if let Some(GenericArgument::Type(ref inner)) = arguments?.args.first() {
+      return auto_lifetime(inner);
+    }
+  }
+  return None;
+}
+
+// Returns true if the argument is an `&AutoreleasePool`. Extern methods may take one, which isn't
+// sent to Objective-C (see `pool_arg`).
+pub fn is_pool_arg(arg: &FnArg) -> bool {
+  match arg {
+    FnArg::Typed(ref pat_ty) => return pool_lifetime(&pat_ty.ty).is_some(),
+    FnArg::Receiver(_) => return false,
+  }
+}
+
+// Returns the index of the method's `&'pool AutoreleasePool` parameter, if it has one.
+//
+// A method that returns an `Auto<'pool, T>` must take the pool that owns the returned object, so the
+// compiler won't let the object outlive it. The runtime autoreleases the object into the innermost
+// pool, so the message send checks that the pool is the innermost one (see
+// `AutoreleasePool::autorelease`).
+pub fn pool_arg(sig: &Signature) -> Result<Option<usize>, Diagnostic> {
+  let mut pool_arg: Option<(usize, &Type, Option<&Lifetime>)> = None;
+  for (i, arg) in sig.inputs.iter().enumerate() {
+    if let FnArg::Typed(ref pat_ty) = arg {
+      if let Some(lifetime) = pool_lifetime(&pat_ty.ty) {
+        if pool_arg.is_some() {
+          return Err(
+            pat_ty.ty.span().unstable().error("methods can take at most one autorelease pool"),
+          );
+        }
+        pool_arg = Some((i, &pat_ty.ty, lifetime));
+      }
+    }
+  }
+
+  let auto_ty = match sig.output {
+    ReturnType::Type(_, ref ty) => ty.as_ref(),
+    ReturnType::Default => return Ok(pool_arg.map(|(i, _, _)| i)),
+  };
+  let auto_lifetime = match auto_lifetime(auto_ty) {
+    Some(auto_lifetime) => auto_lifetime,
+    None => return Ok(pool_arg.map(|(i, _, _)| i)),
+  };
+  let (i, pool_ty, pool_lifetime) = match pool_arg {
+    Some(pool_arg) => pool_arg,
+    None => {
+      return Err(
+        auto_ty
+          .span()
+          .unstable()
+          .error("methods that return an `Auto` must take the autorelease pool that owns it")
+          .note("add a `pool: &'pool AutoreleasePool` parameter and return an `Auto<'pool, T>`, or return a `Strong<T>`"),
+      );
+    }
+  };
+  match (pool_lifetime, auto_lifetime) {
+    (Some(pool_lifetime), Some(auto_lifetime)) if pool_lifetime == auto_lifetime => {
+      return Ok(Some(i));
+    }
+    _ => {
+      return Err(
+        auto_ty
+          .span()
+          .unstable()
+          .error("the returned `Auto` must borrow the autorelease pool parameter")
+          .span_note(pool_ty.span().unstable(), "the pool parameter")
+          .note("name the lifetimes, as in `pool: &'pool AutoreleasePool` and `Auto<'pool, T>`"),
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use syn::parse_quote;
+
+  #[test]
+  fn method_families() {
+    assert_eq!(MethodFamily::from_selector("alloc"), MethodFamily::Alloc);
+    assert_eq!(MethodFamily::from_selector("allocWithZone:"), MethodFamily::Alloc);
+    assert_eq!(MethodFamily::from_selector("copy"), MethodFamily::Copy);
+    assert_eq!(MethodFamily::from_selector("copyWithZone:"), MethodFamily::Copy);
+    assert_eq!(MethodFamily::from_selector("init"), MethodFamily::Init);
+    assert_eq!(MethodFamily::from_selector("initWithFrame:device:"), MethodFamily::Init);
+    assert_eq!(MethodFamily::from_selector("mutableCopy"), MethodFamily::MutableCopy);
+    assert_eq!(MethodFamily::from_selector("new"), MethodFamily::New);
+    assert_eq!(MethodFamily::from_selector("newCommandQueue"), MethodFamily::New);
+    assert_eq!(MethodFamily::from_selector("__new_"), MethodFamily::New);
+    assert_eq!(MethodFamily::from_selector("new2"), MethodFamily::New);
+
+    assert_eq!(MethodFamily::from_selector("newspaper"), MethodFamily::None);
+    assert_eq!(MethodFamily::from_selector("copying"), MethodFamily::None);
+    assert_eq!(MethodFamily::from_selector("initialize"), MethodFamily::None);
+    assert_eq!(MethodFamily::from_selector("mutableCopying"), MethodFamily::None);
+    assert_eq!(MethodFamily::from_selector("string"), MethodFamily::None);
+    assert_eq!(MethodFamily::from_selector("setNew:"), MethodFamily::None);
+  }
+
+  #[test]
+  fn method_family_idents() {
+    let ident: Ident = parse_quote!(mutableCopy);
+    assert_eq!(MethodFamily::from_ident(&ident).unwrap(), MethodFamily::MutableCopy);
+    let ident: Ident = parse_quote!(none);
+    assert_eq!(MethodFamily::from_ident(&ident).unwrap(), MethodFamily::None);
+    let ident: Ident = parse_quote!(retain);
+    assert!(MethodFamily::from_ident(&ident).is_err());
+  }
+
+  #[test]
+  fn ownerships() {
+    let ty: Type = parse_quote!(Option<objrs::Strong<NSString>>);
+    assert_eq!(ownership(&ty), Ownership::Owned);
+    let ty: Type = parse_quote!(objrs::Alloc<Self>);
+    assert_eq!(ownership(&ty), Ownership::Owned);
+    let ty: Type = parse_quote!(Auto<'_, NSString>);
+    assert_eq!(ownership(&ty), Ownership::Unowned);
+    let ty: Type = parse_quote!(Option<&'static NSString>);
+    assert_eq!(ownership(&ty), Ownership::Unowned);
+    let ty: Type = parse_quote!(*mut objrs::Id);
+    assert_eq!(ownership(&ty), Ownership::Unknown);
+    let ty: Type = parse_quote!(usize);
+    assert_eq!(ownership(&ty), Ownership::Unknown);
+  }
+
+  #[test]
+  fn autoreleased_owned_returns() {
+    let output: ReturnType = parse_quote!(-> Strong<NSString>);
+    assert!(returns_autoreleased_owned(MethodFamily::None, &output));
+    assert!(!returns_autoreleased_owned(MethodFamily::Copy, &output));
+    let output: ReturnType = parse_quote!(-> Option<Strong<NSString>>);
+    assert!(returns_autoreleased_owned(MethodFamily::None, &output));
+    let output: ReturnType = parse_quote!(-> Auto<'_, NSString>);
+    assert!(!returns_autoreleased_owned(MethodFamily::None, &output));
+    let output: ReturnType = parse_quote!();
+    assert!(!returns_autoreleased_owned(MethodFamily::None, &output));
+  }
+  #[test]
+  fn pool_lifetimes() {
+    let lifetime: Lifetime = parse_quote!('pool);
+    let ty: Type = parse_quote!(&'pool objrs::AutoreleasePool);
+    assert_eq!(pool_lifetime(&ty), Some(Some(&lifetime)));
+    let ty: Type = parse_quote!(&AutoreleasePool);
+    assert_eq!(pool_lifetime(&ty), Some(None));
+    let ty: Type = parse_quote!(&'pool mut AutoreleasePool);
+    assert_eq!(pool_lifetime(&ty), None);
+    let ty: Type = parse_quote!(&'pool NSString);
+    assert_eq!(pool_lifetime(&ty), None);
+
+    let ty: Type = parse_quote!(Auto<'pool, NSString>);
+    assert_eq!(auto_lifetime(&ty), Some(Some(&lifetime)));
+    let ty: Type = parse_quote!(Option<objrs::Auto<'pool, NSString>>);
+    assert_eq!(auto_lifetime(&ty), Some(Some(&lifetime)));
+    let ty: Type = parse_quote!(Auto<'_, NSString>);
+    assert_eq!(auto_lifetime(&ty), Some(None));
+    let ty: Type = parse_quote!(Auto<NSString>);
+    assert_eq!(auto_lifetime(&ty), Some(None));
+    let ty: Type = parse_quote!(Strong<NSString>);
+    assert_eq!(auto_lifetime(&ty), None);
+  }
+}
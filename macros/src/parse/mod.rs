@@ -17,13 +17,16 @@
 // Omitting an OS from the list means it's not available for that platform.
 
 pub mod attr;
+pub mod catch_panic;
 pub mod class_attr;
 pub mod class_ref_attr;
 mod drain_ext;
 pub mod impl_attr;
 pub mod ivar_attr;
-mod property_attr;
+pub mod method_family;
+pub mod property_attr;
 pub mod protocol_attr;
 pub mod sel_ref_attr;
 pub mod selector_attr;
+pub mod type_encoding_attr;
 mod util;
@@ -14,12 +14,6 @@ pub enum ReadWrite {
   ReadWrite(Span),
 }
 
-pub enum Nullability {
-  Nullable(Span),
-  Nonnull(Span),
-  Unspecified(Span),
-}
-
 pub enum WeakStrong {
   Weak(Span),
   Strong(Span),
@@ -30,6 +24,16 @@ pub enum Atomicity {
   Nonatomic(Span),
 }
 
+// How a property's accessors are provided. This determines the `D` and `V` property attributes.
+pub enum PropertyImpl<'a> {
+  // The property is synthesized (i.e. `@synthesize`) and backed by the named ivar.
+  Synthesized(&'a str),
+  // The property's accessors are implemented manually (i.e. `@dynamic`).
+  Dynamic,
+  // The property is only declared (e.g. in a protocol).
+  Declared,
+}
+
 fn is_weak(weak_strong: &Option<WeakStrong>) -> bool {
   if let Some(WeakStrong::Weak(_)) = weak_strong {
     return true;
@@ -42,23 +46,27 @@ pub struct PropertyAttr {
   pub ty: Type,
   pub class: Option<Span>,
   pub read_write: Option<ReadWrite>,
-  pub nullability: Option<Nullability>,
+  pub copy: Option<Span>,
   pub weak_strong: Option<WeakStrong>,
   pub atomicity: Option<Atomicity>,
   pub retain: Option<Span>,
-  pub null_resettable: Option<Span>,
   pub getter: Option<LitStr>,
   pub setter: Option<LitStr>,
+  pub dynamic: Option<Span>,
 }
 
 // Example: property(prop1: i32, readonly, nonatomic, getter = "foo").
+// `assign`, `unsafe_unretained`, the nullability attributes, and `null_resettable` are accepted for
+// compatibility with clang's grammar, but clang doesn't encode them in the property's attribute
+// string (and they don't affect the synthesized accessors), so they aren't stored.
 // TODO: weak, copy, retain, and strong all require an object type. This should be checked in a
 // post-parse validation step.
 impl Parse for PropertyAttr {
   fn parse(input: ParseStream) -> syn::parse::Result<Self> {
     use crate::parse::attr::{
-      assign, atomic, class, copy, getter, nonatomic, nonnull, null_resettable, null_unspecified,
-      nullable, readonly, readwrite, retain, setter, strong, unsafe_unretained, weak, KV,
+      assign, atomic, class, copy, dynamic, getter, nonatomic, nonnull, null_resettable,
+      null_unspecified, nullable, readonly, readwrite, retain, setter, strong, unsafe_unretained,
+      weak, KV,
     };
     use syn::parenthesized;
     use syn::token::{Colon, Comma, Paren};
@@ -77,13 +85,13 @@ impl Parse for PropertyAttr {
         ty: ty,
         class: None,
         read_write: None,
-        nullability: None,
+        copy: None,
         weak_strong: None,
         atomicity: None,
         retain: None,
-        null_resettable: None,
         getter: None,
         setter: None,
+        dynamic: None,
       });
     }
 
@@ -153,25 +161,9 @@ impl Parse for PropertyAttr {
     } else {
       nonnull = None;
     }
-    let null_unspecified: Option<Span>;
     if nullable.is_none() && nonnull.is_none() {
-      null_unspecified = kv.parse::<null_unspecified, _>()?;
-    } else {
-      null_unspecified = None;
-    }
-    let nullability = nullable
-      .map(Nullability::Nullable)
-      .or(nonnull.map(Nullability::Nonnull))
-      .or(null_unspecified.map(Nullability::Unspecified));
-    // if nullable.is_some() {
-    //   nullability = Some(Nullability::Nullable(ident));
-    // } else if nonnull.is_some() {
-    //   nullability = Some(Nullability::Nonnull(ident));
-    // } else if null_unspecified.is_some()() {
-    //   nullability = Some(Nullability::Unspecified(ident));
-    // } else {
-    //   nullability = None;
-    // }
+      let _: Option<Span> = kv.parse::<null_unspecified, _>()?;
+    }
 
     let atomic: Option<Span> = kv.parse::<atomic, _>()?;
     let nonatomic: Option<Span> = if atomic.is_some() { None } else { kv.parse::<nonatomic, _>()? };
@@ -184,24 +176,26 @@ impl Parse for PropertyAttr {
     //   atomicity = None;
     // }
 
-    let null_resettable: Option<Span> = kv.parse::<null_resettable, _>()?;
+    let _: Option<Span> = kv.parse::<null_resettable, _>()?;
 
     let getter: Option<LitStr> = kv.parse::<getter, _>()?;
     let setter: Option<LitStr> = kv.parse::<setter, _>()?;
 
+    let dynamic: Option<Span> = kv.parse::<dynamic, _>()?;
+
     kv.eof()?;
     return Ok(PropertyAttr {
       name: name,
       ty: ty,
       class: class,
       read_write: read_write,
-      nullability: nullability,
+      copy: copy,
       weak_strong: weak_strong,
       atomicity: atomicity,
       retain: retain,
-      null_resettable: null_resettable,
       getter: getter,
       setter: setter,
+      dynamic: dynamic,
     });
   }
 }
@@ -211,22 +205,93 @@ impl crate::parse::attr::Value for PropertyAttr {
   where
     Self: Sized,
   {
-    use syn::parenthesized;
-
-    let content;
-    let _: syn::token::Paren = parenthesized!(content in input);
-    return content.parse();
+    return <PropertyAttr as Parse>::parse(input);
   }
 }
 
 impl PropertyAttr {
-  fn readonly(&self) -> bool {
+  pub fn readonly(&self) -> bool {
     if let Some(ReadWrite::ReadOnly(_)) = self.read_write {
       return true;
     }
     return false;
   }
 
+  pub fn nonatomic(&self) -> bool {
+    if let Some(Atomicity::Nonatomic(_)) = self.atomicity {
+      return true;
+    }
+    return false;
+  }
+
+  pub fn is_class_property(&self) -> bool {
+    return self.class.is_some();
+  }
+
+  // Dynamic properties (i.e. `@dynamic`) are never synthesized, even if the class has a field with
+  // the same name.
+  pub fn is_dynamic(&self) -> bool {
+    return self.dynamic.is_some();
+  }
+
+  pub fn is_copy(&self) -> bool {
+    return self.copy.is_some();
+  }
+
+  // strong and retain are synonyms.
+  pub fn is_retain(&self) -> bool {
+    if let Some(WeakStrong::Strong(_)) = self.weak_strong {
+      return true;
+    }
+    return self.retain.is_some();
+  }
+
+  pub fn is_weak(&self) -> bool {
+    return is_weak(&self.weak_strong);
+  }
+
+  // Unlike clang (with ARC enabled), objrs can't tell if a type is an object type, so properties
+  // without an explicit ownership attribute are treated as assign properties.
+  pub fn is_object(&self) -> bool {
+    return self.is_copy() || self.is_retain() || self.is_weak();
+  }
+
+  // Builds the property's attribute string, minus the leading type encoding (i.e. "T@"). See
+  // ASTContext::getObjCEncodingForPropertyDecl in clang for how these are built. The property's
+  // implementation determines whether the property is marked dynamic or has a backing ivar.
+  pub fn attributes(&self, implementation: PropertyImpl) -> String {
+    let mut attributes = String::new();
+    if self.readonly() {
+      attributes.push_str(",R");
+    }
+    if self.is_copy() {
+      attributes.push_str(",C");
+    } else if self.is_retain() {
+      attributes.push_str(",&");
+    } else if self.is_weak() {
+      attributes.push_str(",W");
+    }
+    if let PropertyImpl::Dynamic = implementation {
+      attributes.push_str(",D");
+    }
+    if self.nonatomic() {
+      attributes.push_str(",N");
+    }
+    if let Some(ref getter) = self.getter {
+      attributes.push_str(",G");
+      attributes.push_str(&getter.value());
+    }
+    if let Some(ref setter) = self.setter {
+      attributes.push_str(",S");
+      attributes.push_str(&setter.value());
+    }
+    if let PropertyImpl::Synthesized(ivar) = implementation {
+      attributes.push_str(",V");
+      attributes.push_str(ivar);
+    }
+    return attributes;
+  }
+
   pub fn getter(&self) -> LitStr {
     if let Some(ref getter) = self.getter {
       return getter.clone();
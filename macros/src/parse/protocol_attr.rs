@@ -24,7 +24,8 @@
 //! ```ignore
 //! #[objrs(protocol
 //!         [, name = "name"]
-//!         [, property(...)]
+//!         [, property(...)]*
+//!         [, extern]
 //!         [, objrs = IDENT]
 //!         [,])]
 //! trait ThisTraitIsAProtocol {
@@ -40,7 +41,15 @@
 //!   identifier (e.g. `trait NSCopying` → `"NSCopying"`). It is suggested you omit this and just
 //!   give the trait the same name as the protocol it represents. If you use this, additional
 //!   parameters must be passed to `#[objrs(...)]` attributes for implementations.
-//! - `property(...)`. Optional (repeated).
+//! - `property(...)`. Optional (repeated). Declares an Objective-C property. The property's getter
+//!   (and setter, unless the property is `readonly`) must be declared as a method in the trait.
+//! - `extern`. Optional. If this is omitted, objrs will treat the protocol as an external protocol
+//!   only if a `#[link(...)]` attribute is present. An external protocol is one declared by an
+//!   external framework (e.g. `NSCopying`, `MTLBuffer`, etc.). objrs emits the full protocol
+//!   definition (i.e. `protocol_t`) for custom (non-extern) protocols, so Objective-C code can use
+//!   `@protocol(...)` and `conformsToProtocol:` with them. Like clang, objrs emits the protocol
+//!   definition with weak linkage, so crates that define custom protocols must enable
+//!   `#![feature(linkage)]`. Custom protocols cannot be generic.
 //! - `objrs = IDENT`. Optional (default value: `objrs`). The name of the objrs crate. The macro
 //!   needs access to the objrs crate, and if you have renamed the crate, you must use this
 //!   parameter to inform the macro of the crate's name.
@@ -50,12 +59,13 @@
 //! Building on [Rust's syntax and EBNF dialect](https://doc.rust-lang.org/grammar.html):
 //!
 //! ```text
-//! objrs_protocol: '#' '[' "objrs" '(' "protocol" name? property* objrs? ','? ')' ']'
+//! objrs_protocol: '#' '[' "objrs" '(' "protocol" name? property* extern? objrs? ','? ')' ']'
 //! name: ',' "name" '=' string_lit
 //! property: ',' "property" '(' ident ':' type attributes ','? ')'
+//! extern: ',' "extern"
 //! objrs: ',' "objrs" '=' ident
 //!
-//! attributes: class? read_write? xor_attributes? atomicity? null_resettable? getter? setter?
+//! attributes: class? read_write? xor_attributes? atomicity? null_resettable? getter? setter? dynamic?
 //! xor_attributes: [copy_strong nullability?] | nullability | [weak restricted_nullability?]
 //! class: ',' "class"
 //! read_write: ',' ["readonly" | "readwrite"]
@@ -72,6 +82,7 @@
 //! null_resettable: ',' "null_resettable"
 //! getter: ',' "getter" '=' string_lit
 //! setter: ',' "setter" '=' string_lit
+//! dynamic: ',' "dynamic"
 //! ```
 
 // For Objective-C property attributes, see https://github.com/llvm-mirror/clang/blob/master/lib/Parse/ParseObjc.cpp
@@ -80,6 +91,7 @@ use crate::parse::attr::take_objrs_attr;
 use crate::parse::property_attr::PropertyAttr;
 use crate::parse::selector_attr::{ItemMethod, Method};
 use crate::parse::util::objrs_root;
+use crate::util::link_attribute;
 use proc_macro::Diagnostic;
 use proc_macro2::TokenStream;
 use std::collections::HashMap;
@@ -90,6 +102,7 @@ use syn::{parse2, Ident, ItemTrait, LitStr, TraitItem, TraitItemMethod};
 pub struct ProtocolAttr {
   pub name: Option<LitStr>,
   pub properties: Vec<PropertyAttr>,
+  pub force_extern: bool,
   pub objrs: Option<Ident>,
 }
 
@@ -104,11 +117,13 @@ impl Parse for ProtocolAttr {
     while let Some(property) = kv.parse::<property, _>()? {
       properties.push(property);
     }
+    let force_extern: Option<()> = kv.parse::<syn::token::Extern, _>()?;
     let objrs: Option<Ident> = kv.parse::<objrs, _>()?;
     kv.eof()?;
     return Ok(ProtocolAttr {
       name: name,
       properties: properties,
+      force_extern: force_extern.is_some(),
       objrs: objrs,
     });
   }
@@ -161,7 +176,9 @@ fn parse_method(mut method: TraitItemMethod) -> Result<Method, Diagnostic> {
 pub struct Protocol {
   pub objrs: Ident,
   pub trait_name: Option<LitStr>,
+  pub force_extern: bool,
   pub item: ItemTrait,
+  pub properties: Vec<PropertyAttr>,
   pub class_methods: Vec<Method>,
   pub instance_methods: Vec<Method>,
 }
@@ -182,7 +199,17 @@ impl Protocol {
       }
     };
 
-    // TODO: support custom protocols. Currently only extern protocols are supported.
+    let force_extern = attr.force_extern || link_attribute(&item.attrs).is_some();
+    if !force_extern && !item.generics.params.is_empty() {
+      return Err(
+        item
+          .generics
+          .span()
+          .unstable()
+          .error("custom protocols cannot be generic")
+          .note("only extern protocols may have generic parameters"),
+      );
+    }
 
     let mut expected_class_properties = HashMap::new();
     let mut expected_instance_properties = HashMap::new();
@@ -237,7 +264,9 @@ impl Protocol {
     return Ok(Protocol {
       objrs: objrs_root(attr.objrs),
       trait_name: attr.name,
+      force_extern: force_extern,
       item: item,
+      properties: attr.properties,
       class_methods: class_methods,
       instance_methods: instance_methods,
     });
@@ -8,6 +8,7 @@ extern crate syn;
 
 use proc_macro::Diagnostic;
 use proc_macro2::TokenStream;
+use quote::quote;
 use syn::{parse2, Ident, ItemStatic, LitStr, Type};
 
 // TODO: move this somewhere else.
@@ -112,6 +113,8 @@ pub struct SelRef {
   pub sel_string: String,
   pub ident: Ident,
   pub u8_ty: Box<Type>,
+  // The path to the objrs crate (e.g. `__objrs_root`).
+  pub objrs_root: TokenStream,
 }
 
 impl SelRef {
@@ -136,11 +139,23 @@ impl SelRef {
     let array = array.unwrap();
     let u8_ty = array.elem;
 
+    // The u8 type is `objrs::__objrs::u8` (see the `selector!` macro), for whatever path objrs is
+    // at.
+    let objrs_root = match *u8_ty {
+      Type::Path(ref ty) => {
+        let leading_colon = &ty.path.leading_colon;
+        let segments = ty.path.segments.iter().take(ty.path.segments.len().saturating_sub(2));
+        quote!(#leading_colon #(#segments)::*)
+      }
+      _ => panic!("BUG: the sel_ref's element type isn't a path"),
+    };
+
     return Ok(SelRef {
       sel: sel,
       sel_string: sel_string,
       ident: item.ident,
       u8_ty: u8_ty,
+      objrs_root: objrs_root,
     });
   }
 }
@@ -10,7 +10,9 @@
 //!   #[objrs(selector = "LITERAL_STR"
 //!           [, super|no_impl]
 //!           [, class|instance]
-//!           [, optional][,])]
+//!           [, optional]
+//!           [, method_family = IDENT]
+//!           [, catch_panic [= IDENT]][,])]
 //!   pub fn foo() -> usize {}
 //! }
 //! Use `#[objrs(selector)]` on a method to declare it as an Objective-C method.
@@ -31,6 +33,20 @@
 //! - `optional`. Optional. Equivalent to Objective-C's `@optional`. Only use this in protocol
 //!   traits. Attempting to call an `optional` method that the class doesn't implement will result
 //!   in a panic (or exception, if calling from Objective-C).
+//! - `method_family = IDENT`. Optional. One of `alloc`, `copy`, `init`, `mutableCopy`, `new`, or
+//!   `none`. Overrides the method family that objrs infers from the selector (like clang's
+//!   `objc_method_family` attribute). Methods in every family except `none` return a retained (+1)
+//!   object, and objrs warns if the method's return type suggests otherwise. Methods in the `none`
+//!   family return an unretained (+0) object, but may still return `Strong<T>` (message sends
+//!   retain the result with `objc_retainAutoreleasedReturnValue`).
+//! - `catch_panic` or `catch_panic = IDENT`. Optional. One of `abort`, `exception`, or `none`
+//!   (`catch_panic` on its own means `abort`). Only applies to methods implemented in Rust. A panic
+//!   must not unwind out of a method into the Objective-C code that called it, so with `abort`, a
+//!   panic in the method's body aborts the process (printing the method's name, e.g. `-[MyClass
+//!   doFoo:withBar:]`, and the panic message), and with `exception`, the panic is converted into an
+//!   `NSException` (whose reason holds the panic message) that is thrown to the caller. The default
+//!   is `none`, unless objrs's `catch-panic` feature is enabled (which makes it `abort`). Requires
+//!   objrs's `std` feature, and `exception` requires `#![feature(unwind_attributes)]`.
 //!
 //! Calling a super's method:
 //!
@@ -84,22 +100,26 @@
 //! Building on [Rust's syntax and EBNF dialect](https://doc.rust-lang.org/grammar.html):
 //!
 //! ```text
-//! objrs_protocol: '#' '[' "objrs" '(' "selector" '=' string_lit impl? type? optional? ','? ')' ']'
+//! objrs_protocol: '#' '[' "objrs" '(' "selector" '=' string_lit impl? type? optional? family? catch_panic? ','? ')' ']'
 //! impl: ',' ["super" | "no_impl"]
 //! type: ',' ["class" | "instance"]
 //! optional: ',' "optional"
+//! family: ',' "method_family" '=' ["alloc" | "copy" | "init" | "mutableCopy" | "new" | "none"]
+//! catch_panic: ',' "catch_panic" ('=' ["abort" | "exception" | "none"])?
 //! ```
 
 extern crate proc_macro2;
 extern crate syn;
 
+use crate::parse::catch_panic::CatchPanic;
+use crate::parse::method_family::{ownership_warning, pool_arg, MethodFamily};
 use crate::parse::sel_ref_attr::validate_selector;
 use proc_macro::Diagnostic;
 use proc_macro2::Span;
 use syn::parse::{Parse, ParseStream};
 use syn::{
   punctuated::Punctuated, spanned::Spanned, token::Comma, token::Default, Attribute, Block, FnArg,
-  ImplItemMethod, LitStr, Pat, PatType, Signature, TraitItemMethod, Type, Visibility,
+  Ident, ImplItemMethod, LitStr, Pat, PatType, Signature, TraitItemMethod, Type, Visibility,
 };
 
 pub struct SelectorAttr {
@@ -108,6 +128,20 @@ pub struct SelectorAttr {
   pub no_impl: bool,
   pub optional: Option<Span>,
   pub method_type: MethodType,
+  pub method_family: Option<MethodFamily>,
+  pub catch_panic: Option<CatchPanic>,
+}
+
+impl SelectorAttr {
+  // The explicit `method_family`, or the family inferred from the selector.
+  pub fn family(&self) -> MethodFamily {
+    return self.method_family.unwrap_or_else(|| MethodFamily::from_selector(&self.sel.value()));
+  }
+
+  // The explicit `catch_panic`, or the crate-wide default.
+  pub fn catch_panic(&self) -> CatchPanic {
+    return self.catch_panic.unwrap_or_else(CatchPanic::crate_default);
+  }
 }
 
 #[derive(PartialEq)]
@@ -119,7 +153,9 @@ pub enum MethodType {
 
 impl Parse for SelectorAttr {
   fn parse(input: ParseStream) -> syn::parse::Result<Self> {
-    use crate::parse::attr::{class, instance, no_impl, optional, selector, KV};
+    use crate::parse::attr::{
+      catch_panic, class, instance, method_family, no_impl, optional, selector, KV,
+    };
     use syn::parenthesized;
 
     let content;
@@ -133,6 +169,16 @@ impl Parse for SelectorAttr {
     let class: Option<()> = kv.parse::<class, _>()?;
     let instance: Option<()> = if class.is_some() { None } else { kv.parse::<instance, _>()? };
     let optional: Option<Span> = kv.parse::<optional, _>()?;
+    let method_family: Option<Ident> = kv.parse::<method_family, _>()?;
+    let method_family = method_family.map(|ident| MethodFamily::from_ident(&ident)).transpose()?;
+    let catch_panic_span = input.span();
+    let catch_panic: Option<CatchPanic> = kv.parse::<catch_panic, _>()?;
+    if catch_panic.is_some() && no_impl.is_some() {
+      return Err(syn::parse::Error::new(
+        catch_panic_span,
+        "`catch_panic` only applies to methods implemented in Rust",
+      ));
+    }
     let method_type;
     if class.is_some() {
       method_type = MethodType::Class;
@@ -148,6 +194,8 @@ impl Parse for SelectorAttr {
       no_impl: no_impl.is_some(),
       optional: optional,
       method_type: method_type,
+      method_family: method_family,
+      catch_panic: catch_panic,
     });
   }
 }
@@ -206,6 +254,8 @@ pub struct Method {
   pub method: ItemMethod,
   pub is_instance_method: bool,
   pub is_generic: bool,
+  // The index of the method's `&AutoreleasePool` parameter, which isn't sent to Objective-C.
+  pub pool_arg: Option<usize>,
 }
 
 impl Method {
@@ -225,14 +275,21 @@ impl Method {
       || (attr.method_type == MethodType::Auto && is_instance_method(&sig.inputs));
 
     let (sel_string, expected_arg_count) = validate_selector(&attr.sel)?;
-    let arg_count = sig.inputs.len() - is_instance_method as usize;
+    let pool_arg = pool_arg(sig)?;
+    let arg_count = sig.inputs.len() - is_instance_method as usize - pool_arg.is_some() as usize;
     if arg_count != expected_arg_count {
+      let not_counting = match (is_instance_method, pool_arg.is_some()) {
+        (true, true) => " (not counting self and the autorelease pool)",
+        (true, false) => " (not counting self)",
+        (false, true) => " (not counting the autorelease pool)",
+        (false, false) => "",
+      };
       let error_msg = format!(
         "method `{}` has {} parameter{}{} but the selector has {}",
         sig.ident,
         arg_count,
         plural_s(arg_count),
-        if is_instance_method { " (not counting self)" } else { "" },
+        not_counting,
         expected_arg_count
       );
       let note_msg = format!(
@@ -267,6 +324,10 @@ impl Method {
       );
     }
 
+    if let Some(warning) = ownership_warning(attr.family(), &attr.sel, &sig.output) {
+      warning.emit();
+    }
+
     let is_generic = !sig.generics.params.is_empty() || sig.inputs.iter().any(is_impl_trait);
 
     return Ok(Method {
@@ -274,6 +335,7 @@ impl Method {
       method: method,
       is_instance_method: is_instance_method,
       is_generic: is_generic,
+      pool_arg: pool_arg,
     });
   }
 
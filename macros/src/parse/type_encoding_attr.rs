@@ -0,0 +1,194 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+//! The `#[derive(TypeEncoding)]` macro parser module.
+//!
+//! Apply `#[derive(TypeEncoding)]` to a `#[repr(C)]` `struct` or `union` item to implement the
+//! `TypeEncoding` trait for it. Structs are encoded as `{Name=fields}` and unions are encoded as
+//! `(Name=fields)`, where `fields` is the concatenation of the fields' encodings (in declaration
+//! order). The name defaults to the Rust type's name, and can be overridden with an
+//! `#[objrs(encoding_name = "...")]` attribute (e.g., if the Rust type's name differs from the C
+//! struct's tag). The attribute must come after `#[derive(TypeEncoding)]`, so that it's a derive
+//! helper attribute rather than an invocation of the `#[objrs]` attribute macro.
+//!
+//! ```ignore
+//! #[derive(TypeEncoding)]
+//! #[objrs([encoding_name = "ExportName"][, objrs = IDENT][,])]
+//! #[repr(C)]
+//! struct Foo {
+//!   ...
+//! }
+//! ```
+//!
+//! # Syntax
+//!
+//! Building on [Rust's syntax and EBNF dialect](https://doc.rust-lang.org/grammar.html):
+//!
+//! ```text
+//! type_encoding: '#' '[' "objrs" '(' encoding_name? objrs? ','? ')' ']'
+//! encoding_name: "encoding_name" '=' string_lit
+//! objrs: ',' "objrs" '=' ident
+//! ```
+
+extern crate proc_macro2;
+extern crate syn;
+
+use crate::parse::util::objrs_root;
+use proc_macro::Diagnostic;
+use proc_macro2::TokenStream;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{parse2, Attribute, Data, DeriveInput, Generics, Ident, LitStr, Meta, NestedMeta, Type};
+
+pub struct TypeEncodingAttr {
+  pub encoding_name: Option<LitStr>,
+  pub objrs: Option<Ident>,
+}
+
+impl Parse for TypeEncodingAttr {
+  fn parse(input: ParseStream) -> syn::parse::Result<Self> {
+    use crate::parse::attr::{encoding_name, objrs, KV};
+    use syn::parenthesized;
+    use syn::token::Paren;
+
+    let content;
+    let _: Paren = parenthesized!(content in input);
+    let input = &content;
+
+    let mut kv = KV::new(input);
+    let encoding_name: Option<LitStr> = kv.parse::<encoding_name, _>()?;
+    let objrs: Option<Ident> = kv.parse::<objrs, _>()?;
+    kv.eof()?;
+    return Ok(TypeEncodingAttr {
+      encoding_name: encoding_name,
+      objrs: objrs,
+    });
+  }
+}
+
+pub enum Aggregate {
+  Struct,
+  Union,
+}
+
+pub struct DeriveTypeEncoding {
+  pub objrs: Ident,
+  pub aggregate: Aggregate,
+  pub encoding_name: LitStr,
+  pub ident: Ident,
+  pub generics: Generics,
+  pub fields: Vec<Type>,
+}
+
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+  for attr in attrs {
+    if !attr.path.is_ident("repr") {
+      continue;
+    }
+    if let Ok(Meta::List(list)) = attr.parse_meta() {
+      for nested in list.nested.iter() {
+        match nested {
+          NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("C") => return true,
+          _ => continue,
+        }
+      }
+    }
+  }
+  return false;
+}
+
+fn take_type_encoding_attr(attrs: &[Attribute]) -> Result<TypeEncodingAttr, Diagnostic> {
+  let mut type_encoding_attrs = attrs.iter().filter(|attr| attr.path.is_ident("objrs"));
+  let attr = type_encoding_attrs.next();
+  if let Some(duplicate_attr) = type_encoding_attrs.next() {
+    return Err(
+      duplicate_attr.span().unstable().error("unexpected secondary objrs attribute found"),
+    );
+  }
+  match attr {
+    Some(attr) => {
+      let span = attr.span();
+      return parse2(attr.tokens.clone()).map_err(|e| span.unstable().error(e.to_string()));
+    }
+    None => {
+      return Ok(TypeEncodingAttr {
+        encoding_name: None,
+        objrs: None,
+      });
+    }
+  }
+}
+
+impl DeriveTypeEncoding {
+  pub fn new(input: TokenStream) -> Result<DeriveTypeEncoding, Diagnostic> {
+    let item;
+    match parse2::<DeriveInput>(input) {
+      Ok(value) => item = value,
+      Err(error) => {
+        return Err(
+          error
+            .span()
+            .unstable()
+            .error(format!("failed to parse item: {}", error.to_string()))
+            .note("#[derive(TypeEncoding)] must only be applied to a struct or union item"),
+        );
+      }
+    }
+
+    let attr = take_type_encoding_attr(&item.attrs)?;
+    let encoding_name =
+      attr.encoding_name.unwrap_or_else(|| LitStr::new(&item.ident.to_string(), item.ident.span()));
+    if encoding_name.value().is_empty() {
+      return Err(encoding_name.span().unstable().error("the encoding name must not be empty"));
+    }
+
+    let aggregate;
+    let fields;
+    match item.data {
+      Data::Struct(data) => {
+        aggregate = Aggregate::Struct;
+        fields = data.fields.into_iter().map(|field| field.ty).collect();
+      }
+      Data::Union(data) => {
+        aggregate = Aggregate::Union;
+        fields = data.fields.named.into_iter().map(|field| field.ty).collect();
+      }
+      Data::Enum(data) => {
+        return Err(
+          data
+            .enum_token
+            .span()
+            .unstable()
+            .error("#[derive(TypeEncoding)] does not support enums")
+            .note("only #[repr(C)] structs and unions can derive TypeEncoding"),
+        );
+      }
+    }
+
+    if !is_repr_c(&item.attrs) {
+      return Err(
+        item
+          .ident
+          .span()
+          .unstable()
+          .error("#[derive(TypeEncoding)] requires a #[repr(C)] struct or union")
+          .note(
+            "Objective-C type encodings describe C types, and the layout of a type that isn't \
+             #[repr(C)] is unspecified",
+          )
+          .help("add #[repr(C)] to the item"),
+      );
+    }
+
+    return Ok(DeriveTypeEncoding {
+      objrs: objrs_root(attr.objrs),
+      aggregate: aggregate,
+      encoding_name: encoding_name,
+      ident: item.ident,
+      generics: item.generics,
+      fields: fields,
+    });
+  }
+}
@@ -10,16 +10,18 @@ extern crate quote;
 extern crate syn;
 
 use crate::gen::gen_selector::gen_msg_recv;
+use crate::gen::sel_ref::gen_sel_ref;
 use crate::parse::attr::take_objrs_attr;
+use crate::parse::method_family::{is_pool_arg, returns_autoreleased_owned};
+use crate::parse::sel_ref_attr::SelRef;
 use crate::parse::selector_attr::{ItemMethod, Method, MethodType, SelectorAttr};
-use crate::util::{is_instance_method, priv_ident_at, RandomIdentifier};
+use crate::util::{is_instance_method, priv_ident_at, require_apple_runtime, AppleOnly};
 use proc_macro::Diagnostic;
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use syn::{
   parse2, parse_quote, punctuated::Punctuated, spanned::Spanned, token::Comma, token::Extern, Abi,
-  Expr, FnArg, GenericParam, Ident, ImplItemMethod, LitByteStr, LitStr, Pat, PatIdent, ReturnType,
-  Stmt, Type,
+  Expr, FnArg, GenericParam, Ident, ImplItemMethod, LitStr, Pat, PatIdent, ReturnType, Stmt, Type,
 };
 
 pub struct ObjrsMethod {
@@ -64,6 +66,16 @@ pub fn parse_selector_method(
     force_extern = true;
   }
 
+  if let (Some(pool_arg), false) = (method.pool_arg, force_extern) {
+    return Err(
+      method.sig().inputs[pool_arg]
+        .span()
+        .unstable()
+        .error("only extern methods can take an autorelease pool")
+        .note("methods implemented in Rust should return a `Strong<T>` instead of an `Auto`"),
+    );
+  }
+
   let msg_recv;
   if force_extern {
     msg_recv = None;
@@ -80,6 +92,7 @@ pub fn parse_selector_method(
       method.impl_method().cloned().unwrap(), // TODO: this is an ugly hack.
       is_generic_class,
       empty_msg_recv,
+      force_extern,
       None,
       objrs_root,
     )?);
@@ -93,17 +106,40 @@ pub fn parse_selector_method(
   }));
 }
 
-fn msg_send_fn(
-  selector: &LitStr,
+// The method that a msg_send function is generated for, and how it sends its message.
+struct MsgSend<'a> {
+  selector: &'a LitStr,
   call_super: bool,
-  method: &ImplItemMethod,
-  name: &Ident,
-  inline: &dyn ToTokens,
+  method: &'a ImplItemMethod,
+  // The name of the generated msg_send function.
+  name: &'a Ident,
+  inline: &'a dyn ToTokens,
   is_instance_method: bool,
   is_generic_class: bool,
-  objrs_root: &Ident,
-) -> Result<TokenStream, Diagnostic> {
-  let mut selector_string = selector.value();
+  verify_signature: bool,
+  // Whether the send claims an autoreleased (+0) result (see returns_autoreleased_owned).
+  claim_autoreleased: bool,
+  objrs_root: &'a Ident,
+}
+
+fn msg_send_fn(send: MsgSend) -> Result<TokenStream, Diagnostic> {
+  let MsgSend {
+    selector,
+    call_super,
+    method,
+    name,
+    inline,
+    is_instance_method,
+    is_generic_class,
+    verify_signature,
+    claim_autoreleased,
+    objrs_root,
+  } = send;
+  let selector_string = selector.value();
+
+  if call_super {
+    require_apple_runtime(selector.span(), AppleOnly::SuperCall)?;
+  }
 
   let objc_send;
   let objc_send_stret;
@@ -115,17 +151,14 @@ fn msg_send_fn(
     objc_send_stret = quote!(objc_msgSend_stret);
   }
 
-  let random_id = &RandomIdentifier::new();
-
-  let meth_name_export_name =
-    ["\x01L_OBJC_METH_VAR_NAME_.__objrs_meth.", random_id, ".", &selector_string].concat();
-
-  let sel_ref_export_name =
-    ["\x01L_OBJC_SELECTOR_REFERENCES_.__objrs_sel.", random_id, ".", &selector_string].concat();
-
-  selector_string.push('\x00');
-  let selector = LitByteStr::new(selector_string.as_bytes(), selector.span());
-  let selector_len = selector_string.len();
+  let selector_len = selector_string.len() + 1; // Include the NUL terminator.
+  let sel_ref = gen_sel_ref(SelRef {
+    sel: selector.clone(),
+    sel_string: selector_string,
+    ident: Ident::new("SEL_REF", Span::call_site()),
+    u8_ty: parse_quote!(#objrs_root::__objrs::u8),
+    objrs_root: quote!(#objrs_root),
+  })?;
 
   let empty_tuple = parse_quote!(());
   let return_type = match method.sig.output {
@@ -210,9 +243,19 @@ fn msg_send_fn(
   // TODO: handle variadic.
   let output = &method.sig.output;
 
+  // The autorelease pool (if any) isn't sent. The send checks that it's the innermost pool, since
+  // that's the pool that owns the returned object.
   let mut tail_arg_types: Punctuated<&Type, Comma> = Punctuated::new();
   let mut tail_arg_values: Punctuated<&Ident, Comma> = Punctuated::new();
+  let mut check_pool = TokenStream::new();
   for arg in inputs.iter().skip(2) {
+    if let (FnArg::Typed(ref pat_ty), true) = (arg, is_pool_arg(arg)) {
+      if let Pat::Ident(ref ident) = *pat_ty.pat {
+        let ident = &ident.ident;
+        check_pool = quote!(#native_ty::assert_innermost_pool(#ident););
+        continue;
+      }
+    }
     if let FnArg::Typed(ref pat_ty) = arg {
       tail_arg_types.push(&pat_ty.ty);
       if let Pat::Ident(ref ident) = *pat_ty.pat {
@@ -227,6 +270,52 @@ fn msg_send_fn(
 
   let unsafety = &method.sig.unsafety;
 
+  // If the selector returns an autoreleased (+0) object but the method returns an owned one, the
+  // message send returns a raw pointer that's immediately claimed with
+  // objc_retainAutoreleasedReturnValue (see parse::method_family::returns_autoreleased_owned).
+  let send_output;
+  let send_result;
+  if claim_autoreleased {
+    send_output = quote!(-> *mut #native_ty::runtime::Id);
+    send_result = quote!(#native_ty::retain_autoreleased_return_value(msg_send(#self_arg_value, sel, #tail_arg_values)));
+  } else {
+    send_output = quote!(#output);
+    send_result = quote!(msg_send(#self_arg_value, sel, #tail_arg_values));
+  }
+
+  // With the verify-signatures feature, extern methods check their signature against the runtime's
+  // type encoding the first time they're called. Super calls are skipped since the receiver is an
+  // objc_super (and the superclass's signature was already verified by non-super calls).
+  let verify;
+  if cfg!(feature = "verify-signatures") && verify_signature && !call_super {
+    let mut type_encodings = TokenStream::new();
+    let mut type_encoding_bytes: Punctuated<TokenStream, Comma> = Punctuated::new();
+    for (i, ty) in core::iter::once(return_type).chain(tail_arg_types.iter().cloned()).enumerate() {
+      let type_encoding = Ident::new(&format!("type_encoding_{}", i), Span::call_site());
+      type_encodings.extend(quote! {
+        let #type_encoding = <#ty as #native_ty::TypeEncodingHack>::BYTES;
+      });
+      type_encoding_bytes.push(quote!(#native_ty::type_encoding_bytes(&#type_encoding)));
+    }
+    verify = quote! {
+      static VERIFIED: #native_ty::core::sync::atomic::AtomicBool = #native_ty::core::sync::atomic::AtomicBool::new(false);
+      if !VERIFIED.load(#native_ty::core::sync::atomic::Ordering::Relaxed) {
+        #type_encodings
+        unsafe {
+          #native_ty::verify_method_type(
+            &VERIFIED,
+            #native_ty::core::mem::transmute_copy(&#self_arg_value),
+            #native_ty::core::mem::transmute::<_, &'static #objrs_root::Sel>(sel),
+            #is_instance_method,
+            &[#type_encoding_bytes],
+          )
+        };
+      }
+    };
+  } else {
+    verify = TokenStream::new();
+  }
+
   let msg_send = quote! {
     // TODO: can this be inline(always) for LTO builds?
     #[allow(dead_code)]
@@ -234,13 +323,7 @@ fn msg_send_fn(
     #[doc(hidden)]
     #[allow(non_upper_case_globals)]
     #unsafety extern "C" fn #name #generics(#inputs) #output #where_clause {
-      #[link_section = "__TEXT,__objc_methname,cstring_literals"]
-      #[export_name = #meth_name_export_name]
-      static METH_NAME: [#native_ty::u8; #selector_len] = * #selector;
-
-      #[link_section = "__DATA,__objc_selrefs,literal_pointers,no_dead_strip"]
-      #[export_name = #sel_ref_export_name]
-      static SEL_REF: &'static [#native_ty::u8; #selector_len] = &METH_NAME;
+      #sel_ref
 
       #[cfg(target_arch = "x86_64")]
       let msg_send = if #objrs_root::__objrs::core::mem::size_of::<#return_type>() <= 16 {
@@ -252,11 +335,15 @@ fn msg_send_fn(
       let msg_send = #objrs_root::__objrs::runtime::objc_msgSend;
 
       // Use a pointer (*const [#native_ty::u8; N]) for the selector type rather than a reference (&[#native_ty::u8; N]). If we used a reference, users would have to explicitly annotate most functions with lifetime parameters.
-      let msg_send: unsafe extern fn(#self_arg_type, *const [#native_ty::u8; #selector_len], #tail_arg_types) #output = unsafe { #objrs_root::__objrs::core::mem::transmute(msg_send as *const ()) };
+      let msg_send: unsafe extern fn(#self_arg_type, *const [#native_ty::u8; #selector_len], #tail_arg_types) #send_output = unsafe { #objrs_root::__objrs::core::mem::transmute(msg_send as *const ()) };
 
       #ref_hack
 
-      return unsafe { msg_send(#self_arg_value, sel, #tail_arg_values) };
+      #verify
+
+      #check_pool
+
+      return unsafe { #send_result };
 
       // compile_error!("impl blocks must use the #[objrs(impl)] attribute");
     }
@@ -299,6 +386,7 @@ pub fn transform_selector(
   mut method: ImplItemMethod,
   is_generic_class: bool,
   empty_msg_recv: bool,
+  verify_signature: bool,
   fn_span: Option<Span>,
   objrs_root: &Ident,
 ) -> Result<TokenStream, Diagnostic> {
@@ -461,16 +549,18 @@ pub fn transform_selector(
   })));
 
   // let msg_send = gen_msg_send(method, class_name, is_generic_class, objrs_root);
-  let msg_send = msg_send_fn(
-    &attr.sel,
-    attr.call_super,
-    &method,
-    &msg_send_name,
-    &inline,
-    is_instance_method,
-    is_generic_class,
-    objrs_root,
-  )?;
+  let msg_send = msg_send_fn(MsgSend {
+    selector: &attr.sel,
+    call_super: attr.call_super,
+    method: &method,
+    name: &msg_send_name,
+    inline: &inline,
+    is_instance_method: is_instance_method,
+    is_generic_class: is_generic_class,
+    verify_signature: verify_signature,
+    claim_autoreleased: returns_autoreleased_owned(attr.family(), &method.sig.output),
+    objrs_root: objrs_root,
+  })?;
 
   let msg_recv;
   if empty_msg_recv {
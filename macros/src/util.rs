@@ -49,6 +49,56 @@ pub fn is_instance_method(args: &Punctuated<FnArg, Comma>) -> bool {
   }
 }
 
+// The GNUstep runtime (libobjc2's v2 ABI) is only supported for using existing classes, categories,
+// and protocols. Defining new ones requires emitting libobjc2's class structures, which objrs doesn't
+// do yet (and super calls are only useful when defining a class).
+// The constructs that require the Apple runtime.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppleOnly {
+  Class,
+  Category,
+  Protocol,
+  SuperCall,
+}
+
+impl AppleOnly {
+  fn description(self) -> &'static str {
+    match self {
+      AppleOnly::Class => return "defining an Objective-C class",
+      AppleOnly::Category => return "defining an Objective-C category",
+      AppleOnly::Protocol => return "defining an Objective-C protocol",
+      AppleOnly::SuperCall => return "calling a `super` method",
+    }
+  }
+}
+
+// Returns the error message if the construct isn't supported by the selected runtime. This is split
+// from `require_apple_runtime` so it can be tested (diagnostics can only be created inside a macro).
+fn unsupported_runtime_message(construct: AppleOnly, gnustep: bool) -> Option<String> {
+  if gnustep {
+    return Some(format!(
+      "{} is not supported with the runtime-gnustep feature",
+      construct.description()
+    ));
+  }
+  return None;
+}
+
+pub fn require_apple_runtime(
+  span: proc_macro2::Span,
+  construct: AppleOnly,
+) -> Result<(), proc_macro::Diagnostic> {
+  if let Some(message) = unsupported_runtime_message(construct, cfg!(feature = "runtime-gnustep")) {
+    return Err(
+      span
+        .unstable()
+        .error(message)
+        .note("with the GNUstep runtime, objrs can only use existing Objective-C definitions"),
+    );
+  }
+  return Ok(());
+}
+
 pub fn priv_ident(ident: &str) -> proc_macro2::Ident {
   return priv_ident_at(ident, proc_macro2::Span::call_site());
 }
@@ -189,3 +239,33 @@ fn drain_test() {
   }
   assert_eq!(v, vec![1, 3, 4]);
 }
+
+#[cfg(test)]
+#[test]
+fn gnustep_rejects_apple_only_features() {
+  let cases = [
+    (
+      AppleOnly::Class,
+      "defining an Objective-C class is not supported with the runtime-gnustep feature",
+    ),
+    (
+      AppleOnly::Category,
+      "defining an Objective-C category is not supported with the runtime-gnustep feature",
+    ),
+    (
+      AppleOnly::Protocol,
+      "defining an Objective-C protocol is not supported with the runtime-gnustep feature",
+    ),
+    (
+      AppleOnly::SuperCall,
+      "calling a `super` method is not supported with the runtime-gnustep feature",
+    ),
+  ];
+  for &(construct, message) in cases.iter() {
+    assert_eq!(
+      unsupported_runtime_message(construct, true).as_ref().map(String::as_str),
+      Some(message)
+    );
+    assert_eq!(unsupported_runtime_message(construct, false), None);
+  }
+}
@@ -7,12 +7,47 @@ pub extern crate core;
 pub extern crate libc;
 pub extern crate objrs_macros;
 
-use crate::{marker, TypeEncoding};
+use crate::{arc, marker, PointeeTypeEncoding, Strong, TypeEncoding};
 
 pub mod runtime {
   pub use crate::runtime::*;
 }
 
+#[cfg(feature = "std")]
+pub use crate::catch_panic::{abort_on_panic, throw_on_panic};
+
+// Catching panics requires std (for `catch_unwind`). Methods that use `catch_panic` invoke this
+// macro first, so that using `catch_panic` without the `std` feature reports a clear error rather
+// than an unresolved `abort_on_panic` or `throw_on_panic`.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __objrs_catch_panic_requires_std {
+  () => {};
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __objrs_catch_panic_requires_std {
+  () => {
+    compile_error!("`catch_panic` requires objrs's `std` feature");
+  };
+}
+
+// Stand-ins for the real functions, so that only the error above is reported without std.
+#[cfg(not(feature = "std"))]
+pub fn abort_on_panic<R, F: FnOnce() -> R>(_method: &str, f: F) -> R {
+  return f();
+}
+
+#[cfg(not(feature = "std"))]
+pub fn throw_on_panic<R, F: FnOnce() -> R>(_method: &str, f: F) -> R {
+  return f();
+}
+#[cfg(feature = "verify-signatures")]
+pub use crate::verify::verify_method_type;
+
 mod primitive_types {
   pub type U8 = u8;
   pub type U16 = u16;
@@ -105,6 +140,79 @@ pub union TransmuteHack<T: Copy, U: Copy> {
   pub to: U,
 }
 
+// The most protocols a custom class can adopt with `impl Protocol for Class` blocks.
+pub const MAX_CLASS_PROTOCOLS: usize = 64;
+
+// A custom class's protocol_list_t, which its class_ro_t's base_protocols points to. The
+// #[objrs(class)] macro defines one for each custom class, and each #[objrs(impl)] block that
+// implements a protocol for the class adds the protocol to it from a constructor. The runtime reads
+// the list through the class_ro_t's pointer whenever it's queried, so it sees every protocol that
+// was added before the first query.
+#[repr(C)]
+pub struct ClassProtocolList {
+  count: core::cell::UnsafeCell<usize>,
+  protocols: core::cell::UnsafeCell<[*const runtime::protocol_t; MAX_CLASS_PROTOCOLS]>,
+  // Like clang's protocol lists, the list is null terminated.
+  null: usize,
+}
+
+unsafe impl Sync for ClassProtocolList {}
+
+impl ClassProtocolList {
+  pub const fn new() -> ClassProtocolList {
+    return ClassProtocolList {
+      count: core::cell::UnsafeCell::new(0),
+      protocols: core::cell::UnsafeCell::new([core::ptr::null(); MAX_CLASS_PROTOCOLS]),
+      null: 0,
+    };
+  }
+
+  // Only call this from a constructor (i.e., before any other thread can read the list).
+  pub unsafe fn add(&self, protocol: *const runtime::protocol_t) {
+    let count = *self.count.get();
+    if count == MAX_CLASS_PROTOCOLS {
+      panic!("a class can't adopt more than {} protocols", MAX_CLASS_PROTOCOLS);
+    }
+    (*self.protocols.get())[count] = protocol;
+    *self.count.get() = count + 1;
+  }
+}
+
+// Message sends and method implementations whose Rust return type is an owned object (e.g.
+// `Strong<T>` or `Option<Strong<T>>`) but whose selector returns an autoreleased (+0) object use
+// these to convert between the two. The runtime only lets the result skip the autorelease pool if
+// the caller's send is immediately followed by a marker instruction, which objrs doesn't emit, so
+// results of objrs's message sends still go through the innermost pool (ARC callers of objrs method
+// implementations do skip it).
+#[inline(always)]
+pub unsafe fn retain_autoreleased_return_value<T>(obj: *mut runtime::Id) -> T {
+  let objc_retain_autoreleased_return_value: unsafe extern "C" fn(*mut runtime::Id) -> T =
+    core::mem::transmute(runtime::objc_retainAutoreleasedReturnValue as *const ());
+  return objc_retain_autoreleased_return_value(obj);
+}
+
+#[inline(always)]
+pub unsafe fn autorelease_return_value<T>(obj: T) -> *mut runtime::Id {
+  let objc_autorelease_return_value: unsafe extern "C" fn(T) -> *mut runtime::Id =
+    core::mem::transmute(runtime::objc_autoreleaseReturnValue as *const ());
+  return objc_autorelease_return_value(obj);
+}
+
+// Message sends that return an `Auto<'pool, T>` take the pool, and check that it's the innermost
+// pool (which the runtime autoreleases the result into).
+#[inline(always)]
+pub fn assert_innermost_pool(pool: &arc::AutoreleasePool) {
+  pool.assert_innermost();
+}
+
+/// Views a (packed) type encoding as a byte slice. Used with `TypeEncodingHack::BYTES`.
+#[inline(always)]
+pub fn type_encoding_bytes<T: Copy>(encoding: &T) -> &[u8] {
+  return unsafe {
+    core::slice::from_raw_parts(encoding as *const T as *const u8, core::mem::size_of::<T>())
+  };
+}
+
 #[repr(C, packed)]
 pub struct Packed2<T: Copy, U: Copy>(pub T, pub U);
 impl<T: Copy, U: Copy> Clone for Packed2<T, U> {
@@ -115,6 +223,20 @@ impl<T: Copy, U: Copy> Clone for Packed2<T, U> {
 impl<T: Copy, U: Copy> Copy for Packed2<T, U> {}
 unsafe impl<T: Send + Copy, U: Send + Copy> Send for Packed2<T, U> {}
 unsafe impl<T: Sync + Copy, U: Sync + Copy> Sync for Packed2<T, U> {}
+impl<T: Copy + PartialEq, U: Copy + PartialEq> PartialEq for Packed2<T, U> {
+  fn eq(&self, other: &Self) -> bool {
+    // Fields are copied out first since references to packed fields may be unaligned.
+    let (a, b) = (self.0, self.1);
+    let (other_a, other_b) = (other.0, other.1);
+    return a == other_a && b == other_b;
+  }
+}
+impl<T: Copy + core::fmt::Debug, U: Copy + core::fmt::Debug> core::fmt::Debug for Packed2<T, U> {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let (a, b) = (self.0, self.1);
+    return f.debug_tuple("Packed2").field(&a).field(&b).finish();
+  }
+}
 
 #[repr(C, packed)]
 pub struct Packed3<T: Copy, U: Copy, V: Copy>(pub T, pub U, pub V);
@@ -126,6 +248,21 @@ impl<T: Copy, U: Copy, V: Copy> Clone for Packed3<T, U, V> {
 impl<T: Copy, U: Copy, V: Copy> Copy for Packed3<T, U, V> {}
 unsafe impl<T: Send + Copy, U: Send + Copy, V: Send + Copy> Send for Packed3<T, U, V> {}
 unsafe impl<T: Sync + Copy, U: Sync + Copy, V: Sync + Copy> Sync for Packed3<T, U, V> {}
+impl<T: Copy + PartialEq, U: Copy + PartialEq, V: Copy + PartialEq> PartialEq for Packed3<T, U, V> {
+  fn eq(&self, other: &Self) -> bool {
+    let (a, b, c) = (self.0, self.1, self.2);
+    let (other_a, other_b, other_c) = (other.0, other.1, other.2);
+    return a == other_a && b == other_b && c == other_c;
+  }
+}
+impl<T: Copy + core::fmt::Debug, U: Copy + core::fmt::Debug, V: Copy + core::fmt::Debug>
+  core::fmt::Debug for Packed3<T, U, V>
+{
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let (a, b, c) = (self.0, self.1, self.2);
+    return f.debug_tuple("Packed3").field(&a).field(&b).field(&c).finish();
+  }
+}
 
 const fn log_2(value: usize) -> u32 {
   return 0usize.count_zeros() - (value != 0) as u32 - value.leading_zeros();
@@ -243,6 +380,20 @@ pub const fn to_ascii_hack(value: usize) -> ToAsciiHack2 {
   };
 }
 
+// Objective-C method type encodings promote integer arguments (including bools, and enums, which
+// are encoded as their underlying integer type) that are smaller than an int to the size of an int
+// when computing frame offsets. Other small arguments (e.g. small structs and arrays) aren't
+// promoted. `encoding` is the first byte of the argument's type encoding.
+pub const fn promoted_arg_size(size: usize, encoding: u8) -> usize {
+  const INT_SIZE: usize = core::mem::size_of::<i32>();
+  let is_integer = (encoding == b'c')
+    | (encoding == b'C')
+    | (encoding == b's')
+    | (encoding == b'S')
+    | (encoding == b'B');
+  return [size, INT_SIZE][(is_integer & (size < INT_SIZE)) as usize];
+}
+
 pub trait ToAsciiHack {
   const VALUE: usize;
 
@@ -467,6 +618,173 @@ impl<T: ?Sized + marker::Weak> IsWeak for T {
   const VALUE: bool = true;
 }
 
+pub trait IsStrong {
+  const VALUE: bool;
+}
+
+impl<T: ?Sized> IsStrong for T {
+  default const VALUE: bool = false;
+}
+
+impl<T: marker::Class + ?Sized> IsStrong for Strong<T> {
+  const VALUE: bool = true;
+}
+
+impl<T: marker::Class + ?Sized> IsStrong for Option<Strong<T>> {
+  const VALUE: bool = true;
+}
+
+// An ivar's offset (from the start of the object), its size, and whether it's included in the
+// layout (i.e., whether it's a strong or weak pointer, depending on the layout).
+pub type IvarLayoutEntry = (usize, usize, bool);
+
+// Builds an ARC ivar layout (the class_ro_t's ivarLayout or weakIvarLayout), matching clang's
+// IvarLayoutBuilder. The layout is a string of bytes, each of which skips a number of words (the
+// high nibble) and then scans a number of words (the low nibble). It starts at the class's instance
+// start (rounded up to a whole word) and omits trailing skips. Ivars that aren't word-aligned can't
+// be described, so they're omitted (as clang does).
+//
+// Only the first N bytes are stored, but `len` is always the full length (not including the NUL
+// terminator). Call `ivar_layout::<0>` to find the length, and then `ivar_layout::<{ LEN + 1 }>` to
+// get the NUL-terminated bytes.
+pub struct IvarLayout<const N: usize> {
+  pub bytes: [u8; N],
+  pub len: usize,
+  last: u8,
+}
+
+const WORD_SIZE: usize = core::mem::size_of::<usize>();
+const MAX_NIBBLE: usize = 0x0f;
+
+const fn min(a: usize, b: usize) -> usize {
+  return [a, b][(b < a) as usize];
+}
+
+impl<const N: usize> IvarLayout<N> {
+  const fn push(mut self, byte: u8) -> Self {
+    if self.len < N {
+      self.bytes[self.len] = byte;
+    }
+    self.len += 1;
+    self.last = byte;
+    return self;
+  }
+
+  const fn replace_last(mut self, byte: u8) -> Self {
+    if self.len - 1 < N {
+      self.bytes[self.len - 1] = byte;
+    }
+    self.last = byte;
+    return self;
+  }
+
+  const fn skip(mut self, mut words: usize) -> Self {
+    // Scans happen after skips, so a skip can only be merged into the previous byte if it doesn't
+    // scan anything.
+    if self.len > 0 && self.last & 0x0f == 0 {
+      let last_skip = (self.last >> 4) as usize;
+      let claimed = min(MAX_NIBBLE - last_skip, words);
+      words -= claimed;
+      self = self.replace_last(((last_skip + claimed) << 4) as u8);
+    }
+    while words >= MAX_NIBBLE {
+      self = self.push((MAX_NIBBLE << 4) as u8);
+      words -= MAX_NIBBLE;
+    }
+    if words > 0 {
+      self = self.push((words << 4) as u8);
+    }
+    return self;
+  }
+
+  const fn scan(mut self, mut words: usize) -> Self {
+    if self.len > 0 {
+      let last = self.last;
+      let last_scan = (last & 0x0f) as usize;
+      let claimed = min(MAX_NIBBLE - last_scan, words);
+      words -= claimed;
+      self = self.replace_last((last & 0xf0) | (last_scan + claimed) as u8);
+    }
+    while words >= MAX_NIBBLE {
+      self = self.push(MAX_NIBBLE as u8);
+      words -= MAX_NIBBLE;
+    }
+    if words > 0 {
+      self = self.push(words as u8);
+    }
+    return self;
+  }
+
+  // Adds an ivar to the layout, given the end of the previous scan (in words). Returns the new end.
+  const fn ivar(self, base: usize, scan_end: usize, offset: usize, size: usize) -> (Self, usize) {
+    let words = size / WORD_SIZE;
+    if offset < base || (offset - base) % WORD_SIZE != 0 || words == 0 {
+      return (self, scan_end);
+    }
+    let mut begin = (offset - base) / WORD_SIZE;
+    let end = begin + words;
+    let layout;
+    if begin > scan_end {
+      layout = self.skip(begin - scan_end);
+    } else {
+      // Overlapping ivars (e.g., in a union) only scan what hasn't already been scanned.
+      begin = scan_end;
+      if begin >= end {
+        return (self, scan_end);
+      }
+      layout = self;
+    }
+    return (layout.scan(end - begin), end);
+  }
+}
+
+pub const fn ivar_layout<const N: usize>(
+  instance_start: usize,
+  ivars: &[IvarLayoutEntry],
+) -> IvarLayout<N> {
+  let base = (instance_start + WORD_SIZE - 1) / WORD_SIZE * WORD_SIZE;
+  let mut layout = IvarLayout {
+    bytes: [0; N],
+    len: 0,
+    last: 0,
+  };
+  let mut scan_end = 0;
+
+  // Rust may reorder fields, so the ivars are visited in order of their offsets (and then their
+  // indices). `prev` is the index of the previously visited ivar.
+  let mut prev = ivars.len();
+  let mut visited = 0;
+  while visited < ivars.len() {
+    let mut next = ivars.len();
+    let mut i = 0;
+    while i < ivars.len() {
+      let after_prev = prev == ivars.len()
+        || ivars[i].0 > ivars[prev].0
+        || (ivars[i].0 == ivars[prev].0 && i > prev);
+      if after_prev && (next == ivars.len() || ivars[i].0 < ivars[next].0) {
+        next = i;
+      }
+      i += 1;
+    }
+
+    let (offset, size, included) = ivars[next];
+    if included {
+      let (new_layout, new_scan_end) = layout.ivar(base, scan_end, offset, size);
+      layout = new_layout;
+      scan_end = new_scan_end;
+    }
+    prev = next;
+    visited += 1;
+  }
+  return layout;
+}
+
+// Types that can back a synthesized weak property. The location must not move for as long as the
+// value is alive, since the Objective-C runtime tracks weak pointers by their address.
+pub unsafe trait WeakLocation: marker::Weak {
+  fn location(&self) -> *mut *mut crate::runtime::Id;
+}
+
 pub trait TypeEncodingHack {
   type Type: Copy + Send + Sync;
   const BYTES: Self::Type;
@@ -487,7 +805,143 @@ impl<T: ?Sized + TypeEncoding> TypeEncodingHack for T {
   const BYTES: Self::Type = <T as TypeEncoding>::BYTES;
 }
 
+// The extended encoding of a pointer to Self. Extended type encodings are only used for protocol
+// methods (see ExtendedTypeEncodingHack). The #[objrs(class)] macro implements this for classes
+// (`@"ClassName"`), and blocks implement it too (`@?<signature>`).
+pub unsafe trait ExtendedPointeeTypeEncoding: PointeeTypeEncoding {
+  type ExtendedType: Copy + Send + Sync;
+  const EXTENDED_BYTES: Self::ExtendedType;
+}
+
+// Like TypeEncodingHack, but for the extended type encodings that clang emits for protocol methods.
+// They're the same as the regular type encodings, except object pointers include the object's class
+// name (e.g. `@"NSString"`) and block pointers include the block's signature (e.g. `@?<v@?i>`).
+pub trait ExtendedTypeEncodingHack {
+  type Type: Copy + Send + Sync;
+  const BYTES: Self::Type;
+}
+
+impl<T: ?Sized> ExtendedTypeEncodingHack for T {
+  default type Type = <T as TypeEncodingHack>::Type;
+  default const BYTES: Self::Type = unsafe {
+    TransmuteHack {
+      from: <T as TypeEncodingHack>::BYTES,
+    }
+    .to
+  };
+}
+
+macro_rules! extended_pointer_type_encoding {
+  () => {};
+  ([$($generics:tt)*] $ty:ty, $($tt:tt)*) => {
+    impl<$($generics)*> ExtendedTypeEncodingHack for $ty {
+      type Type = <T as ExtendedPointeeTypeEncoding>::ExtendedType;
+      const BYTES: Self::Type = <T as ExtendedPointeeTypeEncoding>::EXTENDED_BYTES;
+    }
+
+    extended_pointer_type_encoding!{$($tt)*}
+  };
+}
+
+extended_pointer_type_encoding! {
+  [T: ExtendedPointeeTypeEncoding + ?Sized] *const T,
+  [T: ExtendedPointeeTypeEncoding + ?Sized] *mut T,
+  ['a, T: ExtendedPointeeTypeEncoding + ?Sized] &'a T,
+  ['a, T: ExtendedPointeeTypeEncoding + ?Sized] &'a mut T,
+  ['a, T: ExtendedPointeeTypeEncoding + ?Sized] Option<&'a T>,
+  ['a, T: ExtendedPointeeTypeEncoding + ?Sized] Option<&'a mut T>,
+  [T: ExtendedPointeeTypeEncoding + ?Sized] core::ptr::NonNull<T>,
+  [T: ExtendedPointeeTypeEncoding + ?Sized] Option<core::ptr::NonNull<T>>,
+  [T: ExtendedPointeeTypeEncoding + marker::Class + ?Sized] arc::Strong<T>,
+  [T: ExtendedPointeeTypeEncoding + marker::Class + ?Sized] arc::WeakCell<T>,
+  ['pool, T: ExtendedPointeeTypeEncoding + marker::Class + ?Sized] arc::Auto<'pool, T>,
+}
+
+#[cfg(feature = "alloc")]
+extended_pointer_type_encoding! {
+  [T: ExtendedPointeeTypeEncoding + marker::Class + ?Sized] arc::Weak<T>,
+}
+
 #[cfg(test)]
 pub mod test {
   pub use super::super::test::*;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn layout(instance_start: usize, ivars: &[IvarLayoutEntry]) -> ([u8; 8], usize) {
+    let layout = ivar_layout::<8>(instance_start, ivars);
+    assert_eq!(layout.len, ivar_layout::<0>(instance_start, ivars).len);
+    return (layout.bytes, layout.len);
+  }
+
+  fn bytes(instance_start: usize, ivars: &[IvarLayoutEntry]) -> [u8; 8] {
+    let (bytes, len) = layout(instance_start, ivars);
+    assert!(bytes[len..].iter().all(|&byte| byte == 0));
+    return bytes;
+  }
+
+  #[test]
+  fn ivar_layouts() {
+    const W: usize = WORD_SIZE;
+
+    // No included ivars (which is a nil layout).
+    assert_eq!(layout(8, &[]).1, 0);
+    assert_eq!(layout(8, &[(8, W, false), (8 + W, W, false)]).1, 0);
+
+    // A single ivar at the instance start.
+    assert_eq!(&bytes(8, &[(8, W, true)]), b"\x01\0\0\0\0\0\0\0");
+
+    // @interface Foo : NSObject { id a; __weak id w1; id b; __weak id w2; int c; }
+    let weak = [(8, W, false), (8 + W, W, true), (8 + 2 * W, W, false), (8 + 3 * W, W, true)];
+    assert_eq!(&bytes(8, &weak), b"\x11\x11\0\0\0\0\0\0");
+    let strong = [(8, W, true), (8 + W, W, false), (8 + 2 * W, W, true), (8 + 3 * W, W, false)];
+    assert_eq!(&bytes(8, &strong), b"\x01\x11\0\0\0\0\0\0");
+
+    // Trailing skips are omitted.
+    assert_eq!(&bytes(8, &[(8, W, true), (8 + W, 4 * W, false)]), b"\x01\0\0\0\0\0\0\0");
+
+    // Fields may be reordered (and adjacent scans are merged).
+    assert_eq!(&bytes(8, &[(8 + W, W, true), (8, W, true)]), b"\x02\0\0\0\0\0\0\0");
+
+    // Long runs are split into multiple bytes.
+    let mut ivars = [(0, 0, false); 20];
+    for i in 0..20 {
+      ivars[i] = (8 + i * W, W, i >= 17);
+    }
+    assert_eq!(&bytes(8, &ivars), b"\xf0\x23\0\0\0\0\0\0");
+    for i in 0..20 {
+      ivars[i] = (8 + i * W, W, i < 17);
+    }
+    assert_eq!(&bytes(8, &ivars), b"\x0f\x02\0\0\0\0\0\0");
+
+    // The instance start is rounded up to a whole word, and misaligned ivars are omitted.
+    assert_eq!(&bytes(W + 1, &[(W + 1, 1, false), (2 * W, W, true)]), b"\x01\0\0\0\0\0\0\0");
+    assert_eq!(&bytes(8, &[(9, W, true), (8 + 2 * W, W, true)]), b"\x21\0\0\0\0\0\0\0");
+
+    // Overlapping ivars (in a union) are only scanned once.
+    assert_eq!(&bytes(8, &[(8, W, true), (8, 2 * W, true)]), b"\x02\0\0\0\0\0\0\0");
+
+    // The length doesn't depend on the capacity.
+    assert_eq!(layout(8, &ivars).1, 2);
+    assert_eq!(ivar_layout::<1>(8, &ivars).bytes, [0x0f]);
+  }
+
+  #[test]
+  fn promoted_arg_sizes() {
+    // Small integers (and bools) are promoted to an int.
+    assert_eq!(promoted_arg_size(1, b'C'), 4);
+    assert_eq!(promoted_arg_size(1, b'c'), 4);
+    assert_eq!(promoted_arg_size(2, b's'), 4);
+    assert_eq!(promoted_arg_size(1, b'B'), 4);
+    assert_eq!(promoted_arg_size(8, b'q'), 8);
+
+    // Small aggregates aren't.
+    assert_eq!(promoted_arg_size(2, b'['), 2);
+    assert_eq!(promoted_arg_size(3, b'{'), 3);
+    assert_eq!(promoted_arg_size(1, b'('), 1);
+    assert_eq!(promoted_arg_size(0, b'{'), 0);
+  }
+}
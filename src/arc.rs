@@ -5,7 +5,10 @@
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
+extern crate libc;
 extern crate objrs_macros;
+#[cfg(feature = "std")]
+extern crate std;
 
 use crate::__objrs::TransmuteHack;
 use crate::marker;
@@ -57,14 +60,32 @@ impl<T: marker::Class + ?Sized> Strong<T> {
     }
   }
 
+  /// Autoreleases the object into `pool`, which owns the autoreleased object.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `pool` isn't the innermost autorelease pool (see `AutoreleasePool::autorelease`).
   #[inline(always)]
-  pub fn autorelease(self) -> Auto<T> {
+  pub fn autorelease<'pool>(self, pool: &'pool AutoreleasePool) -> Auto<'pool, T> {
+    pool.assert_innermost();
     unsafe {
-      let objc_autorelease: unsafe extern "C" fn(Strong<T>) -> Auto<T> =
+      let objc_autorelease: unsafe extern "C" fn(Strong<T>) -> Auto<'pool, T> =
         core::mem::transmute(runtime::objc_autorelease as *const ());
       return objc_autorelease(self);
     }
   }
+
+  /// Casts the object to a `U` if it's an instance of `U` or one of its subclasses (see
+  /// `Id::downcast`). Returns the original object otherwise.
+  #[inline]
+  pub fn downcast<U: marker::ClassRef>(self) -> Result<Strong<U>, Strong<T>> {
+    let ptr = self.0.as_ptr();
+    if !unsafe { &*(ptr as *const runtime::Id) }.is_kind_of::<U>() {
+      return Err(self);
+    }
+    core::mem::forget(self);
+    return Ok(Strong(unsafe { core::ptr::NonNull::new_unchecked(ptr as *mut U) }));
+  }
 }
 
 impl<T: marker::NonRootClass + marker::Class + ?Sized> Strong<T> {
@@ -154,9 +175,9 @@ impl<T: marker::Class + ?Sized> core::convert::AsMut<runtime::Id> for Strong<T>
   }
 }
 
-impl<T: marker::Class + ?Sized> core::convert::From<Auto<T>> for Strong<T> {
+impl<'pool, T: marker::Class + ?Sized> core::convert::From<Auto<'pool, T>> for Strong<T> {
   #[inline(always)]
-  fn from(this: Auto<T>) -> Strong<T> {
+  fn from(this: Auto<'pool, T>) -> Strong<T> {
     return this.retain();
   }
 }
@@ -169,41 +190,69 @@ impl<T: marker::Class + ?Sized> core::convert::TryFrom<Weak<T>> for Strong<T> {
   }
 }
 
+/// An autoreleased (+0) object. The `'pool` lifetime bounds the autorelease pool that owns the
+/// object, so an `Auto` can't be used after the pool has been popped. Extern methods that return
+/// objects their caller doesn't own (i.e., methods that aren't in the `alloc`, `copy`,
+/// `mutableCopy`, `new`, or `init` families) may return an `Auto<'pool, T>` if they take the pool as
+/// a `&'pool AutoreleasePool` parameter (which must be the innermost pool).
 #[repr(transparent)]
-pub struct Auto<T: marker::Class + ?Sized>(core::ptr::NonNull<T>);
-
-impl<T: marker::Class + ?Sized> Auto<T> {
+pub struct Auto<'pool, T: marker::Class + ?Sized>(
+  core::ptr::NonNull<T>,
+  core::marker::PhantomData<&'pool AutoreleasePool>,
+);
+
+impl<'pool, T: marker::Class + ?Sized> Auto<'pool, T> {
+  /// Retains the object.
+  ///
+  /// This uses `objc_retain`, not `objc_retainAutoreleasedReturnValue`: the object was already
+  /// autoreleased into a pool, so there's no pending return value handshake for the latter to
+  /// claim, and it would only retain the object anyway (message sends that return `Strong` claim
+  /// the result themselves).
   #[inline(always)]
   pub fn retain(self) -> Strong<T> {
     unsafe {
-      let objc_retain: unsafe extern "C" fn(Auto<T>) -> Strong<T> =
+      let objc_retain: unsafe extern "C" fn(Auto<'pool, T>) -> Strong<T> =
         core::mem::transmute(runtime::objc_retain as *const ());
       return objc_retain(self);
     }
   }
+
+  /// Casts the object to a `U` if it's an instance of `U` or one of its subclasses (see
+  /// `Id::downcast`). Returns the original object otherwise.
+  #[inline]
+  pub fn downcast<U: marker::ClassRef>(self) -> Result<Auto<'pool, U>, Auto<'pool, T>> {
+    let ptr = self.0.as_ptr();
+    if !unsafe { &*(ptr as *const runtime::Id) }.is_kind_of::<U>() {
+      return Err(self);
+    }
+    return Ok(Auto(
+      unsafe { core::ptr::NonNull::new_unchecked(ptr as *mut U) },
+      core::marker::PhantomData,
+    ));
+  }
 }
 
-// unsafe impl<T: marker::Class + ?Sized> marker::Class for Auto<T> {}
-// unsafe impl<T: marker::RootClass + ?Sized> marker::RootClass for Auto<T> {}
-// unsafe impl<T: marker::NonRootClass + ?Sized> marker::NonRootClass for Auto<T> {
+// unsafe impl<'pool, T: marker::Class + ?Sized> marker::Class for Auto<'pool, T> {}
+// unsafe impl<'pool, T: marker::RootClass + ?Sized> marker::RootClass for Auto<'pool, T> {}
+// unsafe impl<'pool, T: marker::NonRootClass + ?Sized> marker::NonRootClass for Auto<'pool, T> {
 //   type Super = <T as marker::NonRootClass>::Super;
 // }
 
-impl<T: marker::Class + ?Sized> core::fmt::Pointer for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::fmt::Pointer for Auto<'pool, T> {
   #[inline(always)]
   fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
     return core::fmt::Pointer::fmt(&self.0, formatter);
   }
 }
 
-impl<T: marker::Class + ?Sized> core::fmt::Debug for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::fmt::Debug for Auto<'pool, T> {
   #[inline(always)]
   fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
     return core::fmt::Pointer::fmt(&self, formatter);
   }
 }
 
-impl<T: marker::Class + ?Sized> core::ops::Deref for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::ops::Deref for Auto<'pool, T> {
   type Target = T;
 
   #[inline(always)]
@@ -212,53 +261,41 @@ impl<T: marker::Class + ?Sized> core::ops::Deref for Auto<T> {
   }
 }
 
-impl<T: marker::Class + ?Sized> core::ops::DerefMut for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::ops::DerefMut for Auto<'pool, T> {
   #[inline(always)]
   fn deref_mut(&mut self) -> &mut T {
     return unsafe { self.0.as_mut() };
   }
 }
 
-impl<T: marker::Class + ?Sized> core::convert::AsRef<runtime::Id> for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::convert::AsRef<runtime::Id> for Auto<'pool, T> {
   #[inline(always)]
   fn as_ref(&self) -> &runtime::Id {
     return unsafe { core::mem::transmute(self) };
   }
 }
 
-impl<T: marker::Class + ?Sized> core::convert::AsMut<runtime::Id> for Auto<T> {
+impl<'pool, T: marker::Class + ?Sized> core::convert::AsMut<runtime::Id> for Auto<'pool, T> {
   #[inline(always)]
   fn as_mut(&mut self) -> &mut runtime::Id {
     return unsafe { core::mem::transmute(self) };
   }
 }
 
-impl<T: marker::Class + ?Sized> core::convert::From<Strong<T>> for Auto<T> {
+impl<'pool, T: marker::NonRootClass + marker::Class + ?Sized> Auto<'pool, T> {
   #[inline(always)]
-  fn from(this: Strong<T>) -> Auto<T> {
-    return this.autorelease();
-  }
-}
-
-#[cfg(feature = "alloc")]
-impl<T: marker::Class + ?Sized> core::convert::TryFrom<Weak<T>> for Auto<T> {
-  type Error = ();
-  fn try_from(weak: Weak<T>) -> Result<Auto<T>, ()> {
-    return weak.autorelease().ok_or(());
-  }
-}
-
-impl<T: marker::NonRootClass + marker::Class + ?Sized> Auto<T> {
-  #[inline(always)]
-  pub fn into_super(self) -> Auto<<T as marker::NonRootClass>::Super> {
+  pub fn into_super(self) -> Auto<'pool, <T as marker::NonRootClass>::Super> {
     let ptr = self.0.as_ptr();
     core::mem::forget(self);
-    return Auto(unsafe {
-      TransmuteHack {
-        from: ptr,
-      }
-      .to
-    });
+    return Auto(
+      unsafe {
+        TransmuteHack {
+          from: ptr,
+        }
+        .to
+      },
+      core::marker::PhantomData,
+    );
   }
 }
 
@@ -271,6 +308,41 @@ pub struct Weak<T: marker::Class + ?Sized>(alloc::boxed::Box<*mut T>);
 
 #[cfg(feature = "alloc")]
 impl<T: marker::Class + ?Sized> Weak<T> {
+  /// Creates a nil weak pointer.
+  #[inline(always)]
+  pub fn new() -> Weak<T> {
+    // TODO: replace this transmute with a nil value.
+    return Weak(alloc::boxed::Box::new(unsafe {
+      TransmuteHack {
+        from: 0usize,
+      }
+      .to
+    }));
+  }
+
+  /// Loads and retains the weakly referenced object without consuming the weak pointer. Returns
+  /// `None` if it's nil or deallocating.
+  #[inline(always)]
+  pub fn upgrade(&self) -> Option<Strong<T>> {
+    let ptr_to_weak = core::ops::Deref::deref(&self.0);
+    unsafe {
+      let load_weak_retained: unsafe extern "C" fn(&*mut T) -> Option<Strong<T>> =
+        core::mem::transmute(runtime::objc_loadWeakRetained as *const ());
+      return load_weak_retained(ptr_to_weak);
+    }
+  }
+
+  /// Replaces the weakly referenced object.
+  #[inline(always)]
+  pub fn set(&mut self, value: Option<&Strong<T>>) {
+    let ptr_to_weak = core::ops::DerefMut::deref_mut(&mut self.0);
+    unsafe {
+      let store_weak: unsafe extern "C" fn(&mut *mut T, Option<&T>) -> *mut T =
+        core::mem::transmute(runtime::objc_storeWeak as *const ());
+      store_weak(ptr_to_weak, value.map(|strong| &**strong));
+    }
+  }
+
   pub fn retain(mut self) -> Option<Strong<T>> {
     let ptr_to_weak = core::ops::DerefMut::deref_mut(&mut self.0);
     unsafe {
@@ -280,10 +352,16 @@ impl<T: marker::Class + ?Sized> Weak<T> {
     }
   }
 
-  pub fn autorelease(mut self) -> Option<Auto<T>> {
+  /// Loads and autoreleases the weakly referenced object into `pool`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `pool` isn't the innermost autorelease pool (see `AutoreleasePool::autorelease`).
+  pub fn autorelease<'pool>(mut self, pool: &'pool AutoreleasePool) -> Option<Auto<'pool, T>> {
+    pool.assert_innermost();
     let ptr_to_weak = core::ops::DerefMut::deref_mut(&mut self.0);
     unsafe {
-      let load_weak: unsafe extern "C" fn(&mut *mut T) -> Option<Auto<T>> =
+      let load_weak: unsafe extern "C" fn(&mut *mut T) -> Option<Auto<'pool, T>> =
         core::mem::transmute(runtime::objc_loadWeak as *const ());
       return load_weak(ptr_to_weak);
     }
@@ -298,6 +376,14 @@ impl<T: marker::Class + ?Sized> Weak<T> {
 #[cfg(feature = "alloc")]
 unsafe impl<T: marker::Class + ?Sized> marker::Weak for Weak<T> {}
 
+#[cfg(feature = "alloc")]
+unsafe impl<T: marker::Class + ?Sized> crate::__objrs::WeakLocation for Weak<T> {
+  #[inline(always)]
+  fn location(&self) -> *mut *mut runtime::Id {
+    return core::ops::Deref::deref(&self.0) as *const *mut T as *mut *mut runtime::Id;
+  }
+}
+
 #[cfg(feature = "alloc")]
 impl<T: marker::Class + ?Sized> core::ops::Drop for Weak<T> {
   #[inline(always)]
@@ -311,17 +397,46 @@ impl<T: marker::Class + ?Sized> core::ops::Drop for Weak<T> {
   }
 }
 
+#[cfg(feature = "alloc")]
+impl<T: marker::Class + ?Sized> core::clone::Clone for Weak<T> {
+  #[inline(always)]
+  fn clone(&self) -> Self {
+    let mut weak = Weak::new();
+    let ptr_to_weak = core::ops::DerefMut::deref_mut(&mut weak.0);
+    unsafe {
+      let copy_weak: unsafe extern "C" fn(&mut *mut T, &*mut T) =
+        core::mem::transmute(runtime::objc_copyWeak as *const ());
+      copy_weak(ptr_to_weak, core::ops::Deref::deref(&self.0));
+    }
+    return weak;
+  }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: marker::Class + ?Sized> core::default::Default for Weak<T> {
+  #[inline(always)]
+  fn default() -> Weak<T> {
+    return Weak::new();
+  }
+}
+
+// Weak pointers are equal if they currently refer to the same object (or are both nil).
+#[cfg(feature = "alloc")]
+impl<T: marker::Class + ?Sized> core::cmp::PartialEq for Weak<T> {
+  fn eq(&self, other: &Weak<T>) -> bool {
+    return match (self.upgrade(), other.upgrade()) {
+      (Some(lhs), Some(rhs)) => core::ptr::eq(&*lhs, &*rhs),
+      (None, None) => true,
+      _ => false,
+    };
+  }
+}
+
 #[cfg(feature = "alloc")]
 impl<T: marker::Class + ?Sized> core::convert::From<Strong<T>> for Weak<T> {
   #[inline(always)]
   fn from(this: Strong<T>) -> Weak<T> {
-    // TODO: replace this transmute with a nil value.
-    let mut weak = Weak(alloc::boxed::Box::new(unsafe {
-      TransmuteHack {
-        from: 0usize,
-      }
-      .to
-    }));
+    let mut weak = Weak::new();
     let ptr_to_weak = core::ops::DerefMut::deref_mut(&mut weak.0);
     unsafe {
       let init_weak: unsafe extern "C" fn(&mut *mut T, *mut T) -> *mut T =
@@ -354,11 +469,230 @@ impl<T: marker::Class + ?Sized> core::fmt::Debug for Weak<T> {
   }
 }
 
-// TODO: What happens to autoreleasepools when an exception is thrown (before the pool is popped)? How does Objective-C handle it, and does objrs match it?
-// We only take Fn instead of FnOnce or FnMut in order to avoid allowing the user to accidentally create dangling references to autoreleased values.
+/// `WeakCell` is an Objective-C `__weak` pointer that's stored in place instead of being boxed, so it
+/// has the same ABI as an Objective-C weak pointer and can be used as an instance variable (with or
+/// without the `alloc` feature). The Objective-C runtime tracks weak pointers by their address, so a
+/// `WeakCell` must be pinned before it can be given a value. Instance variables are never moved, so
+/// it's safe to pin them with `Pin::new_unchecked`.
+#[repr(transparent)]
+pub struct WeakCell<T: marker::Class + ?Sized> {
+  ptr: core::cell::UnsafeCell<Option<core::ptr::NonNull<T>>>,
+  _pinned: core::marker::PhantomPinned,
+}
+
+impl<T: marker::Class + ?Sized> WeakCell<T> {
+  /// Creates a nil weak pointer. A nil `WeakCell` isn't registered with the runtime, so it may be
+  /// freely moved until it's stored to.
+  #[inline(always)]
+  pub const fn new() -> WeakCell<T> {
+    return WeakCell {
+      ptr: core::cell::UnsafeCell::new(None),
+      _pinned: core::marker::PhantomPinned,
+    };
+  }
+
+  /// Initializes uninitialized memory with a weak pointer to `value` (via `objc_initWeak`).
+  ///
+  /// # Safety
+  ///
+  /// `location` must be valid for writes, and the `WeakCell` must not be moved until it is dropped.
+  #[inline(always)]
+  pub unsafe fn init(location: *mut WeakCell<T>, value: Option<&T>) {
+    let init_weak: unsafe extern "C" fn(*mut WeakCell<T>, Option<&T>) -> *mut T =
+      core::mem::transmute(runtime::objc_initWeak as *const ());
+    init_weak(location, value);
+  }
+
+  /// Initializes uninitialized memory with a copy of the weak pointer `from` (via `objc_copyWeak`).
+  ///
+  /// # Safety
+  ///
+  /// `location` must be valid for writes, and the `WeakCell` must not be moved until it is dropped.
+  #[inline(always)]
+  pub unsafe fn init_copy(location: *mut WeakCell<T>, from: core::pin::Pin<&WeakCell<T>>) {
+    let copy_weak: unsafe extern "C" fn(*mut WeakCell<T>, *mut WeakCell<T>) =
+      core::mem::transmute(runtime::objc_copyWeak as *const ());
+    copy_weak(location, from.slot() as *mut WeakCell<T>);
+  }
+
+  /// Initializes uninitialized memory by moving the weak pointer out of `from` (via `objc_moveWeak`).
+  /// `from` is left nil.
+  ///
+  /// # Safety
+  ///
+  /// `location` must be valid for writes, and the `WeakCell` must not be moved until it is dropped.
+  #[inline(always)]
+  pub unsafe fn init_move(location: *mut WeakCell<T>, from: core::pin::Pin<&mut WeakCell<T>>) {
+    let move_weak: unsafe extern "C" fn(*mut WeakCell<T>, *mut WeakCell<T>) =
+      core::mem::transmute(runtime::objc_moveWeak as *const ());
+    move_weak(location, from.slot() as *mut WeakCell<T>);
+  }
+
+  #[inline(always)]
+  fn slot(&self) -> *mut *mut T {
+    return self.ptr.get() as *mut *mut T;
+  }
+
+  /// Loads and retains the weakly referenced object. Returns `None` if it's nil or deallocating.
+  // Loading doesn't require pinning: a nil pointer can be loaded from any address, and a non-nil
+  // pointer can only have been stored through a pin.
+  #[inline(always)]
+  pub fn load(&self) -> Option<Strong<T>> {
+    unsafe {
+      let load_weak_retained: unsafe extern "C" fn(*mut *mut T) -> Option<Strong<T>> =
+        core::mem::transmute(runtime::objc_loadWeakRetained as *const ());
+      return load_weak_retained(self.slot());
+    }
+  }
+
+  /// Stores a weak pointer to `value` (via `objc_storeWeak`).
+  #[inline(always)]
+  pub fn store(self: core::pin::Pin<&Self>, value: Option<&T>) {
+    unsafe {
+      let store_weak: unsafe extern "C" fn(*mut *mut T, Option<&T>) -> *mut T =
+        core::mem::transmute(runtime::objc_storeWeak as *const ());
+      store_weak(self.slot(), value);
+    }
+  }
+}
+
+unsafe impl<T: marker::Class + ?Sized> marker::Weak for WeakCell<T> {}
+
+// A zeroed WeakCell is nil (just like a zeroed Objective-C __weak ivar).
+unsafe impl<T: marker::Class + ?Sized> marker::Zeroed for WeakCell<T> {}
+
+unsafe impl<T: marker::Class + ?Sized> crate::__objrs::WeakLocation for WeakCell<T> {
+  #[inline(always)]
+  fn location(&self) -> *mut *mut runtime::Id {
+    return self.slot() as *mut *mut runtime::Id;
+  }
+}
+
+impl<T: marker::Class + ?Sized> core::default::Default for WeakCell<T> {
+  #[inline(always)]
+  fn default() -> WeakCell<T> {
+    return WeakCell::new();
+  }
+}
+
+impl<T: marker::Class + ?Sized> core::ops::Drop for WeakCell<T> {
+  #[inline(always)]
+  fn drop(&mut self) {
+    unsafe {
+      let destroy_weak: unsafe extern "C" fn(*mut *mut T) =
+        core::mem::transmute(runtime::objc_destroyWeak as *const ());
+      destroy_weak(self.slot());
+    }
+  }
+}
+
+impl<T: marker::Class + ?Sized> core::fmt::Pointer for WeakCell<T> {
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let ptr;
+    unsafe {
+      let load_weak: unsafe extern "C" fn(*mut *mut T) -> *const T =
+        core::mem::transmute(runtime::objc_loadWeak as *const ());
+      ptr = load_weak(self.slot());
+    }
+    return core::fmt::Pointer::fmt(&ptr, formatter);
+  }
+}
+
+impl<T: marker::Class + ?Sized> core::fmt::Debug for WeakCell<T> {
+  #[inline(always)]
+  fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+    return core::fmt::Pointer::fmt(&self, formatter);
+  }
+}
+
+// The number of pools that `autoreleasepool` has pushed (and not yet popped) on this thread. Pools
+// are popped in the reverse order they're pushed, so a pool is the innermost pool if its depth is
+// the current depth.
+//
+// Pools pushed by Objective-C code (e.g. by NSRunLoop, by `enumerateObjectsUsingBlock:`, or by an
+// `@autoreleasepool` in a method that calls back into Rust) aren't counted. Safe code can't reach an
+// outer `AutoreleasePool` from inside one of those pools, since Objective-C only calls back into
+// Rust through method implementations and blocks, which can't borrow it (blocks' closures must be
+// 'static).
+#[cfg(feature = "std")]
+std::thread_local! {
+  static POOL_DEPTH: core::cell::Cell<usize> = core::cell::Cell::new(0);
+}
+#[cfg(not(feature = "std"))]
+#[thread_local]
+static POOL_DEPTH: core::cell::Cell<usize> = core::cell::Cell::new(0);
+
+#[inline(always)]
+fn pool_depth<R, F: FnOnce(&core::cell::Cell<usize>) -> R>(f: F) -> R {
+  #[cfg(feature = "std")]
+  return POOL_DEPTH.with(f);
+  #[cfg(not(feature = "std"))]
+  return f(&POOL_DEPTH);
+}
+
+/// An autorelease pool pushed by `autoreleasepool`. The pool is popped when it's dropped (including
+/// when a Rust panic or an Objective-C exception unwinds out of `autoreleasepool`).
+pub struct AutoreleasePool {
+  // Pools are per-thread, so the raw pointer conveniently makes AutoreleasePool !Send and !Sync.
+  pool: *mut libc::c_void,
+  depth: usize,
+}
+
+impl AutoreleasePool {
+  /// Autoreleases `value` into this pool.
+  ///
+  /// # Panics
+  ///
+  /// Panics if this isn't the innermost pool (i.e. if it's used inside a nested `autoreleasepool`).
+  /// The runtime always autoreleases into the innermost pool, so the object would be released when
+  /// the nested pool is popped, even though `'pool` says it lives as long as this pool.
+  ///
+  /// Only pools pushed by `autoreleasepool` are checked. Unsafe code that smuggles a pool into a
+  /// callback from Objective-C (e.g. through a context pointer) must not autorelease into it if
+  /// Objective-C may have pushed its own pool in between.
+  #[inline(always)]
+  pub fn autorelease<'pool, T: marker::Class + ?Sized>(
+    &'pool self,
+    value: Strong<T>,
+  ) -> Auto<'pool, T> {
+    return value.autorelease(self);
+  }
+
+  #[inline(always)]
+  pub(crate) fn assert_innermost(&self) {
+    if self.depth != pool_depth(|depth| depth.get()) {
+      not_innermost_pool();
+    }
+  }
+}
+
+#[cold]
+#[inline(never)]
+fn not_innermost_pool() -> ! {
+  panic!("objects can only be autoreleased into the innermost autorelease pool");
+}
+
+impl core::ops::Drop for AutoreleasePool {
+  #[inline(always)]
+  fn drop(&mut self) {
+    unsafe { runtime::objc_autoreleasePoolPop(self.pool) };
+    pool_depth(|depth| depth.set(depth.get() - 1));
+  }
+}
+
+// The closure must work for any pool lifetime, so nothing borrowed from the pool can escape through
+// the return value.
 #[inline(always)]
-pub fn autoreleasepool<F: Fn()>(f: F) {
-  let pool = unsafe { runtime::objc_autoreleasePoolPush() };
-  f();
-  unsafe { runtime::objc_autoreleasePoolPop(pool) };
+pub fn autoreleasepool<F: FnOnce(&AutoreleasePool) -> R, R>(f: F) -> R {
+  // Compiling the Objective-C program `int main() {@autoreleasepool{} return 0;}` shows that clang
+  // doesn't check the return value of the pool push, so neither do we.
+  let depth = pool_depth(|depth| {
+    depth.set(depth.get() + 1);
+    return depth.get();
+  });
+  let pool = AutoreleasePool {
+    pool: unsafe { runtime::objc_autoreleasePoolPush() },
+    depth: depth,
+  };
+  return f(&pool);
 }
@@ -0,0 +1,436 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Objective-C blocks (i.e. `^{}` closures).
+//
+// A block is an object whose header holds an `invoke` function pointer (which takes the block as
+// its first argument, followed by the block's arguments) and a pointer to a descriptor (which holds
+// the block's size, its copy and dispose helpers, and its type signature). Captured state follows
+// the header. Blocks start out on the stack and are moved to the heap by `_Block_copy`, which
+// copies the block's bytes and then calls its copy helper to copy the captured state.
+//
+// Blocks created from Rust closures store the closure after the header. The copy helper clones the
+// closure and the dispose helper drops it, so the closure must be `Clone`. Any method that's given
+// a block may copy it and keep the copy indefinitely, so the closure must also be `'static`.
+//
+// Global blocks (`_NSConcreteGlobalBlock`, which clang emits for block literals that don't capture
+// anything) are out of scope: every block created in Rust starts out as a stack block, even if its
+// closure doesn't capture anything. `runtime::_NSConcreteGlobalBlock` is only declared for
+// completeness.
+//
+// See https://clang.llvm.org/docs/Block-ABI-Apple.html
+
+extern crate core;
+extern crate libc;
+
+use crate::__objrs::{
+  ExtendedPointeeTypeEncoding, ExtendedTypeEncodingHack, Packed2, Packed3, TransmuteHack,
+};
+use crate::runtime;
+use crate::{FieldsTypeEncoding, PointeeTypeEncoding, TypeEncoding};
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+// The block has copy and dispose helpers in its descriptor.
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+// The block has a type signature in its descriptor.
+const BLOCK_HAS_SIGNATURE: i32 = 1 << 30;
+
+// The maximum length of a block's type signature (including the NUL terminator). Blocks whose
+// signatures are longer than this don't have a signature.
+const MAX_SIGNATURE_LEN: usize = 256;
+
+// Most of the header and descriptor fields are only read by the blocks runtime.
+#[allow(dead_code)]
+#[repr(C)]
+struct BlockDescriptor {
+  reserved: usize,
+  size: usize,
+  copy: unsafe extern "C" fn(dst: *mut libc::c_void, src: *const libc::c_void),
+  dispose: unsafe extern "C" fn(block: *mut libc::c_void),
+  signature: *const libc::c_char,
+}
+
+/// A block that takes the arguments in the tuple `A` and returns `R`. Blocks are always used by
+/// reference: blocks received from Objective-C are `&Block<A, R>`, and blocks created in Rust are
+/// `StackBlock`s or `RcBlock`s (which deref to `Block`). `&Block<A, R>` is encoded as `@?`.
+#[allow(dead_code)]
+#[repr(C)]
+pub struct Block<A, R> {
+  isa: *const [usize; 32],
+  flags: i32,
+  reserved: i32,
+  invoke: unsafe extern "C" fn(),
+  descriptor: *const BlockDescriptor,
+  marker: PhantomData<fn(A) -> R>,
+}
+
+unsafe impl<A, R> PointeeTypeEncoding for Block<A, R> {
+  type Type = [u8; 2];
+  const BYTES: Self::Type = *b"@?";
+
+  type ConstType = [u8; 2];
+  const CONST_BYTES: Self::ConstType = *b"@?";
+}
+
+// The extended encoding includes the block's signature (without frame offsets), like clang's
+// `@?<v@?@"NSString">`.
+unsafe impl<A: BlockArguments, R: TypeEncoding> ExtendedPointeeTypeEncoding for Block<A, R> {
+  type ExtendedType = Packed3<
+    Packed2<[u8; 3], <R as ExtendedTypeEncodingHack>::Type>,
+    Packed2<[u8; 2], <A as BlockArguments>::ExtendedType>,
+    u8,
+  >;
+  const EXTENDED_BYTES: Self::ExtendedType = Packed3(
+    Packed2(*b"@?<", <R as ExtendedTypeEncodingHack>::BYTES),
+    Packed2(*b"@?", <A as BlockArguments>::EXTENDED_BYTES),
+    b'>',
+  );
+}
+
+/// The arguments of a block. This is implemented for tuples (of up to 8 elements) whose elements
+/// implement `TypeEncoding`. Use `()` for blocks that don't take any arguments, and `(arg,)` for
+/// blocks that take one argument.
+pub unsafe trait BlockArguments: FieldsTypeEncoding + Sized {
+  // The concatenated extended type encodings of the arguments (see ExtendedTypeEncodingHack).
+  #[doc(hidden)]
+  type ExtendedType: Copy + Send + Sync;
+  #[doc(hidden)]
+  const EXTENDED_BYTES: Self::ExtendedType;
+
+  #[doc(hidden)]
+  unsafe fn call<R>(self, block: &Block<Self, R>) -> R;
+}
+
+/// A closure that can be used as a block's implementation. This is implemented for `Fn` closures
+/// whose arguments are the elements of the tuple `A`, and which return `R`.
+pub unsafe trait IntoBlock<A, R> {
+  // Returns the block's invoke function (whose first argument is a `StackBlock<A, R, Self>`).
+  #[doc(hidden)]
+  fn invoke() -> unsafe extern "C" fn();
+}
+
+macro_rules! extended_arguments_type {
+  () => { [u8; 0] };
+  ($first:ident $(, $rest:ident)*) => {
+    Packed2<<$first as ExtendedTypeEncodingHack>::Type, extended_arguments_type!($($rest),*)>
+  };
+}
+
+macro_rules! extended_arguments_bytes {
+  () => { [] };
+  ($first:ident $(, $rest:ident)*) => {
+    Packed2(<$first as ExtendedTypeEncodingHack>::BYTES, extended_arguments_bytes!($($rest),*))
+  };
+}
+
+macro_rules! block_arguments {
+  ($($arg:ident $name:ident $index:tt),*) => {
+    unsafe impl<$($arg: TypeEncoding),*> BlockArguments for ($($arg,)*) {
+      type ExtendedType = extended_arguments_type!($($arg),*);
+      const EXTENDED_BYTES: Self::ExtendedType = extended_arguments_bytes!($($arg),*);
+
+      #[inline(always)]
+      unsafe fn call<R>(self, block: &Block<Self, R>) -> R {
+        let invoke: unsafe extern "C" fn(&Block<Self, R>, $($arg),*) -> R =
+          core::mem::transmute(block.invoke);
+        return invoke(block, $(self.$index),*);
+      }
+    }
+
+    unsafe impl<$($arg,)* R, Closure> IntoBlock<($($arg,)*), R> for Closure
+    where
+      Closure: Fn($($arg),*) -> R,
+    {
+      #[inline(always)]
+      fn invoke() -> unsafe extern "C" fn() {
+        unsafe extern "C" fn invoke<$($arg,)* R, Closure>(
+          block: &StackBlock<($($arg,)*), R, Closure>,
+          $($name: $arg),*
+        ) -> R
+        where
+          Closure: Fn($($arg),*) -> R,
+        {
+          return (block.closure)($($name),*);
+        }
+
+        let invoke: unsafe extern "C" fn(&StackBlock<($($arg,)*), R, Closure>, $($arg),*) -> R =
+          invoke::<$($arg,)* R, Closure>;
+        return unsafe { core::mem::transmute(invoke) };
+      }
+    }
+  };
+}
+
+block_arguments!();
+block_arguments!(A a 0);
+block_arguments!(A a 0, B b 1);
+block_arguments!(A a 0, B b 1, C c 2);
+block_arguments!(A a 0, B b 1, C c 2, D d 3);
+block_arguments!(A a 0, B b 1, C c 2, D d 3, E e 4);
+block_arguments!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
+block_arguments!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
+block_arguments!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
+
+impl<A: BlockArguments, R> Block<A, R> {
+  /// Calls the block.
+  #[inline]
+  pub fn call(&self, args: A) -> R {
+    return unsafe { args.call(self) };
+  }
+
+  /// Copies the block to the heap (with `_Block_copy`). Copying a block that's already on the heap
+  /// just retains it.
+  ///
+  /// # Safety
+  ///
+  /// The copy may outlive the original block, so the block must not borrow anything that the copy
+  /// could outlive. Blocks created in Rust (i.e. `StackBlock`s) always satisfy this, since their
+  /// closures are `'static`.
+  #[inline]
+  pub unsafe fn copy(&self) -> RcBlock<A, R> {
+    let copy = runtime::_Block_copy(self as *const Block<A, R> as *const libc::c_void);
+    return RcBlock {
+      block: NonNull::new_unchecked(copy as *mut Block<A, R>),
+    };
+  }
+}
+
+// A block's type signature: the return type, the block itself (`@?`), and the arguments, followed by
+// a NUL terminator.
+type Signature<A, R> =
+  Packed3<Packed2<<R as TypeEncoding>::Type, [u8; 2]>, <A as FieldsTypeEncoding>::Type, u8>;
+
+// The signature's type depends on `A` and `R`, so a reference to it can't be a constant (the
+// compiler can't tell whether the type is `'static` or free of interior mutability). So it's
+// copied into a fixed-size array, using padding to make sure the source is at least as big.
+type PaddedSignature<A, R> = Packed2<Signature<A, R>, [u8; MAX_SIGNATURE_LEN]>;
+
+/// A block created from a Rust closure. It starts out on the stack (and can be passed by reference
+/// to methods that take a block, since it derefs to `Block`). Methods that keep the block will copy
+/// it to the heap, which clones the closure. Since any method might keep the block, the closure must
+/// be `'static` (it can't borrow anything).
+#[repr(C)]
+pub struct StackBlock<A, R, F> {
+  block: Block<A, R>,
+  closure: F,
+}
+
+impl<A, R, F> StackBlock<A, R, F>
+where
+  A: BlockArguments,
+  R: TypeEncoding,
+  F: IntoBlock<A, R> + Clone + 'static,
+{
+  const HAS_SIGNATURE: bool = core::mem::size_of::<Signature<A, R>>() <= MAX_SIGNATURE_LEN;
+
+  const SIGNATURE: [u8; MAX_SIGNATURE_LEN] = unsafe {
+    TransmuteHack::<PaddedSignature<A, R>, [u8; MAX_SIGNATURE_LEN]> {
+      from: Packed2(
+        Packed3(Packed2(<R as TypeEncoding>::BYTES, *b"@?"), <A as FieldsTypeEncoding>::BYTES, 0),
+        [0; MAX_SIGNATURE_LEN],
+      ),
+    }
+    .to
+  };
+
+  const DESCRIPTOR: &'static BlockDescriptor = &BlockDescriptor {
+    reserved: 0,
+    size: core::mem::size_of::<StackBlock<A, R, F>>(),
+    copy: Self::copy_helper,
+    dispose: Self::dispose_helper,
+    signature: &Self::SIGNATURE as *const [u8; MAX_SIGNATURE_LEN] as *const libc::c_char,
+  };
+
+  pub fn new(closure: F) -> StackBlock<A, R, F> {
+    return StackBlock {
+      block: Block {
+        isa: unsafe { &runtime::_NSConcreteStackBlock },
+        flags: BLOCK_HAS_COPY_DISPOSE | (BLOCK_HAS_SIGNATURE * Self::HAS_SIGNATURE as i32),
+        reserved: 0,
+        invoke: <F as IntoBlock<A, R>>::invoke(),
+        descriptor: Self::DESCRIPTOR,
+        marker: PhantomData,
+      },
+      closure: closure,
+    };
+  }
+
+  /// Copies the block to the heap (see `Block::copy`).
+  #[inline]
+  pub fn copy(&self) -> RcBlock<A, R> {
+    return unsafe { self.block.copy() };
+  }
+
+  // Called by `_Block_copy` after it copies the block's bytes to `dst`.
+  unsafe extern "C" fn copy_helper(dst: *mut libc::c_void, src: *const libc::c_void) {
+    let src = &*(src as *const StackBlock<A, R, F>);
+    let dst = dst as *mut StackBlock<A, R, F>;
+    core::ptr::write(&mut (*dst).closure, src.closure.clone());
+  }
+
+  // Called by `_Block_release` before it frees a heap block.
+  unsafe extern "C" fn dispose_helper(block: *mut libc::c_void) {
+    core::ptr::drop_in_place(&mut (*(block as *mut StackBlock<A, R, F>)).closure);
+  }
+}
+
+impl<A, R, F> core::ops::Deref for StackBlock<A, R, F> {
+  type Target = Block<A, R>;
+
+  #[inline(always)]
+  fn deref(&self) -> &Self::Target {
+    return &self.block;
+  }
+}
+
+/// A reference to a block on the heap (e.g. from `StackBlock::copy` or `Block::copy`). Cloning it
+/// retains the block, and dropping it releases the block.
+pub struct RcBlock<A, R> {
+  block: NonNull<Block<A, R>>,
+}
+
+impl<A, R> RcBlock<A, R>
+where
+  A: BlockArguments,
+  R: TypeEncoding,
+{
+  /// Creates a heap block from a closure.
+  #[inline]
+  pub fn new<F: IntoBlock<A, R> + Clone + 'static>(closure: F) -> RcBlock<A, R> {
+    return StackBlock::new(closure).copy();
+  }
+}
+
+impl<A, R> Clone for RcBlock<A, R> {
+  #[inline]
+  fn clone(&self) -> RcBlock<A, R> {
+    let copy = unsafe { runtime::_Block_copy(self.block.as_ptr() as *const libc::c_void) };
+    return RcBlock {
+      block: unsafe { NonNull::new_unchecked(copy as *mut Block<A, R>) },
+    };
+  }
+}
+
+impl<A, R> core::ops::Deref for RcBlock<A, R> {
+  type Target = Block<A, R>;
+
+  #[inline(always)]
+  fn deref(&self) -> &Self::Target {
+    return unsafe { self.block.as_ref() };
+  }
+}
+
+impl<A, R> core::ops::Drop for RcBlock<A, R> {
+  #[inline]
+  fn drop(&mut self) {
+    unsafe { runtime::_Block_release(self.block.as_ptr() as *const libc::c_void) };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::__objrs::type_encoding_bytes;
+  #[cfg(feature = "mock-runtime")]
+  use crate::mock::{add_method, new_object, register_class};
+  #[cfg(feature = "mock-runtime")]
+  use crate::runtime::{Id, Sel};
+  #[cfg(feature = "mock-runtime")]
+  use core::sync::atomic::{AtomicUsize, Ordering};
+
+  #[test]
+  fn signature() {
+    fn signature<A: BlockArguments, R: TypeEncoding, F: IntoBlock<A, R> + Clone + 'static>(
+      _closure: F,
+    ) -> &'static [u8] {
+      let signature: &'static [u8] = &StackBlock::<A, R, F>::SIGNATURE;
+      let len = signature.iter().position(|&byte| byte == 0).unwrap();
+      return &signature[..=len];
+    }
+
+    assert_eq!(signature(|| {}), b"v@?\0");
+    assert_eq!(signature(|a: i32, b: f64| a as f64 + b), b"d@?id\0");
+    assert_eq!(signature(|_: *const u8| true), b"B@?^C\0");
+    assert!(StackBlock::<(), (), fn()>::HAS_SIGNATURE);
+    assert_eq!(type_encoding_bytes(&<&Block<(i32,), i32> as TypeEncoding>::BYTES), b"@?");
+    assert_eq!(type_encoding_bytes(&<Option<&Block<(), ()>> as TypeEncoding>::BYTES), b"@?");
+  }
+
+  #[test]
+  fn extended_encoding() {
+    assert_eq!(
+      type_encoding_bytes(&<&Block<(), ()> as ExtendedTypeEncodingHack>::BYTES),
+      b"@?<v@?>"
+    );
+    assert_eq!(
+      type_encoding_bytes(
+        &<Option<&Block<(i32, *const u8), f64>> as ExtendedTypeEncodingHack>::BYTES
+      ),
+      b"@?<d@?ir^C>"
+    );
+    assert_eq!(
+      type_encoding_bytes(&<&Block<(&Block<(), bool>,), ()> as ExtendedTypeEncodingHack>::BYTES),
+      b"@?<v@?@?<B@?>>"
+    );
+    assert_eq!(type_encoding_bytes(&<i32 as ExtendedTypeEncodingHack>::BYTES), b"i");
+  }
+
+  #[cfg(feature = "mock-runtime")]
+  #[test]
+  fn blocks() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone)]
+    struct Counted(i32);
+    impl Drop for Counted {
+      fn drop(&mut self) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+      }
+    }
+
+    let counted = Counted(10);
+    let block = StackBlock::new(move |a: i32, b: i32| a + b + counted.0);
+    assert_eq!(block.call((1, 2)), 13);
+
+    // Copying a stack block clones its closure, and copying a heap block retains it.
+    let copy = block.copy();
+    assert_eq!(copy.call((3, 4)), 17);
+    let retained = copy.clone();
+    assert!(core::ptr::eq(&*retained, &*copy));
+    let retained_again = unsafe { retained.copy() };
+    assert!(core::ptr::eq(&*retained_again, &*copy));
+
+    drop(block);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    drop(copy);
+    drop(retained);
+    assert_eq!(retained_again.call((0, 0)), 10);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    drop(retained_again);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+  }
+
+  #[cfg(all(
+    feature = "mock-runtime",
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    not(target_vendor = "apple"),
+  ))]
+  #[test]
+  fn block_arguments() {
+    extern "C" fn apply(_this: &Id, _sel: &Sel, block: &Block<(i32,), i32>, value: i32) -> i32 {
+      return block.call((value,));
+    }
+
+    let class = register_class("MockBlockReceiver", None);
+    add_method(class, crate::selector!("apply:to:"), apply as *const (), "i28@0:8@?16i24");
+    let object = new_object(class);
+
+    let offset = 5;
+    let block = StackBlock::new(move |value: i32| value * 2 + offset);
+    let result = unsafe { object.send::<_, i32>(crate::selector!("apply:to:"), (&*block, 4)) };
+    assert_eq!(result, 13);
+  }
+}
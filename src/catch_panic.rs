@@ -0,0 +1,161 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Catching panics in methods implemented in Rust (enabled with `catch_panic` on #[objrs(selector)],
+// or for every method with the `catch-panic` feature, which aborts).
+//
+// A method implementation is called by Objective-C, so a panic that unwinds out of it unwinds through
+// Objective-C frames (which is undefined behavior). With `catch_panic`, the msg_recv function that
+// #[objrs(selector)] generates calls the method's body through one of these functions, which either
+// abort the process or convert the panic into an NSException and throw it to the caller.
+
+extern crate std;
+
+use crate::runtime::{self, Class, Id, Sel};
+use crate::CStr;
+use std::any::Any;
+use std::ffi::CString;
+use std::string::String;
+
+// The name of the NSExceptions that panics are converted into.
+const PANIC_EXCEPTION_NAME: &str = "RustPanic";
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+  if let Some(message) = payload.downcast_ref::<&'static str>() {
+    return message;
+  }
+  if let Some(message) = payload.downcast_ref::<String>() {
+    return message;
+  }
+  return "Box<Any>";
+}
+
+/// Calls `f`, aborting the process if it panics. `method` (e.g. `-[MyClass doFoo:]`) is printed
+/// along with the panic message.
+#[inline]
+pub fn abort_on_panic<R, F: FnOnce() -> R>(method: &str, f: F) -> R {
+  match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+    Ok(ret) => return ret,
+    Err(payload) => abort(method, &*payload),
+  }
+}
+
+/// Calls `f`, converting a panic into an NSException (named `RustPanic`, whose reason holds `method`
+/// and the panic message) that is thrown to the caller. If Foundation isn't loaded, the process
+/// aborts instead (as with `abort_on_panic`).
+#[inline]
+pub fn throw_on_panic<R, F: FnOnce() -> R>(method: &str, f: F) -> R {
+  match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+    Ok(ret) => return ret,
+    Err(payload) => throw(method, &*payload),
+  }
+}
+
+#[cold]
+#[inline(never)]
+fn abort(method: &str, payload: &(dyn Any + Send)) -> ! {
+  std::eprintln!("{} panicked: {}; aborting", method, panic_message(payload));
+  std::process::abort();
+}
+
+#[cold]
+#[inline(never)]
+fn throw(method: &str, payload: &(dyn Any + Send)) -> ! {
+  let reason = std::format!("{} panicked: {}", method, panic_message(payload));
+  match unsafe { new_exception(PANIC_EXCEPTION_NAME, &reason) } {
+    Some(exception) => unsafe { runtime::objc_exception_throw(exception) },
+    None => abort(method, payload),
+  }
+}
+
+// Returns a new (autoreleased) NSString, or None if NSString isn't available.
+unsafe fn new_string(string: &str) -> Option<*mut Id> {
+  let class = Class::get(CStr::new("NSString\0"))?;
+  // Interior NULs would truncate the string.
+  let string = CString::new(string.replace('\0', "\u{fffd}")).unwrap();
+  let sel = Sel::register(CStr::new("stringWithUTF8String:\0"));
+  let string: *mut Id = class.send(sel, (string.as_ptr(),));
+  return if string.is_null() { None } else { Some(string) };
+}
+
+// Returns a new (autoreleased) NSException, or None if NSException isn't available.
+unsafe fn new_exception(name: &str, reason: &str) -> Option<*mut Id> {
+  let class = Class::get(CStr::new("NSException\0"))?;
+  let name = new_string(name)?;
+  let reason = new_string(reason)?;
+  let user_info: *mut Id = core::ptr::null_mut();
+  let sel = Sel::register(CStr::new("exceptionWithName:reason:userInfo:\0"));
+  let exception: *mut Id = class.send(sel, (name, reason, user_info));
+  return if exception.is_null() { None } else { Some(exception) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::boxed::Box;
+  #[cfg(feature = "mock-runtime")]
+  use std::sync::Mutex;
+  #[cfg(feature = "mock-runtime")]
+  use std::vec::Vec;
+
+  #[test]
+  fn panic_messages() {
+    let payload: Box<dyn Any + Send> = Box::new("static message");
+    assert_eq!(panic_message(&*payload), "static message");
+    let payload: Box<dyn Any + Send> = Box::new(String::from("owned message"));
+    assert_eq!(panic_message(&*payload), "owned message");
+    let payload: Box<dyn Any + Send> = Box::new(42);
+    assert_eq!(panic_message(&*payload), "Box<Any>");
+  }
+
+  #[test]
+  fn no_panic() {
+    assert_eq!(abort_on_panic("-[Foo bar]", || 1 + 1), 2);
+    assert_eq!(throw_on_panic("+[Foo bar]", || "baz"), "baz");
+  }
+
+  #[cfg(all(
+    feature = "mock-runtime",
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    not(target_vendor = "apple"),
+  ))]
+  #[test]
+  fn throw_panic() {
+    use crate::mock::{add_class_method, register_class};
+    use crate::selector;
+
+    // Stand-ins for Foundation's NSString and NSException, which record the strings they're given.
+    static STRINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    extern "C" fn string(this: &Class, _sel: &Sel, utf8: &CStr) -> *const Class {
+      STRINGS.lock().unwrap().push(utf8.to_str().unwrap().into());
+      return this;
+    }
+    extern "C" fn exception(
+      this: &Class,
+      _sel: &Sel,
+      _name: *mut Id,
+      _reason: *mut Id,
+      _user_info: *mut Id,
+    ) -> *const Class {
+      return this;
+    }
+
+    let ns_string = register_class("NSString", None);
+    let sel = selector!("stringWithUTF8String:");
+    add_class_method(ns_string, sel, string as *const (), "@24@0:8r*16");
+    let ns_exception = register_class("NSException", None);
+    let sel = selector!("exceptionWithName:reason:userInfo:");
+    add_class_method(ns_exception, sel, exception as *const (), "@40@0:8@16@24@32");
+
+    let result = std::panic::catch_unwind(|| {
+      throw_on_panic("+[MockCatchPanic explode]", || -> i32 { panic!("boom") })
+    });
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("Objective-C exception thrown"));
+    let strings = STRINGS.lock().unwrap();
+    assert_eq!(&strings[..], ["RustPanic", "+[MockCatchPanic explode] panicked: boom"]);
+  }
+}
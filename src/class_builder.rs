@@ -0,0 +1,349 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Creates classes at runtime (for classes whose names or contents aren't known at compile time).
+// Classes that are known at compile time should use `#[objrs(class)]` instead, which emits the class
+// data directly into the binary.
+//
+// See https://developer.apple.com/documentation/objectivec/1418559-objc_allocateclasspair
+
+extern crate alloc;
+extern crate core;
+
+use crate::__objrs::type_encoding_bytes;
+use crate::runtime::{self, Class, Id, Imp, Protocol, Sel};
+use crate::{CStr, TypeEncoding};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// A function that can be used as a method's implementation. It's implemented for `extern "C"`
+/// functions whose first two parameters are the receiver (`&Id` for instance methods, or `&Class`
+/// for class methods) and the selector, and whose remaining parameters and return type implement
+/// `TypeEncoding`.
+///
+/// Functions must be converted to function pointers before they can be used (e.g., `my_method as
+/// extern "C" fn(&Id, &Sel, i32) -> f64`).
+pub unsafe trait MethodImplementation {
+  /// The receiver: `Id` for instance methods or `Class` for class methods.
+  type Receiver: ?Sized;
+
+  /// The implementation's address.
+  fn imp(self) -> &'static Imp;
+
+  /// Appends the method's type encoding (e.g. `d@:i`) to `types`. Frame offsets are omitted, since
+  /// the runtime doesn't need them.
+  fn type_encoding(types: &mut Vec<u8>);
+}
+
+macro_rules! method_implementation {
+  ($($arg:ident),*) => {
+    method_implementation!{@impl [Id] b'@', [] $($arg),*}
+    method_implementation!{@impl [Id] b'@', [unsafe] $($arg),*}
+    method_implementation!{@impl [Class] b'#', [] $($arg),*}
+    method_implementation!{@impl [Class] b'#', [unsafe] $($arg),*}
+  };
+  (@impl [$receiver:ty] $receiver_encoding:expr, [$($unsafety:tt)*] $($arg:ident),*) => {
+    unsafe impl<Ret: TypeEncoding, $($arg: TypeEncoding),*> MethodImplementation
+      for $($unsafety)* extern "C" fn(&$receiver, &Sel, $($arg),*) -> Ret
+    {
+      type Receiver = $receiver;
+
+      #[inline(always)]
+      fn imp(self) -> &'static Imp {
+        return unsafe { &*(self as *const () as *const Imp) };
+      }
+
+      fn type_encoding(types: &mut Vec<u8>) {
+        types.extend_from_slice(type_encoding_bytes(&<Ret as TypeEncoding>::BYTES));
+        types.push($receiver_encoding);
+        types.push(b':');
+        $(types.extend_from_slice(type_encoding_bytes(&<$arg as TypeEncoding>::BYTES));)*
+      }
+    }
+  };
+}
+
+method_implementation!();
+method_implementation!(A);
+method_implementation!(A, B);
+method_implementation!(A, B, C);
+method_implementation!(A, B, C, D);
+method_implementation!(A, B, C, D, E);
+method_implementation!(A, B, C, D, E, F);
+method_implementation!(A, B, C, D, E, F, G);
+method_implementation!(A, B, C, D, E, F, G, H);
+
+// Terminates a type encoding so that it can be passed to the runtime.
+fn to_cstr(types: &mut Vec<u8>) -> &CStr {
+  types.push(b'\0');
+  return unsafe { CStr::with_bytes(types) };
+}
+
+/// Builds a new class at runtime (wrapping `objc_allocateClassPair`). Ivars, methods, and protocols
+/// are added to the class and then it's registered with `register`, which returns the new class.
+/// If the builder is dropped without being registered, the class is disposed of.
+pub struct ClassBuilder {
+  class: NonNull<Class>,
+  ivar_count: usize,
+}
+
+/// An ivar added by `ClassBuilder::add_ivar`. Its offset isn't known until the class is registered,
+/// so it's passed to `RegisteredClass::ivar` to get the `IvarOffset` that accesses it.
+pub struct IvarIndex<T> {
+  class: NonNull<Class>,
+  index: usize,
+  marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for IvarIndex<T> {
+  #[inline(always)]
+  fn clone(&self) -> IvarIndex<T> {
+    return *self;
+  }
+}
+
+impl<T> Copy for IvarIndex<T> {}
+
+/// A class created by `ClassBuilder`, along with the offsets of the ivars that were added to it
+/// (which the runtime picks when the class is registered). Derefs to `Class`.
+pub struct RegisteredClass {
+  class: &'static Class,
+  ivar_offsets: Vec<isize>,
+}
+
+impl RegisteredClass {
+  /// The class itself.
+  #[inline(always)]
+  pub fn class(&self) -> &'static Class {
+    return self.class;
+  }
+
+  /// Returns the offset of an ivar that was added to this class. Panics if `ivar` was added to a
+  /// different class.
+  pub fn ivar<T>(&self, ivar: IvarIndex<T>) -> IvarOffset<T> {
+    assert!(core::ptr::eq(ivar.class.as_ptr(), self.class), "the ivar belongs to another class");
+    return IvarOffset {
+      offset: self.ivar_offsets[ivar.index],
+      marker: PhantomData,
+    };
+  }
+}
+
+impl core::ops::Deref for RegisteredClass {
+  type Target = Class;
+
+  #[inline(always)]
+  fn deref(&self) -> &Class {
+    return self.class;
+  }
+}
+
+/// An ivar of type `T` in instances of a class created by `ClassBuilder`. The ivar is at `offset`
+/// bytes from the start of the object (as returned by `ivar_getOffset`).
+pub struct IvarOffset<T> {
+  offset: isize,
+  marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for IvarOffset<T> {
+  #[inline(always)]
+  fn clone(&self) -> IvarOffset<T> {
+    return *self;
+  }
+}
+
+impl<T> Copy for IvarOffset<T> {}
+
+impl<T> IvarOffset<T> {
+  /// The ivar's offset in bytes from the start of the object.
+  #[inline(always)]
+  pub fn offset(self) -> isize {
+    return self.offset;
+  }
+
+  /// Returns a reference to the ivar in `obj`. This is unsafe because `obj` must be an instance of
+  /// the ivar's class (or one of its subclasses), and the ivar must have been initialized (the
+  /// runtime zeroes ivars when the object is allocated).
+  #[inline(always)]
+  pub unsafe fn get(self, obj: &Id) -> &T {
+    return &*((obj as *const Id as *const u8).offset(self.offset) as *const T);
+  }
+
+  /// Returns a mutable reference to the ivar in `obj`. See `get`.
+  #[inline(always)]
+  pub unsafe fn get_mut(self, obj: &mut Id) -> &mut T {
+    return &mut *((obj as *mut Id as *mut u8).offset(self.offset) as *mut T);
+  }
+}
+
+impl ClassBuilder {
+  /// Starts building a class named `name` that inherits from `superclass` (or a new root class if
+  /// `superclass` is `None`). Returns `None` if a class with the same name already exists.
+  pub fn new(name: &CStr, superclass: Option<&Class>) -> Option<ClassBuilder> {
+    let class = unsafe { runtime::objc_allocateClassPair(superclass, name, 0) };
+    return NonNull::new(class).map(|class| ClassBuilder {
+      class: class,
+      ivar_count: 0,
+    });
+  }
+
+  /// Adds an ivar of type `T`, returning its index (which `RegisteredClass::ivar` turns into the
+  /// ivar's offset). Returns `None` if the class already has an ivar with the same name.
+  pub fn add_ivar<T: TypeEncoding>(&mut self, name: &CStr) -> Option<IvarIndex<T>> {
+    let mut types = Vec::new();
+    types.extend_from_slice(type_encoding_bytes(&<T as TypeEncoding>::BYTES));
+    // The runtime takes the alignment as its base 2 logarithm.
+    let alignment = core::mem::align_of::<T>().trailing_zeros() as u8;
+    let added = unsafe {
+      runtime::class_addIvar(
+        self.class.as_ptr(),
+        name,
+        core::mem::size_of::<T>(),
+        alignment,
+        to_cstr(&mut types),
+      )
+    };
+    if !added {
+      return None;
+    }
+    self.ivar_count += 1;
+    return Some(IvarIndex {
+      class: self.class,
+      index: self.ivar_count - 1,
+      marker: PhantomData,
+    });
+  }
+
+  /// Adds an instance method. This overrides any superclass implementation, but returns false
+  /// (without replacing the implementation) if the class itself already implements the selector.
+  pub fn add_method<F>(&mut self, sel: &Sel, imp: F) -> bool
+  where
+    F: MethodImplementation<Receiver = Id>,
+  {
+    return unsafe { add_method(self.class.as_ptr(), sel, imp) };
+  }
+
+  /// Adds a class method. See `add_method`.
+  pub fn add_class_method<F>(&mut self, sel: &Sel, imp: F) -> bool
+  where
+    F: MethodImplementation<Receiver = Class>,
+  {
+    let metaclass = unsafe { runtime::object_getClass(self.class.as_ptr() as *mut Id) };
+    return unsafe { add_method(metaclass, sel, imp) };
+  }
+
+  /// Declares that the class conforms to `protocol`. Returns false if it already does.
+  pub fn add_protocol(&mut self, protocol: &Protocol) -> bool {
+    return unsafe { runtime::class_addProtocol(self.class.as_ptr(), protocol) };
+  }
+
+  /// Registers the class with the runtime, after which it can be instantiated and looked up by name.
+  /// The returned class records the offsets of the ivars that were added to it.
+  pub fn register(self) -> RegisteredClass {
+    let class = self.class;
+    let ivar_count = self.ivar_count;
+    core::mem::forget(self);
+    let class: &'static Class = unsafe {
+      runtime::objc_registerClassPair(class.as_ptr());
+      &*class.as_ptr()
+    };
+    // The runtime lists the class's own ivars in the order they were added.
+    let ivar_offsets: Vec<isize> = class.ivars().map(|ivar| ivar.offset()).collect();
+    debug_assert_eq!(ivar_offsets.len(), ivar_count);
+    return RegisteredClass {
+      class: class,
+      ivar_offsets: ivar_offsets,
+    };
+  }
+}
+
+unsafe fn add_method<F: MethodImplementation>(class: *mut Class, sel: &Sel, imp: F) -> bool {
+  let mut types = Vec::new();
+  F::type_encoding(&mut types);
+  return runtime::class_addMethod(class, sel, imp.imp(), to_cstr(&mut types));
+}
+
+impl core::ops::Drop for ClassBuilder {
+  #[inline(always)]
+  fn drop(&mut self) {
+    unsafe { runtime::objc_disposeClassPair(self.class.as_ptr()) };
+  }
+}
+
+#[cfg(all(test, feature = "mock-runtime"))]
+mod tests {
+  use super::*;
+  use crate::mock::{register_class, register_protocol};
+  use crate::selector;
+
+  #[test]
+  fn class_builder() {
+    extern "C" fn double(_this: &Id, _sel: &Sel, value: i32) -> f64 {
+      return value as f64 * 2.0;
+    }
+    extern "C" fn count(_this: &Class, _sel: &Sel) -> usize {
+      return 7;
+    }
+
+    let root = register_class("MockBuilderRoot", None);
+    let protocol = register_protocol("MockBuilderProtocol");
+    let name = unsafe { CStr::new("MockBuiltClass\0") };
+    unsafe {
+      assert!(core::ptr::eq(Protocol::get(CStr::new("MockBuilderProtocol\0")).unwrap(), protocol));
+      assert!(Protocol::get(CStr::new("MockMissingProtocol\0")).is_none());
+    }
+
+    let mut builder = ClassBuilder::new(name, Some(root)).unwrap();
+    let count_ivar = builder.add_ivar::<u32>(unsafe { CStr::new("count\0") }).unwrap();
+    assert!(builder.add_ivar::<u64>(unsafe { CStr::new("count\0") }).is_none());
+    let total_ivar = builder.add_ivar::<f64>(unsafe { CStr::new("total\0") }).unwrap();
+    let double = double as extern "C" fn(&Id, &Sel, i32) -> f64;
+    assert!(builder.add_method(selector!("double:"), double));
+    assert!(!builder.add_method(selector!("double:"), double));
+    let count = count as extern "C" fn(&Class, &Sel) -> usize;
+    assert!(builder.add_class_method(selector!("count"), count));
+    assert!(builder.add_protocol(protocol));
+    assert!(!builder.add_protocol(protocol));
+    assert!(unsafe { runtime::objc_getClass(name) }.is_null());
+
+    let registered = builder.register();
+    let class = registered.class();
+    assert!(core::ptr::eq(unsafe { runtime::objc_getClass(name) }, class));
+    assert!(ClassBuilder::new(name, None).is_none());
+    unsafe {
+      let class = class as *const Class as *mut Class;
+      assert!(core::ptr::eq(runtime::class_getSuperclass(class), root));
+      let method = runtime::class_getInstanceMethod(class, selector!("double:"));
+      let types = runtime::method_getTypeEncoding(NonNull::new(method).unwrap()).unwrap();
+      assert_eq!(types.to_bytes(), b"d@:i");
+      let method = runtime::class_getClassMethod(class, selector!("count"));
+      let types = runtime::method_getTypeEncoding(NonNull::new(method).unwrap()).unwrap();
+      assert_eq!(types.to_bytes(), b"Q#:");
+    }
+
+    // The mock runtime doesn't allocate storage for ivars, so access them in a buffer that's laid
+    // out like an instance.
+    let count_ivar = registered.ivar(count_ivar);
+    let total_ivar = registered.ivar(total_ivar);
+    let offsets: Vec<isize> = class.ivars().map(|ivar| ivar.offset()).collect();
+    assert_eq!(offsets, [count_ivar.offset(), total_ivar.offset()]);
+    assert!(total_ivar.offset() % core::mem::align_of::<f64>() as isize == 0);
+    let mut instance = [0u64; 8];
+    unsafe {
+      let obj = &mut *(instance.as_mut_ptr() as *mut Id);
+      *count_ivar.get_mut(obj) = 3;
+      *total_ivar.get_mut(obj) = 1.5;
+      assert_eq!(*count_ivar.get(obj), 3);
+      assert_eq!(*total_ivar.get(obj), 1.5);
+    }
+
+    // Dropping a builder disposes of the class, so the name can be reused.
+    let name = unsafe { CStr::new("MockDisposedClass\0") };
+    drop(ClassBuilder::new(name, None).unwrap());
+    ClassBuilder::new(name, None).unwrap().register();
+    assert!(!unsafe { runtime::objc_getClass(name) }.is_null());
+  }
+}
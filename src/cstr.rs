@@ -31,4 +31,13 @@ impl CStr {
   pub fn as_ptr(&self) -> *const libc::c_char {
     return unsafe { core::mem::transmute(self) };
   }
+
+  /// The string's bytes, excluding the NUL terminator.
+  pub fn to_bytes(&self) -> &[u8] {
+    return unsafe { core::slice::from_raw_parts(self.as_ptr() as *const u8, self.len()) };
+  }
+
+  pub fn to_str(&self) -> Result<&str, core::str::Utf8Error> {
+    return core::str::from_utf8(self.to_bytes());
+  }
 }
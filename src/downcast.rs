@@ -0,0 +1,144 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Checked downcasts. Upcasting is always safe (see `Strong::into_super`), but downcasting requires
+// asking the object what it is. The target class's object comes from its class reference (see
+// `marker::ClassRef`), and the object is checked with `isKindOfClass:` (or `isMemberOfClass:`), so
+// classes (like NSProxy) that override these methods are respected.
+//
+// `Strong::downcast` and `Auto::downcast` are in arc.rs.
+
+extern crate core;
+
+use crate::marker;
+use crate::runtime::{Class, Id};
+
+// These send the message from a non-generic function because selector references are local symbols
+// (see DOCUMENTATION.md's limitations).
+#[inline(never)]
+fn is_kind_of_class(object: &Id, class: &Class) -> bool {
+  return unsafe { object.send::<_, bool>(crate::selector!("isKindOfClass:"), (class,)) };
+}
+
+#[inline(never)]
+fn is_member_of_class(object: &Id, class: &Class) -> bool {
+  return unsafe { object.send::<_, bool>(crate::selector!("isMemberOfClass:"), (class,)) };
+}
+
+impl<U: ?Sized> Id<U> {
+  #[inline(always)]
+  fn as_id(&self) -> &Id {
+    return unsafe { &*(self as *const Id<U> as *const Id) };
+  }
+
+  /// Returns true if the object is an instance of `T` or one of its subclasses (i.e.
+  /// `[object isKindOfClass:[T class]]`).
+  #[inline]
+  pub fn is_kind_of<T: marker::ClassRef>(&self) -> bool {
+    return is_kind_of_class(self.as_id(), T::__objrs_class());
+  }
+
+  /// Returns true if the object is an instance of `T` (but not one of its subclasses) (i.e.
+  /// `[object isMemberOfClass:[T class]]`).
+  #[inline]
+  pub fn is_member_of<T: marker::ClassRef>(&self) -> bool {
+    return is_member_of_class(self.as_id(), T::__objrs_class());
+  }
+
+  /// Casts the object to a `T` if it's an instance of `T` or one of its subclasses. Returns `None`
+  /// otherwise.
+  #[inline]
+  pub fn downcast<T: marker::ClassRef>(&self) -> Option<&T> {
+    if self.is_kind_of::<T>() {
+      return Some(unsafe { &*(self as *const Id<U> as *const T) });
+    }
+    return None;
+  }
+}
+
+#[cfg(all(
+  test,
+  feature = "mock-runtime",
+  any(target_arch = "x86_64", target_arch = "aarch64"),
+  not(target_vendor = "apple"),
+))]
+mod tests {
+  use super::*;
+  use crate::mock::{add_method, new_object, register_class, retain_count};
+  use crate::runtime::{self, Sel};
+  use crate::{autoreleasepool, selector, CStr};
+
+  #[test]
+  fn downcast() {
+    // isKindOfClass: and isMemberOfClass:, as NSObject implements them.
+    extern "C" fn is_kind_of_class(this: &Id, _sel: &Sel, class: &Class) -> bool {
+      let mut current = unsafe { runtime::object_getClass(this as *const Id as *mut Id) };
+      while !current.is_null() {
+        if core::ptr::eq(current, class) {
+          return true;
+        }
+        current = unsafe { runtime::class_getSuperclass(current) };
+      }
+      return false;
+    }
+    extern "C" fn is_member_of_class(this: &Id, _sel: &Sel, class: &Class) -> bool {
+      let this_class = unsafe { runtime::object_getClass(this as *const Id as *mut Id) };
+      return core::ptr::eq(this_class, class);
+    }
+
+    let root = register_class("MockDowncastRoot", None);
+    add_method(root, selector!("isKindOfClass:"), is_kind_of_class as *const (), "B24@0:8#16");
+    add_method(root, selector!("isMemberOfClass:"), is_member_of_class as *const (), "B24@0:8#16");
+    let base = register_class("MockDowncastBase", Some(root));
+    let derived = register_class("MockDowncastDerived", Some(base));
+
+    // A stand-in for a class declared with #[objrs(class)] and #[objrs(impl)], whose class reference
+    // would be resolved by the linker.
+    #[repr(C)]
+    struct MockDowncastBase {
+      isa: *const Class,
+    }
+    fn as_id(object: &MockDowncastBase) -> *const Id {
+      return object as *const MockDowncastBase as *const Id;
+    }
+    unsafe impl marker::Class for MockDowncastBase {}
+    unsafe impl marker::ClassRef for MockDowncastBase {
+      fn __objrs_class() -> &'static Class {
+        return Class::get(unsafe { CStr::new("MockDowncastBase\0") }).unwrap();
+      }
+    }
+
+    let base_object = new_object(base);
+    let derived_object = new_object(derived);
+    let root_object = new_object(root);
+
+    assert!(base_object.is_kind_of::<MockDowncastBase>());
+    assert!(base_object.is_member_of::<MockDowncastBase>());
+    assert!(derived_object.is_kind_of::<MockDowncastBase>());
+    assert!(!derived_object.is_member_of::<MockDowncastBase>());
+    assert!(!root_object.is_kind_of::<MockDowncastBase>());
+
+    let downcast = (*derived_object).downcast::<MockDowncastBase>().unwrap();
+    assert!(core::ptr::eq(as_id(downcast), &*derived_object));
+    assert!((*root_object).downcast::<MockDowncastBase>().is_none());
+
+    let strong = derived_object.clone().downcast::<MockDowncastBase>().unwrap();
+    assert_eq!(retain_count(&derived_object), 2);
+    drop(strong);
+    let original = root_object.clone().downcast::<MockDowncastBase>().unwrap_err();
+    assert!(core::ptr::eq(&*original, &*root_object));
+    assert_eq!(retain_count(&root_object), 2);
+    drop(original);
+    assert_eq!(retain_count(&root_object), 1);
+
+    autoreleasepool(|pool| {
+      let auto = base_object.clone().autorelease(pool);
+      let auto = auto.downcast::<MockDowncastBase>().unwrap();
+      assert!(core::ptr::eq(as_id(&auto), &*base_object));
+      let auto = root_object.clone().autorelease(pool);
+      assert!(auto.downcast::<MockDowncastBase>().is_err());
+    });
+  }
+}
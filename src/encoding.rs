@@ -0,0 +1,884 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+//! A parser for Objective-C type encodings.
+//!
+//! See https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtTypeEncodings.html
+//! and clang's ASTContext::getObjCEncodingForTypeImpl for the encoding grammar.
+//!
+//! The parser doesn't allocate. Nested types (pointees, array elements, and struct/union fields) are
+//! validated when the outer type is parsed, but are only decoded on demand (via [`Nested::get`] and
+//! [`Fields`]).
+//!
+//! ```ignore
+//! let encoding = objrs::encoding::parse("^{CGPoint=dd}").unwrap();
+//! if let Kind::Pointer(pointee) = encoding.kind() {
+//!   assert_eq!(pointee.get().size(), Some(16));
+//! }
+//! ```
+
+extern crate core;
+extern crate libc;
+
+use core::fmt;
+
+/// A type qualifier. Qualifiers precede the type they qualify (e.g. `r*` is a `const char *`).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Qualifier {
+  /// `r`
+  Const,
+  /// `n`
+  In,
+  /// `N`
+  InOut,
+  /// `o`
+  Out,
+  /// `O`
+  Bycopy,
+  /// `R`
+  Byref,
+  /// `V`
+  Oneway,
+}
+
+// In canonical (i.e. clang's) order.
+const QUALIFIERS: [(Qualifier, u8); 7] = [
+  (Qualifier::Const, b'r'),
+  (Qualifier::In, b'n'),
+  (Qualifier::InOut, b'N'),
+  (Qualifier::Out, b'o'),
+  (Qualifier::Bycopy, b'O'),
+  (Qualifier::Byref, b'R'),
+  (Qualifier::Oneway, b'V'),
+];
+
+impl Qualifier {
+  fn from_byte(byte: u8) -> Option<Qualifier> {
+    for &(qualifier, code) in QUALIFIERS.iter() {
+      if code == byte {
+        return Some(qualifier);
+      }
+    }
+    return None;
+  }
+
+  /// The qualifier's code (e.g. `b'r'` for `Const`).
+  pub fn code(self) -> u8 {
+    return QUALIFIERS[self as usize].1;
+  }
+}
+
+/// A set of type qualifiers.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Qualifiers(u8);
+
+impl Qualifiers {
+  pub fn is_empty(self) -> bool {
+    return self.0 == 0;
+  }
+
+  pub fn contains(self, qualifier: Qualifier) -> bool {
+    return self.0 & (1 << qualifier as u8) != 0;
+  }
+
+  fn insert(&mut self, qualifier: Qualifier) {
+    self.0 |= 1 << qualifier as u8;
+  }
+}
+
+impl fmt::Display for Qualifiers {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for &(qualifier, code) in QUALIFIERS.iter() {
+      if self.contains(qualifier) {
+        fmt::Write::write_char(f, code as char)?;
+      }
+    }
+    return Ok(());
+  }
+}
+
+/// The kind of an encoded type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind<'a> {
+  /// `c`
+  Char,
+  /// `s`
+  Short,
+  /// `i`
+  Int,
+  /// `l` (which is always 32 bits, even on 64-bit targets)
+  Long,
+  /// `q`
+  LongLong,
+  /// `t`
+  Int128,
+  /// `C`
+  UnsignedChar,
+  /// `S`
+  UnsignedShort,
+  /// `I`
+  UnsignedInt,
+  /// `L`
+  UnsignedLong,
+  /// `Q`
+  UnsignedLongLong,
+  /// `T`
+  UnsignedInt128,
+  /// `f`
+  Float,
+  /// `d`
+  Double,
+  /// `D`
+  LongDouble,
+  /// `B`
+  Bool,
+  /// `v`
+  Void,
+  /// `*`
+  CString,
+  /// `#`
+  Class,
+  /// `:`
+  Sel,
+  /// `?` (e.g. a function pointer's pointee)
+  Unknown,
+  /// `@`, or `@"ClassName"` if the object's class is known.
+  Object(Option<&'a str>),
+  /// `@?`
+  Block,
+  /// `bN`, where `N` is the bitfield's width.
+  BitField(usize),
+  /// `^` followed by the pointee.
+  Pointer(Nested<'a>),
+  /// `[` count element `]`
+  Array(usize, Nested<'a>),
+  /// `{name=fields}`, or just `{name}` if the fields are omitted.
+  Struct(&'a str, Option<Fields<'a>>),
+  /// `(name=fields)`, or just `(name)` if the fields are omitted.
+  Union(&'a str, Option<Fields<'a>>),
+}
+
+/// An encoded type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Encoding<'a> {
+  qualifiers: Qualifiers,
+  kind: Kind<'a>,
+  str: &'a str,
+}
+
+/// A nested type (i.e. a pointee or array element) that has already been validated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Nested<'a>(&'a str);
+
+impl<'a> Nested<'a> {
+  pub fn get(self) -> Encoding<'a> {
+    return parse(self.0).expect("BUG: nested type encodings are validated when parsed");
+  }
+
+  pub fn as_str(self) -> &'a str {
+    return self.0;
+  }
+}
+
+/// A struct or union field. Field names are only present in some encodings (e.g. ivar encodings).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Field<'a> {
+  pub name: Option<&'a str>,
+  pub encoding: Encoding<'a>,
+}
+
+/// An iterator over a struct's or union's fields. The fields have already been validated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fields<'a> {
+  str: &'a str,
+  named: bool,
+}
+
+impl<'a> Fields<'a> {
+  pub fn as_str(self) -> &'a str {
+    return self.str;
+  }
+}
+
+impl<'a> Iterator for Fields<'a> {
+  type Item = Field<'a>;
+
+  fn next(&mut self) -> Option<Field<'a>> {
+    if self.str.is_empty() {
+      return None;
+    }
+    let mut parser = Parser::new(self.str);
+    let field = parser.field(self.named).expect("BUG: fields are validated when parsed");
+    self.str = &self.str[parser.pos..];
+    return Some(field);
+  }
+}
+
+/// The error returned when an encoding is malformed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseError {
+  /// The byte offset at which the encoding became malformed.
+  pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return write!(f, "malformed Objective-C type encoding at byte {}", self.position);
+  }
+}
+
+/// Parses an encoding that contains exactly one type (e.g. `^{CGPoint=dd}`).
+pub fn parse(encoding: &str) -> Result<Encoding, ParseError> {
+  let (parsed, rest) = parse_prefix(encoding)?;
+  if !rest.is_empty() {
+    return Err(ParseError {
+      position: encoding.len() - rest.len(),
+    });
+  }
+  return Ok(parsed);
+}
+
+/// Parses the first type in the encoding, returning it and the remainder of the encoding.
+pub fn parse_prefix(encoding: &str) -> Result<(Encoding, &str), ParseError> {
+  let mut parser = Parser::new(encoding);
+  let parsed = parser.encoding()?;
+  return Ok((parsed, &encoding[parser.pos..]));
+}
+
+/// Parses a method's type encoding (e.g. `v24@0:8@16`), yielding the return type followed by each
+/// argument's type (including the receiver and selector). Frame offsets are skipped.
+pub fn parse_method(encoding: &str) -> MethodTypes {
+  return MethodTypes {
+    str: encoding,
+    position: 0,
+  };
+}
+
+/// An iterator over the types in a method's type encoding. See [`parse_method`].
+#[derive(Clone, Debug)]
+pub struct MethodTypes<'a> {
+  str: &'a str,
+  position: usize,
+}
+
+impl<'a> Iterator for MethodTypes<'a> {
+  type Item = Result<Encoding<'a>, ParseError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.position >= self.str.len() {
+      return None;
+    }
+    let mut parser = Parser::new(self.str);
+    parser.pos = self.position;
+    let result = parser.encoding();
+    match result {
+      Ok(_) => {
+        // Skip the frame offset (which may be negative for some legacy encodings).
+        if parser.peek() == Some(b'-') {
+          parser.pos += 1;
+        }
+        parser.number();
+        self.position = parser.pos;
+      }
+      Err(_) => self.position = self.str.len(),
+    }
+    return Some(result);
+  }
+}
+
+struct Parser<'a> {
+  str: &'a str,
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn new(str: &'a str) -> Parser<'a> {
+    return Parser {
+      str: str,
+      pos: 0,
+    };
+  }
+
+  fn peek(&self) -> Option<u8> {
+    return self.str.as_bytes().get(self.pos).cloned();
+  }
+
+  fn error(&self) -> ParseError {
+    return ParseError {
+      position: self.pos,
+    };
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+    if self.peek() != Some(byte) {
+      return Err(self.error());
+    }
+    self.pos += 1;
+    return Ok(());
+  }
+
+  fn number(&mut self) -> Option<usize> {
+    let start = self.pos;
+    let mut value: usize = 0;
+    while let Some(byte @ b'0'..=b'9') = self.peek() {
+      value = value.checked_mul(10)?.checked_add((byte - b'0') as usize)?;
+      self.pos += 1;
+    }
+    if self.pos == start {
+      return None;
+    }
+    return Some(value);
+  }
+
+  // Parses a quoted string (e.g. a class name or field name), returning the string's contents.
+  fn quoted(&mut self) -> Result<&'a str, ParseError> {
+    self.expect(b'"')?;
+    let start = self.pos;
+    match self.str[start..].find('"') {
+      Some(len) => {
+        self.pos += len + 1;
+        return Ok(&self.str[start..start + len]);
+      }
+      None => return Err(self.error()),
+    }
+  }
+
+  // Parses the name of a struct or union, up to (but not including) the `=` or closing delimiter.
+  fn aggregate_name(&mut self, close: u8) -> Result<&'a str, ParseError> {
+    let start = self.pos;
+    loop {
+      match self.peek() {
+        Some(b'=') => break,
+        Some(byte) if byte == close => break,
+        Some(b'{') | Some(b'}') | Some(b'(') | Some(b')') | Some(b'[') | Some(b']') | None => {
+          return Err(self.error());
+        }
+        Some(_) => self.pos += 1,
+      }
+    }
+    return Ok(&self.str[start..self.pos]);
+  }
+
+  fn field(&mut self, named: bool) -> Result<Field<'a>, ParseError> {
+    let name;
+    if named {
+      name = Some(self.quoted()?);
+    } else {
+      name = None;
+    }
+    let encoding = self.encoding_in(named)?;
+    return Ok(Field {
+      name: name,
+      encoding: encoding,
+    });
+  }
+
+  // In structs and unions with named fields, `@"..."` is ambiguous: the quoted string is either the
+  // object's class name or the next field's name. Like the runtime, it's treated as a class name only
+  // if it's followed by another field name or a closing delimiter.
+  fn is_class_name(&self, named: bool) -> bool {
+    if !named {
+      return true;
+    }
+    let mut lookahead = Parser {
+      str: self.str,
+      pos: self.pos,
+    };
+    if lookahead.quoted().is_err() {
+      return true;
+    }
+    match lookahead.peek() {
+      Some(b'"') | Some(b'}') | Some(b')') | Some(b']') | None => return true,
+      _ => return false,
+    }
+  }
+
+  fn fields(&mut self, close: u8) -> Result<Fields<'a>, ParseError> {
+    let start = self.pos;
+    let named = self.peek() == Some(b'"');
+    while self.peek() != Some(close) {
+      if self.peek().is_none() {
+        return Err(self.error());
+      }
+      self.field(named)?;
+    }
+    return Ok(Fields {
+      str: &self.str[start..self.pos],
+      named: named,
+    });
+  }
+
+  fn aggregate(&mut self, close: u8) -> Result<(&'a str, Option<Fields<'a>>), ParseError> {
+    let name = self.aggregate_name(close)?;
+    let fields;
+    if self.peek() == Some(b'=') {
+      self.pos += 1;
+      fields = Some(self.fields(close)?);
+    } else {
+      fields = None;
+    }
+    self.expect(close)?;
+    return Ok((name, fields));
+  }
+
+  fn nested(&mut self, named: bool) -> Result<Nested<'a>, ParseError> {
+    let start = self.pos;
+    self.encoding_in(named)?;
+    return Ok(Nested(&self.str[start..self.pos]));
+  }
+
+  fn encoding(&mut self) -> Result<Encoding<'a>, ParseError> {
+    return self.encoding_in(false);
+  }
+
+  // `named` is true if this is (or is nested in) a field of a struct or union with named fields.
+  fn encoding_in(&mut self, named: bool) -> Result<Encoding<'a>, ParseError> {
+    let start = self.pos;
+
+    let mut qualifiers = Qualifiers::default();
+    while let Some(qualifier) = self.peek().and_then(Qualifier::from_byte) {
+      qualifiers.insert(qualifier);
+      self.pos += 1;
+    }
+
+    let byte = self.peek().ok_or_else(|| self.error())?;
+    self.pos += 1;
+    let kind = match byte {
+      b'c' => Kind::Char,
+      b's' => Kind::Short,
+      b'i' => Kind::Int,
+      b'l' => Kind::Long,
+      b'q' => Kind::LongLong,
+      b't' => Kind::Int128,
+      b'C' => Kind::UnsignedChar,
+      b'S' => Kind::UnsignedShort,
+      b'I' => Kind::UnsignedInt,
+      b'L' => Kind::UnsignedLong,
+      b'Q' => Kind::UnsignedLongLong,
+      b'T' => Kind::UnsignedInt128,
+      b'f' => Kind::Float,
+      b'd' => Kind::Double,
+      b'D' => Kind::LongDouble,
+      b'B' => Kind::Bool,
+      b'v' => Kind::Void,
+      b'*' => Kind::CString,
+      b'#' => Kind::Class,
+      b':' => Kind::Sel,
+      b'?' => Kind::Unknown,
+      b'@' => match self.peek() {
+        Some(b'?') => {
+          self.pos += 1;
+          Kind::Block
+        }
+        Some(b'"') if self.is_class_name(named) => Kind::Object(Some(self.quoted()?)),
+        _ => Kind::Object(None),
+      },
+      b'b' => Kind::BitField(self.number().ok_or_else(|| self.error())?),
+      b'^' => Kind::Pointer(self.nested(named)?),
+      b'[' => {
+        let count = self.number().ok_or_else(|| self.error())?;
+        let element = self.nested(named)?;
+        self.expect(b']')?;
+        Kind::Array(count, element)
+      }
+      b'{' => {
+        let (name, fields) = self.aggregate(b'}')?;
+        Kind::Struct(name, fields)
+      }
+      b'(' => {
+        let (name, fields) = self.aggregate(b')')?;
+        Kind::Union(name, fields)
+      }
+      _ => {
+        self.pos -= 1;
+        return Err(self.error());
+      }
+    };
+
+    return Ok(Encoding {
+      qualifiers: qualifiers,
+      kind: kind,
+      str: &self.str[start..self.pos],
+    });
+  }
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+  return (value + align - 1) / align * align;
+}
+
+impl<'a> Encoding<'a> {
+  pub fn qualifiers(&self) -> Qualifiers {
+    return self.qualifiers;
+  }
+
+  pub fn kind(&self) -> Kind<'a> {
+    return self.kind;
+  }
+
+  /// The exact substring this encoding was parsed from.
+  pub fn as_str(&self) -> &'a str {
+    return self.str;
+  }
+
+  /// The size of the type on the current target, or `None` if it can't be determined (e.g. `void`,
+  /// `?`, bitfields, and structs or unions whose fields are omitted or contain bitfields).
+  pub fn size(&self) -> Option<usize> {
+    return self.layout().map(|(size, _)| size);
+  }
+
+  /// The alignment of the type on the current target, or `None` if it can't be determined. See
+  /// [`Encoding::size`].
+  pub fn align(&self) -> Option<usize> {
+    return self.layout().map(|(_, align)| align);
+  }
+
+  fn layout(&self) -> Option<(usize, usize)> {
+    fn of<T>() -> Option<(usize, usize)> {
+      return Some((core::mem::size_of::<T>(), core::mem::align_of::<T>()));
+    }
+
+    match self.kind {
+      Kind::Char | Kind::UnsignedChar => return of::<libc::c_char>(),
+      Kind::Short | Kind::UnsignedShort => return of::<libc::c_short>(),
+      Kind::Int | Kind::UnsignedInt => return of::<libc::c_int>(),
+      // `l` is treated as a 32-bit quantity on 64-bit targets.
+      Kind::Long | Kind::UnsignedLong => return of::<libc::c_int>(),
+      Kind::LongLong | Kind::UnsignedLongLong => return of::<libc::c_longlong>(),
+      Kind::Int128 | Kind::UnsignedInt128 => return Some((16, 16)),
+      Kind::Float => return of::<libc::c_float>(),
+      Kind::Double => return of::<libc::c_double>(),
+      #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+      Kind::LongDouble => return Some((16, 16)),
+      #[cfg(not(any(target_arch = "x86_64", target_arch = "x86")))]
+      Kind::LongDouble => return of::<libc::c_double>(),
+      Kind::Bool => return of::<bool>(),
+      Kind::Void | Kind::Unknown | Kind::BitField(_) => return None,
+      Kind::CString
+      | Kind::Class
+      | Kind::Sel
+      | Kind::Object(_)
+      | Kind::Block
+      | Kind::Pointer(_) => return of::<*const u8>(),
+      Kind::Array(count, element) => {
+        let (size, align) = element.get().layout()?;
+        return Some((size.checked_mul(count)?, align));
+      }
+      Kind::Struct(_, fields) => {
+        let mut size = 0;
+        let mut max_align = 1;
+        for field in fields? {
+          let (field_size, align) = field.encoding.layout()?;
+          size = round_up(size, align).checked_add(field_size)?;
+          max_align = core::cmp::max(max_align, align);
+        }
+        return Some((round_up(size, max_align), max_align));
+      }
+      Kind::Union(_, fields) => {
+        let mut size = 0;
+        let mut max_align = 1;
+        for field in fields? {
+          let (field_size, align) = field.encoding.layout()?;
+          size = core::cmp::max(size, field_size);
+          max_align = core::cmp::max(max_align, align);
+        }
+        return Some((round_up(size, max_align), max_align));
+      }
+    }
+  }
+}
+
+// Writes the encoding in canonical form (i.e. with qualifiers in clang's order). This is the same as
+// the parsed string for any encoding clang generates.
+impl<'a> fmt::Display for Encoding<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn fields(f: &mut fmt::Formatter, fields: Option<Fields>) -> fmt::Result {
+      if let Some(fields) = fields {
+        f.write_str("=")?;
+        for field in fields {
+          if let Some(name) = field.name {
+            write!(f, "\"{}\"", name)?;
+          }
+          write!(f, "{}", field.encoding)?;
+        }
+      }
+      return Ok(());
+    }
+
+    write!(f, "{}", self.qualifiers)?;
+    let code = match self.kind {
+      Kind::Char => "c",
+      Kind::Short => "s",
+      Kind::Int => "i",
+      Kind::Long => "l",
+      Kind::LongLong => "q",
+      Kind::Int128 => "t",
+      Kind::UnsignedChar => "C",
+      Kind::UnsignedShort => "S",
+      Kind::UnsignedInt => "I",
+      Kind::UnsignedLong => "L",
+      Kind::UnsignedLongLong => "Q",
+      Kind::UnsignedInt128 => "T",
+      Kind::Float => "f",
+      Kind::Double => "d",
+      Kind::LongDouble => "D",
+      Kind::Bool => "B",
+      Kind::Void => "v",
+      Kind::CString => "*",
+      Kind::Class => "#",
+      Kind::Sel => ":",
+      Kind::Unknown => "?",
+      Kind::Block => "@?",
+      Kind::Object(None) => "@",
+      Kind::Object(Some(name)) => return write!(f, "@\"{}\"", name),
+      Kind::BitField(width) => return write!(f, "b{}", width),
+      Kind::Pointer(pointee) => return write!(f, "^{}", pointee.get()),
+      Kind::Array(count, element) => return write!(f, "[{}{}]", count, element.get()),
+      Kind::Struct(name, struct_fields) => {
+        write!(f, "{{{}", name)?;
+        fields(f, struct_fields)?;
+        return f.write_str("}");
+      }
+      Kind::Union(name, union_fields) => {
+        write!(f, "({}", name)?;
+        fields(f, union_fields)?;
+        return f.write_str(")");
+      }
+    };
+    return f.write_str(code);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A fixed-size buffer for formatting encodings (since this crate is no_std).
+  struct Buffer {
+    bytes: [u8; 256],
+    len: usize,
+  }
+
+  impl fmt::Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+      let end = self.len + s.len();
+      if end > self.bytes.len() {
+        return Err(fmt::Error);
+      }
+      self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+      self.len = end;
+      return Ok(());
+    }
+  }
+
+  impl Buffer {
+    fn new() -> Buffer {
+      return Buffer {
+        bytes: [0; 256],
+        len: 0,
+      };
+    }
+
+    fn as_str(&self) -> &str {
+      return core::str::from_utf8(&self.bytes[..self.len]).unwrap();
+    }
+  }
+
+  fn assert_displays_as(encoding: &str, expected: &str) {
+    use core::fmt::Write;
+    let parsed = parse(encoding).unwrap_or_else(|e| panic!("`{}`: {}", encoding, e));
+    assert_eq!(parsed.as_str(), encoding);
+    let mut buffer = Buffer::new();
+    write!(buffer, "{}", parsed).unwrap();
+    assert_eq!(buffer.as_str(), expected);
+  }
+
+  fn assert_round_trips(encoding: &str) {
+    assert_displays_as(encoding, encoding);
+  }
+
+  #[test]
+  fn round_trip() {
+    let encodings = [
+      "c", "s", "i", "l", "q", "t", "C", "S", "I", "L", "Q", "T", "f", "d", "D", "B", "v", "*", "#",
+      ":", "?", "@", "@?", "@\"NSString\"", "b1", "b13", "^v", "^^C", "^?", "r*", "r^C", "r^^C",
+      "nO@", "Vv", "rnNoORV@", "[0C]", "[16C]", "[4[4f]]", "[2^{CGPoint=dd}]", "{CGPoint=dd}",
+      "{CGRect={CGPoint=dd}{CGSize=dd}}", "{?=ii}", "{Opaque}", "^{Opaque}", "{Empty=}",
+      "(Value=if^C)", "(?=cs)", "{Bits=b1b7C}", "{Named=\"x\"d\"y\"d}",
+      "{Objects=\"a\"@\"NSString\"\"b\"@\"c\"i}", "{Block=@?^?}", "^{_NSZone}",
+    ];
+    for encoding in encodings.iter() {
+      assert_round_trips(encoding);
+    }
+  }
+
+  // Every primitive, with every qualifier, in every kind of nested position.
+  #[test]
+  fn round_trip_exhaustive() {
+    use core::fmt::Write;
+    let primitives = [
+      "c", "s", "i", "l", "q", "t", "C", "S", "I", "L", "Q", "T", "f", "d", "D", "B", "v", "*", "#",
+      ":", "?", "@", "@?", "@\"NSObject\"", "b7", "{?}", "{S=}", "(U)",
+    ];
+    let containers = [
+      ("", ""),
+      ("^", ""),
+      ("^^", ""),
+      ("[3", "]"),
+      ("[2[1", "]]"),
+      ("{S=", "}"),
+      ("{S=i", "i}"),
+      ("(U=", "c)"),
+      ("{S=\"f\"", "}"),
+      ("{S=\"f\"", "\"g\"i}"),
+    ];
+    for primitive in primitives.iter() {
+      for &(open, close) in containers.iter() {
+        for &(_, qualifier) in [(Qualifier::Const, b'_')].iter().chain(QUALIFIERS.iter()) {
+          let mut buffer = Buffer::new();
+          buffer.write_str(open).unwrap();
+          if qualifier != b'_' {
+            buffer.write_char(qualifier as char).unwrap();
+          }
+          buffer.write_str(primitive).unwrap();
+          buffer.write_str(close).unwrap();
+          assert_round_trips(buffer.as_str());
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn canonical_qualifier_order() {
+    let parsed = parse("Vr^v").unwrap();
+    assert!(parsed.qualifiers().contains(Qualifier::Const));
+    assert!(parsed.qualifiers().contains(Qualifier::Oneway));
+    assert!(!parsed.qualifiers().contains(Qualifier::In));
+    assert_eq!(parsed.as_str(), "Vr^v");
+    assert_displays_as("Vr^v", "rV^v");
+    assert_displays_as("ORnrV@", "rnORV@");
+    assert_displays_as("^Vr^v", "^rV^v");
+  }
+
+  #[test]
+  fn kinds() {
+    assert_eq!(parse("@\"NSString\"").unwrap().kind(), Kind::Object(Some("NSString")));
+    assert_eq!(parse("@?").unwrap().kind(), Kind::Block);
+    assert_eq!(parse("b5").unwrap().kind(), Kind::BitField(5));
+
+    match parse("r^^C").unwrap().kind() {
+      Kind::Pointer(pointee) => {
+        assert_eq!(pointee.as_str(), "^C");
+        assert_eq!(pointee.get().qualifiers(), Qualifiers::default());
+      }
+      kind => panic!("unexpected kind: {:?}", kind),
+    }
+
+    match parse("[12{?=ii}]").unwrap().kind() {
+      Kind::Array(count, element) => {
+        assert_eq!(count, 12);
+        assert_eq!(element.as_str(), "{?=ii}");
+      }
+      kind => panic!("unexpected kind: {:?}", kind),
+    }
+
+    match parse("{Objects=\"a\"@\"NSString\"\"b\"@\"c\"i}").unwrap().kind() {
+      Kind::Struct(name, fields) => {
+        assert_eq!(name, "Objects");
+        let mut fields = fields.unwrap();
+        let a = fields.next().unwrap();
+        assert_eq!(a.name, Some("a"));
+        assert_eq!(a.encoding.kind(), Kind::Object(Some("NSString")));
+        let b = fields.next().unwrap();
+        assert_eq!(b.name, Some("b"));
+        assert_eq!(b.encoding.kind(), Kind::Object(None));
+        let c = fields.next().unwrap();
+        assert_eq!(c.name, Some("c"));
+        assert_eq!(c.encoding.kind(), Kind::Int);
+        assert_eq!(fields.next(), None);
+      }
+      kind => panic!("unexpected kind: {:?}", kind),
+    }
+
+    match parse("{Opaque}").unwrap().kind() {
+      Kind::Struct(name, fields) => {
+        assert_eq!(name, "Opaque");
+        assert_eq!(fields, None);
+      }
+      kind => panic!("unexpected kind: {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn layout() {
+    let ptr = core::mem::size_of::<*const u8>();
+    assert_eq!(parse("c").unwrap().size(), Some(1));
+    assert_eq!(parse("s").unwrap().size(), Some(2));
+    assert_eq!(parse("i").unwrap().size(), Some(4));
+    assert_eq!(parse("l").unwrap().size(), Some(4));
+    assert_eq!(parse("q").unwrap().size(), Some(8));
+    assert_eq!(parse("T").unwrap().size(), Some(16));
+    assert_eq!(parse("f").unwrap().size(), Some(4));
+    assert_eq!(parse("d").unwrap().size(), Some(8));
+    assert_eq!(parse("B").unwrap().size(), Some(1));
+    assert_eq!(parse("v").unwrap().size(), None);
+    assert_eq!(parse("?").unwrap().size(), None);
+    assert_eq!(parse("b3").unwrap().size(), None);
+    for encoding in ["*", "#", ":", "@", "@?", "@\"NSString\"", "^v", "r^{Opaque}"].iter() {
+      assert_eq!(parse(encoding).unwrap().size(), Some(ptr));
+      assert_eq!(parse(encoding).unwrap().align(), Some(ptr));
+    }
+
+    assert_eq!(parse("[16C]").unwrap().size(), Some(16));
+    assert_eq!(parse("[0d]").unwrap().size(), Some(0));
+    assert_eq!(parse("[0d]").unwrap().align(), Some(8));
+    assert_eq!(parse("{CGPoint=dd}").unwrap().size(), Some(16));
+    assert_eq!(parse("{CGRect={CGPoint=dd}{CGSize=dd}}").unwrap().size(), Some(32));
+    assert_eq!(parse("{?=cic}").unwrap().size(), Some(12));
+    assert_eq!(parse("{?=cic}").unwrap().align(), Some(4));
+    assert_eq!(parse("{?=cq}").unwrap().size(), Some(16));
+    assert_eq!(parse("{Empty=}").unwrap().size(), Some(0));
+    assert_eq!(parse("{Opaque}").unwrap().size(), None);
+    assert_eq!(parse("{Bits=b1i}").unwrap().size(), None);
+    assert_eq!(parse("(?=cq)").unwrap().size(), Some(8));
+    assert_eq!(parse("(?=[3c]s)").unwrap().size(), Some(4));
+    assert_eq!(parse("(?=[3c]s)").unwrap().align(), Some(2));
+  }
+
+  #[test]
+  fn malformed() {
+    assert_eq!(parse(""), Err(ParseError { position: 0 }));
+    assert_eq!(parse("r"), Err(ParseError { position: 1 }));
+    assert_eq!(parse("x"), Err(ParseError { position: 0 }));
+    assert_eq!(parse("ii"), Err(ParseError { position: 1 }));
+    assert_eq!(parse("^"), Err(ParseError { position: 1 }));
+    assert_eq!(parse("[C]"), Err(ParseError { position: 1 }));
+    assert_eq!(parse("[4C"), Err(ParseError { position: 3 }));
+    assert_eq!(parse("{CGPoint=dd"), Err(ParseError { position: 11 }));
+    assert_eq!(parse("{CGPoint"), Err(ParseError { position: 8 }));
+    assert_eq!(parse("(?=i}"), Err(ParseError { position: 4 }));
+    assert_eq!(parse("@\"NSString"), Err(ParseError { position: 2 }));
+    assert_eq!(parse("b"), Err(ParseError { position: 1 }));
+  }
+
+  #[test]
+  fn method() {
+    let mut types = parse_method("v24@0:8@16");
+    assert_eq!(types.next().unwrap().unwrap().kind(), Kind::Void);
+    assert_eq!(types.next().unwrap().unwrap().kind(), Kind::Object(None));
+    assert_eq!(types.next().unwrap().unwrap().kind(), Kind::Sel);
+    assert_eq!(types.next().unwrap().unwrap().kind(), Kind::Object(None));
+    assert!(types.next().is_none());
+
+    let mut types = parse_method("{CGRect={CGPoint=dd}{CGSize=dd}}16@0:8");
+    assert_eq!(types.next().unwrap().unwrap().size(), Some(32));
+    assert_eq!(types.clone().count(), 2);
+
+    let mut types = parse_method("vx");
+    assert_eq!(types.next().unwrap().unwrap().kind(), Kind::Void);
+    assert_eq!(types.next().unwrap(), Err(ParseError { position: 1 }));
+    assert!(types.next().is_none());
+  }
+}
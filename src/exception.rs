@@ -36,11 +36,16 @@ use crate::runtime;
 
 pub fn throw<T>(exception: T) -> !
 where
-  T: Into<arc::Auto<runtime::Id>>,
+  T: Into<arc::Strong<runtime::Id>>,
 {
-  unsafe { runtime::objc_exception_throw(exception.into()) };
+  // Like `@throw`, the exception is autoreleased so that it outlives the throw.
+  unsafe { runtime::objc_exception_throw(runtime::objc_autorelease(Some(exception.into()))) };
 }
 
+#[cfg(any(
+  all(target_vendor = "apple", not(any(feature = "runtime-gnustep", feature = "mock-runtime"))),
+  all(not(target_vendor = "apple"), any(feature = "runtime-gnustep", feature = "mock-runtime")),
+))]
 extern "C" {
   fn __objrs_catch_exception(
     payload: *mut libc::c_void,
@@ -49,8 +54,12 @@ extern "C" {
   ) -> bool;
 }
 
+#[cfg(any(
+  all(target_vendor = "apple", not(any(feature = "runtime-gnustep", feature = "mock-runtime"))),
+  all(not(target_vendor = "apple"), any(feature = "runtime-gnustep", feature = "mock-runtime")),
+))]
 #[inline]
-pub fn catch_exception<F: FnOnce() -> R, R>(f: F) -> Result<R, arc::Auto<runtime::Id>> {
+pub fn catch_exception<F: FnOnce() -> R, R>(f: F) -> Result<R, arc::Strong<runtime::Id>> {
   union FunctionAndRet<F, R> {
     f: core::mem::ManuallyDrop<F>,
     ret: core::mem::ManuallyDrop<R>,
@@ -97,12 +106,22 @@ macro_rules! catch_exception {
 //     fn(data);
 //     return 0;
 //   } @catch (id value) {
-//     *exception = value;
+//     *exception = objc_retain(value);
 //     return 1;
 //   }
 // }
 
-#[cfg(target_arch = "x86_64")]
+// __objrs_catch_exception is implemented for x86_64 and aarch64, for both Mach-O (Apple's runtime,
+// with ___objc_personality_v0) and ELF (libobjc2 and the mock runtime, with
+// __gnustep_objc_personality_v0). On ELF, `@catch (id)` is encoded like clang's gnustep-2.0 ABI: the
+// catch clause's type info is the address of libobjc2's __objc_id_type_info. The personality and
+// type info are referenced indirectly (through DW.ref and DW.stub pointers) so the code is position
+// independent.
+#[cfg(all(
+  target_arch = "x86_64",
+  target_vendor = "apple",
+  not(any(feature = "runtime-gnustep", feature = "mock-runtime"))
+))]
 global_asm! {r#"
   .section  __TEXT,__text,regular,pure_instructions
   .globl  ___objrs_catch_exception
@@ -143,7 +162,7 @@ Ltmp2:
   callq  _objc_begin_catch
   movq  %rax, %rbp
   movq  %rbp, %rdi
-  callq  _objc_retainAutorelease
+  callq  _objc_retain
   movq  %rbp, (%rbx)
   callq  _objc_end_catch
   movl  $1, %ebp
@@ -180,7 +199,11 @@ Lset4 = Lfunc_end0-Ltmp1                ##   Call between Ltmp1 and Lfunc_end0
   .p2align  2
 "#}
 
-#[cfg(target_arch = "aarch64")]
+#[cfg(all(
+  target_arch = "aarch64",
+  target_vendor = "apple",
+  not(any(feature = "runtime-gnustep", feature = "mock-runtime"))
+))]
 global_asm! {r#"
   .section  __TEXT,__text,regular,pure_instructions
   .ios_version_min 10, 0
@@ -212,7 +235,7 @@ LBB0_2:
 Ltmp2:
   bl  _objc_begin_catch
   mov  x20, x0
-  bl  _objc_retainAutorelease
+  bl  _objc_retain
   str  x20, [x19]
   bl  _objc_end_catch
   orr  w0, wzr, #0x1
@@ -251,3 +274,240 @@ Ltmp3:                                  ; TypeInfo 1
 Lttbase0:
   .p2align  2
 "#}
+
+#[cfg(all(
+  target_arch = "x86_64",
+  not(target_vendor = "apple"),
+  any(feature = "runtime-gnustep", feature = "mock-runtime")
+))]
+global_asm! {r#"
+  .text
+  .globl  __objrs_catch_exception
+  .p2align  4, 0x90
+  .type  __objrs_catch_exception,@function
+__objrs_catch_exception:                # @__objrs_catch_exception
+.L__objrs_catch_begin:
+  .cfi_startproc
+  .cfi_personality 155, DW.ref.__gnustep_objc_personality_v0
+  .cfi_lsda 27, .L__objrs_catch_lsda
+# %bb.0:
+  pushq  %rbp
+  .cfi_def_cfa_offset 16
+  pushq  %rbx
+  .cfi_def_cfa_offset 24
+  pushq  %rax
+  .cfi_def_cfa_offset 32
+  .cfi_offset %rbx, -24
+  .cfi_offset %rbp, -16
+  movq  %rdx, %rbx
+  xorl  %ebp, %ebp
+.L__objrs_catch_tmp0:
+  callq  *%rsi
+.L__objrs_catch_tmp1:
+.L__objrs_catch_ret:
+  movl  %ebp, %eax
+  addq  $8, %rsp
+  popq  %rbx
+  popq  %rbp
+  retq
+.L__objrs_catch_tmp2:
+  movq  %rax, %rdi
+  callq  objc_begin_catch@PLT
+  movq  %rax, %rbp
+  movq  %rbp, %rdi
+  callq  objc_retain@PLT
+  movq  %rbp, (%rbx)
+  callq  objc_end_catch@PLT
+  movl  $1, %ebp
+  jmp  .L__objrs_catch_ret
+.L__objrs_catch_end:
+  .size  __objrs_catch_exception, .L__objrs_catch_end-__objrs_catch_exception
+  .cfi_endproc
+  .section  .gcc_except_table,"a",@progbits
+  .p2align  2
+.L__objrs_catch_lsda:
+  .byte  255                     # @LPStart Encoding = omit
+  .byte  155                     # @TType Encoding = indirect pcrel sdata4
+  .uleb128 .L__objrs_catch_ttbase-.L__objrs_catch_ttbaseref
+.L__objrs_catch_ttbaseref:
+  .byte  1                       # Call site Encoding = uleb128
+  .uleb128 .L__objrs_catch_cst_end-.L__objrs_catch_cst_begin
+.L__objrs_catch_cst_begin:
+  .uleb128 .L__objrs_catch_tmp0-.L__objrs_catch_begin # >> Call Site 1 <<
+  .uleb128 .L__objrs_catch_tmp1-.L__objrs_catch_tmp0  #   Call between tmp0 and tmp1
+  .uleb128 .L__objrs_catch_tmp2-.L__objrs_catch_begin #     jumps to tmp2
+  .byte  1                       #   On action: 1
+  .uleb128 .L__objrs_catch_tmp1-.L__objrs_catch_begin # >> Call Site 2 <<
+  .uleb128 .L__objrs_catch_end-.L__objrs_catch_tmp1   #   Call between tmp1 and end
+  .byte  0                       #     has no landing pad
+  .byte  0                       #   On action: cleanup
+.L__objrs_catch_cst_end:
+  .byte  1                       # >> Action Record 1 <<
+                                        #   Catch TypeInfo 1
+  .byte  0                       #   No further actions
+  .p2align  2
+                                        # >> Catch TypeInfos <<
+.L__objrs_catch_typeinfo:                # TypeInfo 1
+  .long  .L__objrs_objc_id_type_info.DW.stub-.L__objrs_catch_typeinfo
+.L__objrs_catch_ttbase:
+  .p2align  2
+
+  .hidden  DW.ref.__gnustep_objc_personality_v0
+  .weak  DW.ref.__gnustep_objc_personality_v0
+  .section  .data.DW.ref.__gnustep_objc_personality_v0,"aGw",@progbits,DW.ref.__gnustep_objc_personality_v0,comdat
+  .p2align  3
+  .type  DW.ref.__gnustep_objc_personality_v0,@object
+  .size  DW.ref.__gnustep_objc_personality_v0, 8
+DW.ref.__gnustep_objc_personality_v0:
+  .quad  __gnustep_objc_personality_v0
+
+  .section  .data.rel.ro,"aw",@progbits
+  .p2align  3
+.L__objrs_objc_id_type_info.DW.stub:
+  .quad  __objc_id_type_info
+  .text
+"#}
+
+#[cfg(all(
+  target_arch = "aarch64",
+  not(target_vendor = "apple"),
+  any(feature = "runtime-gnustep", feature = "mock-runtime")
+))]
+global_asm! {r#"
+  .text
+  .globl  __objrs_catch_exception
+  .p2align  2
+  .type  __objrs_catch_exception,@function
+__objrs_catch_exception:                // @__objrs_catch_exception
+.L__objrs_catch_begin:
+  .cfi_startproc
+  .cfi_personality 155, DW.ref.__gnustep_objc_personality_v0
+  .cfi_lsda 27, .L__objrs_catch_lsda
+// %bb.0:
+  stp  x20, x19, [sp, #-32]!   // 16-byte Folded Spill
+  stp  x29, x30, [sp, #16]     // 16-byte Folded Spill
+  .cfi_def_cfa_offset 32
+  .cfi_offset w30, -8
+  .cfi_offset w29, -16
+  .cfi_offset w19, -24
+  .cfi_offset w20, -32
+  mov  x19, x2
+.L__objrs_catch_tmp0:
+  blr  x1
+.L__objrs_catch_tmp1:
+// %bb.1:
+  mov  w0, #0
+  ldp  x29, x30, [sp, #16]     // 16-byte Folded Reload
+  ldp  x20, x19, [sp], #32     // 16-byte Folded Reload
+  ret
+.L__objrs_catch_tmp2:
+  bl  objc_begin_catch
+  mov  x20, x0
+  bl  objc_retain
+  str  x20, [x19]
+  bl  objc_end_catch
+  mov  w0, #1
+  ldp  x29, x30, [sp, #16]     // 16-byte Folded Reload
+  ldp  x20, x19, [sp], #32     // 16-byte Folded Reload
+  ret
+.L__objrs_catch_end:
+  .size  __objrs_catch_exception, .L__objrs_catch_end-__objrs_catch_exception
+  .cfi_endproc
+  .section  .gcc_except_table,"a",@progbits
+  .p2align  2
+.L__objrs_catch_lsda:
+  .byte  255                     // @LPStart Encoding = omit
+  .byte  155                     // @TType Encoding = indirect pcrel sdata4
+  .uleb128 .L__objrs_catch_ttbase-.L__objrs_catch_ttbaseref
+.L__objrs_catch_ttbaseref:
+  .byte  1                       // Call site Encoding = uleb128
+  .uleb128 .L__objrs_catch_cst_end-.L__objrs_catch_cst_begin
+.L__objrs_catch_cst_begin:
+  .uleb128 .L__objrs_catch_tmp0-.L__objrs_catch_begin // >> Call Site 1 <<
+  .uleb128 .L__objrs_catch_tmp1-.L__objrs_catch_tmp0  //   Call between tmp0 and tmp1
+  .uleb128 .L__objrs_catch_tmp2-.L__objrs_catch_begin //     jumps to tmp2
+  .byte  1                       //   On action: 1
+  .uleb128 .L__objrs_catch_tmp1-.L__objrs_catch_begin // >> Call Site 2 <<
+  .uleb128 .L__objrs_catch_end-.L__objrs_catch_tmp1   //   Call between tmp1 and end
+  .byte  0                       //     has no landing pad
+  .byte  0                       //   On action: cleanup
+.L__objrs_catch_cst_end:
+  .byte  1                       // >> Action Record 1 <<
+                                        //   Catch TypeInfo 1
+  .byte  0                       //   No further actions
+  .p2align  2
+                                        // >> Catch TypeInfos <<
+.L__objrs_catch_typeinfo:                // TypeInfo 1
+  .word  .L__objrs_objc_id_type_info.DW.stub-.L__objrs_catch_typeinfo
+.L__objrs_catch_ttbase:
+  .p2align  2
+
+  .hidden  DW.ref.__gnustep_objc_personality_v0
+  .weak  DW.ref.__gnustep_objc_personality_v0
+  .section  .data.DW.ref.__gnustep_objc_personality_v0,"aGw",@progbits,DW.ref.__gnustep_objc_personality_v0,comdat
+  .p2align  3
+  .type  DW.ref.__gnustep_objc_personality_v0,@object
+  .size  DW.ref.__gnustep_objc_personality_v0, 8
+DW.ref.__gnustep_objc_personality_v0:
+  .quad  __gnustep_objc_personality_v0
+
+  .section  .data.rel.ro,"aw",@progbits
+  .p2align  3
+.L__objrs_objc_id_type_info.DW.stub:
+  .quad  __objc_id_type_info
+  .text
+"#}
+
+#[cfg(all(
+  test,
+  feature = "mock-runtime",
+  any(target_arch = "x86_64", target_arch = "aarch64"),
+  not(target_vendor = "apple"),
+))]
+mod tests {
+  extern crate std;
+
+  use super::*;
+  use crate::mock::{add_method, new_object, register_class, retain_count};
+  use crate::runtime::{Id, Sel};
+  use crate::{autoreleasepool, selector};
+
+  #[test]
+  fn catch_exception() {
+    let class = register_class("MockException", None);
+    let object = new_object(class);
+
+    let result = super::catch_exception(|| 42);
+    assert_eq!(result.ok(), Some(42));
+
+    let caught = autoreleasepool(|_| {
+      let result = super::catch_exception(|| -> i32 { throw(object.clone()) });
+      return result.err().expect("the exception wasn't caught");
+    });
+    assert!(core::ptr::eq(&*caught, &*object));
+    assert_eq!(retain_count(&object), 2);
+
+    // Exceptions thrown from Objective-C methods are caught too.
+    #[unwind(allowed)]
+    extern "C" fn raise(this: &Id, _sel: &Sel) {
+      unsafe { runtime::objc_exception_throw(this as *const Id as *mut Id) };
+    }
+    add_method(class, selector!("raise"), raise as *const (), "v16@0:8");
+    let result = super::catch_exception(|| unsafe { object.send::<_, ()>(selector!("raise"), ()) });
+    assert!(core::ptr::eq(&*result.err().unwrap(), &*object));
+
+    // Rust panics aren't caught, and unwind through catch_exception.
+    let result = std::panic::catch_unwind(|| {
+      let _ = super::catch_exception(|| panic!("not an Objective-C exception"));
+    });
+    assert!(result.is_err());
+
+    // Exceptions thrown outside of catch_exception panic (like objc_exception_throw's default
+    // uncaught exception handler, which terminates the process).
+    let result =
+      std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| throw(new_object(class))));
+    let payload = result.unwrap_err();
+    let message = payload.downcast_ref::<std::string::String>().unwrap();
+    assert!(message.starts_with("Objective-C exception thrown"));
+  }
+}
@@ -0,0 +1,287 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Safe wrappers around the runtime's introspection functions.
+//
+// Classes, methods, ivars, properties, and protocols are never deallocated once they've been
+// registered with the runtime, so they're all exposed as `&'static` references. The lists returned
+// by the runtime's copy*List functions are freed when the iterator is dropped.
+//
+// See https://github.com/opensource-apple/objc4/blob/master/runtime/runtime.h
+
+extern crate core;
+extern crate libc;
+
+use crate::encoding::{self, Encoding, MethodTypes, ParseError};
+use crate::runtime::{self, Class, Id, Imp, Ivar, Method, Property, Protocol, Sel};
+use crate::CStr;
+use core::ptr::NonNull;
+
+/// An iterator over a list that the runtime copied (e.g. with `class_copyMethodList`). The list is
+/// freed when the iterator is dropped.
+pub struct CopiedList<T: ?Sized + 'static> {
+  list: *mut &'static T,
+  len: usize,
+  index: usize,
+}
+
+impl<T: ?Sized + 'static> CopiedList<T> {
+  // Takes ownership of a list returned by one of the runtime's copy*List functions, which return
+  // NULL (and a count of zero) if the list is empty.
+  unsafe fn new(copy: impl FnOnce(*mut libc::c_uint) -> *mut &'static T) -> CopiedList<T> {
+    let mut len: libc::c_uint = 0;
+    let list = copy(&mut len);
+    return CopiedList {
+      list: list,
+      len: if list.is_null() { 0 } else { len as usize },
+      index: 0,
+    };
+  }
+}
+
+impl<T: ?Sized + 'static> Iterator for CopiedList<T> {
+  type Item = &'static T;
+
+  #[inline]
+  fn next(&mut self) -> Option<&'static T> {
+    if self.index >= self.len {
+      return None;
+    }
+    let item = unsafe { *self.list.add(self.index) };
+    self.index += 1;
+    return Some(item);
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let remaining = self.len - self.index;
+    return (remaining, Some(remaining));
+  }
+}
+
+impl<T: ?Sized + 'static> ExactSizeIterator for CopiedList<T> {}
+
+impl<T: ?Sized + 'static> core::ops::Drop for CopiedList<T> {
+  #[inline]
+  fn drop(&mut self) {
+    unsafe { libc::free(self.list as *mut libc::c_void) };
+  }
+}
+
+// Converts a type encoding (or property attributes) from the runtime into a string for parsing. A
+// missing encoding (or one that isn't valid UTF-8) is reported as malformed.
+fn encoding_str(encoding: Option<&'static CStr>) -> Result<&'static str, ParseError> {
+  return encoding.map_or(Ok(""), CStr::to_str).map_err(|error| ParseError {
+    position: error.valid_up_to(),
+  });
+}
+
+impl Class {
+  /// Looks up a class by name. Returns `None` if no class with that name has been registered with
+  /// the runtime.
+  #[inline]
+  pub fn get(name: &CStr) -> Option<&'static Class> {
+    return unsafe { runtime::objc_getClass(name).as_ref() };
+  }
+
+  /// All of the classes registered with the runtime.
+  pub fn all() -> CopiedList<Class> {
+    return unsafe { CopiedList::new(|len| runtime::objc_copyClassList(len)) };
+  }
+
+  #[inline]
+  pub fn name(&self) -> &'static CStr {
+    return unsafe { runtime::class_getName(self) }.expect("BUG: classes always have a name");
+  }
+
+  /// The class's superclass, or `None` if it's a root class.
+  #[inline]
+  pub fn superclass(&self) -> Option<&'static Class> {
+    return unsafe { runtime::class_getSuperclass(self as *const Class as *mut Class).as_ref() };
+  }
+
+  /// The class's metaclass, which holds its class methods.
+  #[inline]
+  pub fn metaclass(&self) -> &'static Class {
+    return unsafe { &*runtime::object_getClass(self as *const Class as *mut Id) };
+  }
+
+  /// The class's instance methods (but not its superclasses' methods). Use `metaclass().methods()`
+  /// for class methods.
+  pub fn methods(&self) -> CopiedList<Method> {
+    return unsafe { CopiedList::new(|len| runtime::class_copyMethodList(self, len)) };
+  }
+
+  /// The class's ivars (but not its superclasses' ivars).
+  pub fn ivars(&self) -> CopiedList<Ivar> {
+    return unsafe { CopiedList::new(|len| runtime::class_copyIvarList(self, len)) };
+  }
+
+  /// The class's properties (but not its superclasses' properties).
+  pub fn properties(&self) -> CopiedList<Property> {
+    return unsafe { CopiedList::new(|len| runtime::class_copyPropertyList(self, len)) };
+  }
+
+  /// The protocols the class adopts (but not those adopted by its superclasses).
+  pub fn protocols(&self) -> CopiedList<Protocol> {
+    return unsafe { CopiedList::new(|len| runtime::class_copyProtocolList(self, len)) };
+  }
+}
+
+impl Method {
+  #[inline]
+  pub fn name(&self) -> &'static Sel {
+    return unsafe { runtime::method_getName(NonNull::from(self)) };
+  }
+
+  #[inline]
+  pub fn implementation(&self) -> &'static Imp {
+    return unsafe { runtime::method_getImplementation(NonNull::from(self)) };
+  }
+
+  /// The method's type encoding, parsed into the return type followed by each argument's type
+  /// (including the receiver and selector). Returns an error if the encoding isn't valid UTF-8.
+  pub fn type_encoding(&self) -> Result<MethodTypes<'static>, ParseError> {
+    let encoding = unsafe { runtime::method_getTypeEncoding(NonNull::from(self)) };
+    return Ok(encoding::parse_method(encoding_str(encoding)?));
+  }
+}
+
+impl Ivar {
+  /// The ivar's name, or `None` if it's anonymous (e.g. an anonymous bitfield).
+  #[inline]
+  pub fn name(&self) -> Option<&'static CStr> {
+    return unsafe { runtime::ivar_getName(self) };
+  }
+
+  pub fn type_encoding(&self) -> Result<Encoding<'static>, ParseError> {
+    return encoding::parse(encoding_str(unsafe { runtime::ivar_getTypeEncoding(self) })?);
+  }
+
+  /// The ivar's offset (in bytes) from the start of the object.
+  #[inline]
+  pub fn offset(&self) -> isize {
+    return unsafe { runtime::ivar_getOffset(self) };
+  }
+}
+
+impl Property {
+  #[inline]
+  pub fn name(&self) -> &'static CStr {
+    return unsafe { runtime::property_getName(self) };
+  }
+
+  /// The property's attribute string (e.g. `T@"NSString",C,N,V_name`). See
+  /// https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjCRuntimeGuide/Articles/ocrtPropertyIntrospection.html
+  #[inline]
+  pub fn attributes(&self) -> Option<&'static CStr> {
+    return unsafe { runtime::property_getAttributes(self) };
+  }
+
+  /// The property's type, parsed from the `T` attribute (which is always the first attribute).
+  pub fn type_encoding(&self) -> Result<Encoding<'static>, ParseError> {
+    let attributes = encoding_str(self.attributes())?;
+    if !attributes.starts_with('T') {
+      return Err(ParseError {
+        position: 0,
+      });
+    }
+    let (encoding, rest) = match encoding::parse_prefix(&attributes[1..]) {
+      Ok(parsed) => parsed,
+      Err(error) => {
+        return Err(ParseError {
+          position: error.position + 1,
+        })
+      }
+    };
+    if !rest.is_empty() && !rest.starts_with(',') {
+      return Err(ParseError {
+        position: attributes.len() - rest.len(),
+      });
+    }
+    return Ok(encoding);
+  }
+}
+
+impl Protocol {
+  /// Looks up a protocol by name. Returns `None` if no protocol with that name has been registered
+  /// with the runtime.
+  #[inline]
+  pub fn get(name: &CStr) -> Option<&'static Protocol> {
+    return unsafe { runtime::objc_getProtocol(name) };
+  }
+
+  #[inline]
+  pub fn name(&self) -> &'static CStr {
+    return unsafe { runtime::protocol_getName(self) };
+  }
+}
+
+#[cfg(all(test, feature = "mock-runtime"))]
+mod tests {
+  extern crate alloc;
+
+  use super::*;
+  use crate::mock::{add_property, register_class, register_protocol};
+  use crate::{selector, ClassBuilder};
+  use alloc::vec::Vec;
+
+  #[test]
+  fn introspection() {
+    extern "C" fn answer(_this: &Id, _sel: &Sel) -> i32 {
+      return 42;
+    }
+
+    let root = register_class("MockIntrospectionRoot", None);
+    let protocol = register_protocol("MockIntrospectionProtocol");
+    let name = unsafe { CStr::new("MockIntrospection\0") };
+    let mut builder = ClassBuilder::new(name, Some(root)).unwrap();
+    builder.add_ivar::<u8>(unsafe { CStr::new("flag\0") });
+    builder.add_ivar::<f64>(unsafe { CStr::new("value\0") });
+    let answer = answer as extern "C" fn(&Id, &Sel) -> i32;
+    builder.add_method(selector!("answer"), answer);
+    builder.add_protocol(protocol);
+    let class = builder.register().class();
+    add_property(class, "value", "Td,N,Vvalue");
+
+    assert_eq!(class.name().to_str(), Ok("MockIntrospection"));
+    assert!(core::ptr::eq(Class::get(name).unwrap(), class));
+    assert!(core::ptr::eq(class.superclass().unwrap(), root));
+    assert!(root.superclass().is_none());
+    assert!(Class::all().any(|registered| core::ptr::eq(registered, class)));
+
+    let methods: Vec<&Method> = class.methods().collect();
+    assert_eq!(methods.len(), 1);
+    assert_eq!(methods[0].name().name().to_str(), Ok("answer"));
+    assert_eq!(
+      methods[0].implementation() as *const runtime::Imp as *const (),
+      answer as *const ()
+    );
+    let types: Vec<encoding::Kind> =
+      methods[0].type_encoding().unwrap().map(|encoding| encoding.unwrap().kind()).collect();
+    assert_eq!(types, [encoding::Kind::Int, encoding::Kind::Object(None), encoding::Kind::Sel]);
+    assert_eq!(class.metaclass().methods().len(), 0);
+
+    let ivars: Vec<&Ivar> = class.ivars().collect();
+    assert_eq!(ivars.len(), 2);
+    assert_eq!(ivars[0].name().unwrap().to_str(), Ok("flag"));
+    assert_eq!(ivars[0].type_encoding().unwrap().kind(), encoding::Kind::UnsignedChar);
+    assert_eq!(ivars[1].name().unwrap().to_str(), Ok("value"));
+    assert_eq!(ivars[1].type_encoding().unwrap().kind(), encoding::Kind::Double);
+    assert_eq!(ivars[1].offset(), ivars[0].offset() + 8);
+
+    let properties: Vec<&Property> = class.properties().collect();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].name().to_str(), Ok("value"));
+    assert_eq!(properties[0].attributes().unwrap().to_str(), Ok("Td,N,Vvalue"));
+    assert_eq!(properties[0].type_encoding().unwrap().kind(), encoding::Kind::Double);
+
+    let protocols: Vec<&Protocol> = class.protocols().collect();
+    assert_eq!(protocols.len(), 1);
+    assert!(core::ptr::eq(protocols[0], protocol));
+    assert_eq!(protocols[0].name().to_str(), Ok("MockIntrospectionProtocol"));
+    assert_eq!(root.protocols().len(), 0);
+  }
+}
@@ -14,27 +14,51 @@
   specialization,
   untagged_unions
 )]
+// Thread-local statics, for the mock runtime and for tracking autorelease pools without std.
+#![cfg_attr(any(feature = "mock-runtime", not(feature = "std")), feature(thread_local))]
 #![no_std]
 
+#[cfg(all(feature = "mock-runtime", feature = "runtime-gnustep"))]
+compile_error!("the mock-runtime and runtime-gnustep features are mutually exclusive");
+
 #[doc(hidden)]
 pub mod __objrs;
 mod arc;
+mod block;
+#[cfg(feature = "std")]
+mod catch_panic;
+#[cfg(feature = "alloc")]
+mod class_builder;
 mod cstr;
+mod downcast;
+pub mod encoding;
 mod exception;
+mod introspection;
 pub mod marker;
+#[cfg(feature = "mock-runtime")]
+pub mod mock;
 mod runtime;
+mod send;
 #[cfg(test)]
 mod test;
 mod type_encoding;
+#[cfg(feature = "verify-signatures")]
+mod verify;
 
 extern crate libc;
 extern crate objrs_macros;
 
 pub use objrs_macros::objrs;
+pub use objrs_macros::TypeEncoding;
 
 pub use arc::*;
+pub use block::*;
+#[cfg(feature = "alloc")]
+pub use class_builder::*;
 pub use cstr::*;
 pub use exception::*;
+pub use introspection::*;
+pub use send::*;
 pub use type_encoding::*;
 
 #[macro_export]
@@ -78,26 +102,4 @@ macro_rules! class {
   }};
 }
 
-pub use runtime::{Class, Id, Imp, Method, Sel};
-
-// TODO: I think that this macro should be removed. Consider the following code:
-// fn do_stuff() {
-//   autoreleasepool!{
-//     return;
-//   }
-// }
-// I believe the return statement would result in the autoreleasepool not being popped.
-// This could possibly be fixed by using a gaurd variable with a Drop implementation that pops the pool, but I'm not sure that's foolproof.
-// #[macro_export]
-// macro_rules! autoreleasepool {
-//   ($($tt: tt)*) => {
-//     // Compiling the Objective-C program `int main() {@autoreleasepool{} return 0;}` shows that
-//     // clang doesn't check the return value of the pool push. If clang never does it, then I feel
-//     // comfortable not doing it here either (plus I've looked through the Objective-C runtime code
-//     // and I don't think our lack of null-checking here can be problematic).
-//     // TODO: does this need to be surrounded in {} for any reason?
-//     let pool = unsafe { objc_autoreleasePoolPush() };
-//     {$($tt)*;}
-//     unsafe { objc_autoreleasePoolPop(pool) };
-//   };
-// }
+pub use runtime::{Class, Id, Imp, Ivar, Method, Property, Protocol, Sel};
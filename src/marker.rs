@@ -13,6 +13,14 @@ pub unsafe trait NonRootClass: Class {
   type Super: Class + ?Sized;
 }
 
+// Classes whose class object can be referenced statically. `#[objrs(impl)]` implements this (with
+// the class's class reference) for the class it's applied to. Used for checked downcasts (e.g.
+// `Id::downcast`).
+pub unsafe trait ClassRef: Class {
+  #[doc(hidden)]
+  fn __objrs_class() -> &'static crate::Class;
+}
+
 // The Protocol trait is unusable due to https://github.com/rust-lang/rfcs/blob/master/text/0255-object-safety.md
 // pub unsafe trait Protocol {}
 
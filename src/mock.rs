@@ -0,0 +1,1581 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+//! A minimal Objective-C runtime written in Rust (enabled by the `mock-runtime` feature).
+//!
+//! The mock runtime implements the runtime entry points that objrs uses (message sending, reference
+//! counting, weak references, autorelease pools, selector and class lookup, exceptions, and the
+//! blocks runtime) so that code using objrs can be tested without libobjc (e.g., with `cargo test
+//! --features mock-runtime` on Linux).
+//! Classes are registered with [`register_class`] and their methods with [`add_method`] and
+//! [`add_class_method`] (or created with `ClassBuilder`), and protocols are registered with
+//! [`register_protocol`]. Instances are created with [`new_object`].
+//!
+//! The mock runtime is not a faithful reimplementation of Apple's runtime:
+//!
+//! - Selectors are compared by name, not by address. A selector from `selector!` is not uniqued, so
+//!   it won't compare equal to the (registered) selector that `sel_registerName` returns.
+//! - Class references (`class!` and `extern` classes' class methods) are resolved by the linker and
+//!   won't resolve to mock classes. Use `objc_getClass` (or the class returned by
+//!   [`register_class`]) instead.
+//! - Instances have no storage for ivars (though ivars can be added to a class and introspected). A
+//!   class's `dealloc` method (if it has one) is called when an instance's
+//!   retain count drops to zero, but the instance is freed by the mock runtime (so `dealloc` must
+//!   not call `[super dealloc]`).
+//! - Message sending is only implemented for x86_64 and aarch64 ELF targets.
+//! - Exceptions thrown outside of `catch_exception` panic, and exceptions can't be rethrown.
+
+extern crate alloc;
+extern crate core;
+extern crate libc;
+
+use crate::encoding;
+use crate::runtime::{objc_super, Class, Id, Ivar, Method, Property, Protocol, Sel};
+use crate::{CStr, Strong};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ptr::{null, null_mut, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+
+// Classes (and metaclasses) are never deallocated, so retaining and releasing them is a no-op.
+const IMMORTAL: usize = usize::max_value();
+
+// A spin lock. The mock runtime is no_std, so it can't use std's Mutex.
+struct Lock<T> {
+  locked: AtomicBool,
+  value: UnsafeCell<T>,
+}
+
+// The mock runtime's tables hold raw pointers (which aren't Send), but they're only accessed while
+// the lock is held.
+unsafe impl<T> Sync for Lock<T> {}
+
+struct LockGuard<'a, T> {
+  lock: &'a Lock<T>,
+}
+
+impl<T> Lock<T> {
+  const fn new(value: T) -> Lock<T> {
+    return Lock {
+      locked: AtomicBool::new(false),
+      value: UnsafeCell::new(value),
+    };
+  }
+
+  fn lock(&self) -> LockGuard<T> {
+    while self
+      .locked
+      .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+      .is_err()
+    {
+      core::hint::spin_loop();
+    }
+    return LockGuard {
+      lock: self,
+    };
+  }
+}
+
+impl<'a, T> core::ops::Deref for LockGuard<'a, T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    return unsafe { &*self.lock.value.get() };
+  }
+}
+
+impl<'a, T> core::ops::DerefMut for LockGuard<'a, T> {
+  fn deref_mut(&mut self) -> &mut T {
+    return unsafe { &mut *self.lock.value.get() };
+  }
+}
+
+impl<'a, T> core::ops::Drop for LockGuard<'a, T> {
+  fn drop(&mut self) {
+    self.lock.locked.store(false, Ordering::Release);
+  }
+}
+
+#[repr(C)]
+struct Object {
+  isa: *const ClassData,
+  retain_count: AtomicUsize,
+}
+
+// The layout of a class (or metaclass) object. It starts with an Object so that classes can receive
+// messages. A metaclass's isa points to itself, and a root class's metaclass's superclass is the root
+// class (so root classes' instance methods are also class methods, as in Apple's runtime).
+#[repr(C)]
+struct ClassData {
+  object: Object,
+  superclass: *const ClassData,
+  name: &'static [u8],
+  is_metaclass: bool,
+  methods: Lock<Vec<&'static MethodData>>,
+  ivars: Lock<Vec<&'static IvarData>>,
+  properties: Lock<Vec<&'static PropertyData>>,
+  protocols: Lock<Vec<&'static ProtocolData>>,
+}
+
+unsafe impl Sync for ClassData {}
+
+struct MethodData {
+  name: &'static [u8],
+  types: &'static [u8],
+  imp: *const (),
+}
+
+unsafe impl Sync for MethodData {}
+
+struct IvarData {
+  name: &'static [u8],
+  types: &'static [u8],
+  offset: usize,
+  size: usize,
+}
+
+struct PropertyData {
+  name: &'static [u8],
+  attributes: &'static [u8],
+}
+
+struct ProtocolData {
+  name: &'static [u8],
+}
+
+// Registered selector names. A registered selector is the address of its (NUL-terminated) name.
+static SELECTORS: Lock<Vec<&'static [u8]>> = Lock::new(Vec::new());
+static CLASSES: Lock<Vec<&'static ClassData>> = Lock::new(Vec::new());
+static PROTOCOLS: Lock<Vec<&'static ProtocolData>> = Lock::new(Vec::new());
+// The locations of all (non-nil) weak references.
+static WEAK_LOCATIONS: Lock<Vec<*mut *mut Id>> = Lock::new(Vec::new());
+
+#[thread_local]
+static mut AUTORELEASE_POOL: Vec<*mut Id> = Vec::new();
+
+static NIL_NAME: [u8; 4] = *b"nil\0";
+
+fn leak_cstring(bytes: &[u8]) -> &'static [u8] {
+  let mut cstring = Vec::with_capacity(bytes.len() + 1);
+  cstring.extend_from_slice(bytes);
+  cstring.push(b'\0');
+  return Box::leak(cstring.into_boxed_slice());
+}
+
+// Returns the bytes of a C string (including the NUL terminator).
+unsafe fn cstring_bytes<'a>(ptr: *const libc::c_char) -> &'a [u8] {
+  return core::slice::from_raw_parts(ptr as *const u8, libc::strlen(ptr) + 1);
+}
+
+fn as_str(cstring: &[u8]) -> &str {
+  let bytes = &cstring[..cstring.len() - 1];
+  return core::str::from_utf8(bytes).unwrap_or("<invalid UTF-8>");
+}
+
+// Selectors are compared by name (since selectors from `selector!` aren't registered with the mock
+// runtime).
+unsafe fn sel_name<'a>(sel: &'a Sel) -> &'a [u8] {
+  return cstring_bytes(sel as *const Sel as *const libc::c_char);
+}
+
+fn register_sel(name: &[u8]) -> &'static Sel {
+  let mut selectors = SELECTORS.lock();
+  let name = match selectors.iter().find(|registered| **registered == name) {
+    Some(registered) => *registered,
+    None => {
+      let registered = leak_cstring(&name[..name.len() - 1]);
+      selectors.push(registered);
+      registered
+    }
+  };
+  return unsafe { &*(name.as_ptr() as *const Sel) };
+}
+
+#[inline(always)]
+fn class_data<'a>(class: *const Class) -> &'a ClassData {
+  return unsafe { &*(class as *const ClassData) };
+}
+
+#[inline(always)]
+unsafe fn object<'a>(object: *const Id) -> &'a Object {
+  return &*(object as *const Object);
+}
+
+fn find_method(mut class: *const ClassData, name: &[u8]) -> Option<&'static MethodData> {
+  while let Some(data) = unsafe { class.as_ref() } {
+    if let Some(method) = data.methods.lock().iter().find(|method| method.name == name) {
+      return Some(*method);
+    }
+    class = data.superclass;
+  }
+  return None;
+}
+
+fn insert_method(class: &ClassData, sel: &Sel, imp: *const (), types: &str) {
+  let name = unsafe { sel_name(sel) };
+  let method: &'static MethodData = Box::leak(Box::new(MethodData {
+    name: unsafe { sel_name(register_sel(name)) },
+    types: leak_cstring(types.as_bytes()),
+    imp: imp,
+  }));
+  let mut methods = class.methods.lock();
+  match methods.iter_mut().find(|existing| existing.name == name) {
+    Some(existing) => *existing = method,
+    None => methods.push(method),
+  }
+}
+
+/// Registers a new class with the mock runtime, making it available to `objc_getClass`. Panics if a
+/// class with the same name is already registered.
+pub fn register_class(name: &str, superclass: Option<&'static Class>) -> &'static Class {
+  let name = leak_cstring(name.as_bytes());
+  let mut classes = CLASSES.lock();
+  if classes.iter().any(|class| class.name == name) {
+    panic!("class {} is already registered", as_str(name));
+  }
+
+  let superclass = superclass.map_or(null(), |superclass| class_data(superclass) as *const _);
+  let class = allocate_class(name, superclass);
+  classes.push(unsafe { &*class });
+  return unsafe { &*(class as *const Class) };
+}
+
+/// Registers a new protocol with the mock runtime, making it available to `objc_getProtocol`. Panics
+/// if a protocol with the same name is already registered.
+pub fn register_protocol(name: &str) -> &'static Protocol {
+  let name = leak_cstring(name.as_bytes());
+  let mut protocols = PROTOCOLS.lock();
+  if protocols.iter().any(|protocol| protocol.name == name) {
+    panic!("protocol {} is already registered", as_str(name));
+  }
+
+  let protocol: &'static ProtocolData = Box::leak(Box::new(ProtocolData {
+    name: name,
+  }));
+  protocols.push(protocol);
+  return unsafe { &*(protocol as *const ProtocolData as *const Protocol) };
+}
+
+// Allocates a class and its metaclass, without registering them.
+fn allocate_class(name: &'static [u8], superclass: *const ClassData) -> *mut ClassData {
+  let metaclass = Box::into_raw(Box::new(ClassData {
+    object: Object {
+      isa: null(),
+      retain_count: AtomicUsize::new(IMMORTAL),
+    },
+    superclass: unsafe { superclass.as_ref() }.map_or(null(), |superclass| superclass.object.isa),
+    name: name,
+    is_metaclass: true,
+    methods: Lock::new(Vec::new()),
+    ivars: Lock::new(Vec::new()),
+    properties: Lock::new(Vec::new()),
+    protocols: Lock::new(Vec::new()),
+  }));
+  let class = Box::into_raw(Box::new(ClassData {
+    object: Object {
+      isa: metaclass,
+      retain_count: AtomicUsize::new(IMMORTAL),
+    },
+    superclass: superclass,
+    name: name,
+    is_metaclass: false,
+    methods: Lock::new(Vec::new()),
+    ivars: Lock::new(Vec::new()),
+    properties: Lock::new(Vec::new()),
+    protocols: Lock::new(Vec::new()),
+  }));
+  unsafe {
+    (*metaclass).object.isa = metaclass;
+    if superclass.is_null() {
+      (*metaclass).superclass = class;
+    }
+  }
+  return class;
+}
+
+/// Adds (or replaces) an instance method. `imp` must be an `extern "C"` function whose first two
+/// parameters are the receiver and the selector, and `types` is its method type encoding.
+pub fn add_method(class: &Class, sel: &Sel, imp: *const (), types: &str) {
+  insert_method(class_data(class), sel, imp, types);
+}
+
+/// Adds (or replaces) a class method. See `add_method`.
+pub fn add_class_method(class: &Class, sel: &Sel, imp: *const (), types: &str) {
+  insert_method(unsafe { &*class_data(class).object.isa }, sel, imp, types);
+}
+
+/// Adds a property (with an attribute string like `T@"NSString",C,N`) to a class. Properties are
+/// only used for introspection.
+pub fn add_property(class: &Class, name: &str, attributes: &str) {
+  let property: &'static PropertyData = Box::leak(Box::new(PropertyData {
+    name: leak_cstring(name.as_bytes()),
+    attributes: leak_cstring(attributes.as_bytes()),
+  }));
+  class_data(class).properties.lock().push(property);
+}
+
+/// Creates a new instance of a class. The instance has a retain count of one.
+pub fn new_object(class: &Class) -> Strong<Id> {
+  let class = class_data(class);
+  assert!(!class.is_metaclass, "can't create an instance of a metaclass");
+  let object = Box::into_raw(Box::new(Object {
+    isa: class,
+    retain_count: AtomicUsize::new(1),
+  }));
+  return unsafe { core::mem::transmute(object) };
+}
+
+/// Returns an object's retain count (which is `usize::max_value()` for classes).
+pub fn retain_count(object: &Id) -> usize {
+  return unsafe { self::object(object) }.retain_count.load(Ordering::Relaxed);
+}
+
+unsafe fn dealloc(obj: *mut Id) {
+  if let Some(method) = find_method(object(obj).isa, b"dealloc\0") {
+    let imp: unsafe extern "C" fn(*mut Id, &Sel) = core::mem::transmute(method.imp);
+    imp(obj, &*(method.name.as_ptr() as *const Sel));
+  }
+
+  // Weak references to the object are cleared before it's freed (but after its retain count drops
+  // to zero, so weak references can't be loaded while it's being deallocated).
+  WEAK_LOCATIONS.lock().retain(|&location| {
+    if *location != obj {
+      return true;
+    }
+    *location = null_mut();
+    return false;
+  });
+
+  drop(Box::from_raw(obj as *mut Object));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_retain(obj: *mut Id) -> *mut Id {
+  if !obj.is_null() && object(obj).retain_count.load(Ordering::Relaxed) != IMMORTAL {
+    object(obj).retain_count.fetch_add(1, Ordering::Relaxed);
+  }
+  return obj;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_release(obj: *mut Id) {
+  if obj.is_null() || object(obj).retain_count.load(Ordering::Relaxed) == IMMORTAL {
+    return;
+  }
+  if object(obj).retain_count.fetch_sub(1, Ordering::Release) == 1 {
+    core::sync::atomic::fence(Ordering::Acquire);
+    dealloc(obj);
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_autorelease(obj: *mut Id) -> *mut Id {
+  if !obj.is_null() {
+    AUTORELEASE_POOL.push(obj);
+  }
+  return obj;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_retainAutorelease(obj: *mut Id) -> *mut Id {
+  return objc_autorelease(objc_retain(obj));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_retainAutoreleasedReturnValue(obj: *mut Id) -> *mut Id {
+  return objc_retain(obj);
+}
+
+// The mock runtime doesn't implement the return value handshake, so objects returned with
+// objc_autoreleaseReturnValue always go through the autorelease pool.
+#[no_mangle]
+pub unsafe extern "C" fn objc_autoreleaseReturnValue(obj: *mut Id) -> *mut Id {
+  return objc_autorelease(obj);
+}
+
+// A pool is identified by the (one-based) size of the thread's autorelease stack when it's pushed.
+#[no_mangle]
+pub unsafe extern "C" fn objc_autoreleasePoolPush() -> *mut libc::c_void {
+  return (AUTORELEASE_POOL.len() + 1) as *mut libc::c_void;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_autoreleasePoolPop(pool: *mut libc::c_void) {
+  let len = pool as usize - 1;
+  // Releasing an object may autorelease more objects, so the stack is re-checked after each release.
+  while AUTORELEASE_POOL.len() > len {
+    let obj = AUTORELEASE_POOL.pop().unwrap();
+    objc_release(obj);
+  }
+}
+
+fn store_weak(
+  locations: &mut Vec<*mut *mut Id>,
+  location: *mut *mut Id,
+  value: *mut Id,
+) -> *mut Id {
+  let mut value = value;
+  // Deallocating objects can't be weakly referenced.
+  if !value.is_null() && unsafe { object(value) }.retain_count.load(Ordering::Relaxed) == 0 {
+    value = null_mut();
+  }
+  if !value.is_null() {
+    locations.push(location);
+  }
+  unsafe { *location = value };
+  return value;
+}
+
+fn unregister_weak(locations: &mut Vec<*mut *mut Id>, location: *mut *mut Id) {
+  if let Some(index) = locations.iter().position(|&registered| registered == location) {
+    locations.swap_remove(index);
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_initWeak(location: *mut *mut Id, value: *mut Id) -> *mut Id {
+  return store_weak(&mut WEAK_LOCATIONS.lock(), location, value);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_storeWeak(location: *mut *mut Id, value: *mut Id) -> *mut Id {
+  let mut locations = WEAK_LOCATIONS.lock();
+  unregister_weak(&mut locations, location);
+  return store_weak(&mut locations, location, value);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_destroyWeak(location: *mut *mut Id) {
+  unregister_weak(&mut WEAK_LOCATIONS.lock(), location);
+  *location = null_mut();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_loadWeakRetained(location: *mut *mut Id) -> *mut Id {
+  // Holding the lock keeps the object from being freed (though its retain count may drop to zero).
+  let _locations = WEAK_LOCATIONS.lock();
+  let obj = *location;
+  if obj.is_null() {
+    return obj;
+  }
+  let retain_count = &object(obj).retain_count;
+  let mut count = retain_count.load(Ordering::Relaxed);
+  loop {
+    if count == 0 {
+      return null_mut();
+    }
+    if count == IMMORTAL {
+      return obj;
+    }
+    let exchange =
+      retain_count.compare_exchange_weak(count, count + 1, Ordering::Relaxed, Ordering::Relaxed);
+    match exchange {
+      Ok(_) => return obj,
+      Err(previous) => count = previous,
+    }
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_copyWeak(to: *mut *mut Id, from: *mut *mut Id) {
+  let obj = objc_loadWeakRetained(from);
+  objc_initWeak(to, obj);
+  objc_release(obj);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_moveWeak(to: *mut *mut Id, from: *mut *mut Id) {
+  let mut locations = WEAK_LOCATIONS.lock();
+  unregister_weak(&mut locations, from);
+  store_weak(&mut locations, to, *from);
+  *from = null_mut();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_loadWeak(location: *mut *mut Id) -> *mut Id {
+  return objc_autorelease(objc_loadWeakRetained(location));
+}
+
+// Objective-C exceptions are thrown with the platform's unwinder (like libobjc2), but only if there's
+// an `__objrs_catch_exception` frame (i.e. a `catch_exception` call) on the stack to catch them.
+// Otherwise the exception is thrown as a Rust panic: a foreign exception would be caught by any Rust
+// `catch_unwind` (such as the test harness's), which Rust doesn't support. A `catch_unwind` between
+// the throw and the `catch_exception` call has the same problem, so don't do that.
+#[no_mangle]
+#[unwind(allowed)]
+pub unsafe extern "C" fn objc_exception_throw(exception: *mut Id) -> ! {
+  #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), not(target_vendor = "apple")))]
+  {
+    if unwind::is_catching() {
+      unwind::raise(exception);
+    }
+  }
+  panic!("Objective-C exception thrown: {:p}", exception);
+}
+
+#[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), not(target_vendor = "apple")))]
+mod unwind {
+  // See https://itanium-cxx-abi.github.io/cxx-abi/abi-eh.html
+
+  use super::{Box, Id};
+
+  #[allow(non_camel_case_types)]
+  type _Unwind_Reason_Code = libc::c_int;
+  const _URC_NO_REASON: _Unwind_Reason_Code = 0;
+  const _URC_NORMAL_STOP: _Unwind_Reason_Code = 4;
+  const _URC_HANDLER_FOUND: _Unwind_Reason_Code = 6;
+  const _URC_INSTALL_CONTEXT: _Unwind_Reason_Code = 7;
+  const _URC_CONTINUE_UNWIND: _Unwind_Reason_Code = 8;
+
+  #[allow(non_camel_case_types)]
+  type _Unwind_Action = libc::c_int;
+  const _UA_SEARCH_PHASE: _Unwind_Action = 1;
+
+  const DW_EH_PE_OMIT: u8 = 0xff;
+  const DW_EH_PE_ULEB128: u8 = 0x01;
+
+  // Identifies the mock runtime's exceptions ("MOCKOBJC").
+  const EXCEPTION_CLASS: u64 = 0x4d4f_434b_4f42_4a43;
+
+  extern "C" {
+    #[allow(non_camel_case_types)]
+    type _Unwind_Context;
+  }
+
+  #[allow(dead_code)]
+  #[repr(C, align(16))]
+  struct _Unwind_Exception {
+    exception_class: u64,
+    exception_cleanup: unsafe extern "C" fn(_Unwind_Reason_Code, *mut _Unwind_Exception),
+    private: [usize; 2],
+  }
+
+  #[repr(C)]
+  struct Exception {
+    header: _Unwind_Exception,
+    object: *mut Id,
+  }
+
+  extern "C" {
+    #[unwind(allowed)]
+    fn _Unwind_RaiseException(exception: *mut _Unwind_Exception) -> _Unwind_Reason_Code;
+    fn _Unwind_Backtrace(
+      trace: extern "C" fn(*mut _Unwind_Context, *mut libc::c_void) -> _Unwind_Reason_Code,
+      arg: *mut libc::c_void,
+    ) -> _Unwind_Reason_Code;
+    fn _Unwind_GetLanguageSpecificData(context: *mut _Unwind_Context) -> *const u8;
+    fn _Unwind_GetRegionStart(context: *mut _Unwind_Context) -> usize;
+    fn _Unwind_GetIPInfo(context: *mut _Unwind_Context, ip_before_insn: *mut libc::c_int) -> usize;
+    fn _Unwind_SetGR(context: *mut _Unwind_Context, index: libc::c_int, value: usize);
+    fn _Unwind_SetIP(context: *mut _Unwind_Context, value: usize);
+
+    fn __objrs_catch_exception(
+      payload: *mut libc::c_void,
+      function: extern "C" fn(_: *mut libc::c_void),
+      exception: *mut libc::c_void,
+    ) -> bool;
+  }
+
+  // Returns true if there's an `__objrs_catch_exception` frame on the stack.
+  pub fn is_catching() -> bool {
+    extern "C" fn trace(
+      context: *mut _Unwind_Context,
+      found: *mut libc::c_void,
+    ) -> _Unwind_Reason_Code {
+      if unsafe { _Unwind_GetRegionStart(context) } == __objrs_catch_exception as usize {
+        unsafe { *(found as *mut bool) = true };
+        return _URC_NORMAL_STOP;
+      }
+      return _URC_NO_REASON;
+    }
+
+    let mut found = false;
+    unsafe { _Unwind_Backtrace(trace, &mut found as *mut bool as *mut libc::c_void) };
+    return found;
+  }
+
+  // Throws `object`. Only returns if the unwinder didn't find a handler.
+  pub unsafe fn raise(object: *mut Id) {
+    unsafe extern "C" fn cleanup(_: _Unwind_Reason_Code, exception: *mut _Unwind_Exception) {
+      drop(Box::from_raw(exception as *mut Exception));
+    }
+
+    let exception = Box::into_raw(Box::new(Exception {
+      header: _Unwind_Exception {
+        exception_class: EXCEPTION_CLASS,
+        exception_cleanup: cleanup,
+        private: [0; 2],
+      },
+      object: object,
+    }));
+    _Unwind_RaiseException(exception as *mut _Unwind_Exception);
+    drop(Box::from_raw(exception));
+  }
+
+  unsafe fn read_u8(ptr: &mut *const u8) -> u8 {
+    let byte = **ptr;
+    *ptr = ptr.add(1);
+    return byte;
+  }
+
+  unsafe fn read_uleb128(ptr: &mut *const u8) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+      let byte = read_u8(ptr);
+      value |= ((byte & 0x7f) as usize) << shift;
+      shift += 7;
+      if byte & 0x80 == 0 {
+        return value;
+      }
+    }
+  }
+
+  // Finds the landing pad of the catch clause that covers the context's call site. Only
+  // __objrs_catch_exception uses this personality, so this only supports the LSDA layout it uses
+  // (no LPStart, and uleb128 call sites), and treats every catch clause as `@catch (id)`. Cleanups
+  // (call sites whose action is 0) are ignored, since __objrs_catch_exception doesn't have any.
+  unsafe fn find_landing_pad(context: *mut _Unwind_Context) -> Option<usize> {
+    let mut lsda = _Unwind_GetLanguageSpecificData(context);
+    if lsda.is_null() {
+      return None;
+    }
+    let start = _Unwind_GetRegionStart(context);
+    let mut ip_before_insn = 0;
+    let mut ip = _Unwind_GetIPInfo(context, &mut ip_before_insn);
+    if ip_before_insn == 0 {
+      // The IP is the return address, which may be past the end of the call site.
+      ip -= 1;
+    }
+
+    assert_eq!(read_u8(&mut lsda), DW_EH_PE_OMIT, "LPStart isn't supported");
+    if read_u8(&mut lsda) != DW_EH_PE_OMIT {
+      read_uleb128(&mut lsda);
+    }
+    assert_eq!(read_u8(&mut lsda), DW_EH_PE_ULEB128, "unsupported call site encoding");
+    let call_sites_len = read_uleb128(&mut lsda);
+    let call_sites_end = lsda.add(call_sites_len);
+    while lsda < call_sites_end {
+      let call_site_start = start + read_uleb128(&mut lsda);
+      let call_site_len = read_uleb128(&mut lsda);
+      let landing_pad = read_uleb128(&mut lsda);
+      let action = read_uleb128(&mut lsda);
+      if ip < call_site_start {
+        break;
+      }
+      if ip < call_site_start + call_site_len {
+        if landing_pad == 0 || action == 0 {
+          return None;
+        }
+        return Some(start + landing_pad);
+      }
+    }
+    return None;
+  }
+
+  #[no_mangle]
+  #[unwind(allowed)]
+  pub unsafe extern "C" fn __gnustep_objc_personality_v0(
+    version: libc::c_int,
+    actions: _Unwind_Action,
+    exception_class: u64,
+    exception: *mut _Unwind_Exception,
+    context: *mut _Unwind_Context,
+  ) -> _Unwind_Reason_Code {
+    if version != 1 || exception_class != EXCEPTION_CLASS {
+      return _URC_CONTINUE_UNWIND;
+    }
+    let landing_pad = match find_landing_pad(context) {
+      Some(landing_pad) => landing_pad,
+      None => return _URC_CONTINUE_UNWIND,
+    };
+    if actions & _UA_SEARCH_PHASE != 0 {
+      return _URC_HANDLER_FOUND;
+    }
+    // The landing pad gets the exception in the first exception register, and the catch clause's
+    // type index in the second (on both x86_64 and aarch64, these are registers 0 and 1).
+    _Unwind_SetGR(context, 0, exception as usize);
+    _Unwind_SetGR(context, 1, 1);
+    _Unwind_SetIP(context, landing_pad);
+    return _URC_INSTALL_CONTEXT;
+  }
+
+  // The type info of `@catch (id)` clauses (only its address matters).
+  #[no_mangle]
+  pub static __objc_id_type_info: u8 = 0;
+
+  // Returns the caught exception's object, and frees the exception. The mock runtime doesn't
+  // support rethrowing, so the exception isn't needed once it's been caught.
+  #[no_mangle]
+  pub unsafe extern "C" fn objc_begin_catch(exception: *mut _Unwind_Exception) -> *mut Id {
+    let exception = Box::from_raw(exception as *mut Exception);
+    return exception.object;
+  }
+
+  #[no_mangle]
+  pub extern "C" fn objc_end_catch() {}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sel_registerName(name: &CStr) -> &'static Sel {
+  return register_sel(cstring_bytes(name.as_ptr()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sel_getUid(name: &CStr) -> &'static Sel {
+  return sel_registerName(name);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sel_getName(sel: &Sel) -> &CStr {
+  return &*(sel as *const Sel as *const CStr);
+}
+
+// Every selector is usable (since selectors are compared by name).
+#[no_mangle]
+pub unsafe extern "C" fn sel_isMapped(_sel: &Sel) -> bool {
+  return true;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_getClass(name: &CStr) -> *const Class {
+  let name = cstring_bytes(name.as_ptr());
+  return match CLASSES.lock().iter().find(|class| class.name == name) {
+    Some(class) => *class as *const ClassData as *const Class,
+    None => null(),
+  };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_getProtocol(name: &CStr) -> *const Protocol {
+  let name = cstring_bytes(name.as_ptr());
+  return match PROTOCOLS.lock().iter().find(|protocol| protocol.name == name) {
+    Some(protocol) => *protocol as *const ProtocolData as *const Protocol,
+    None => null(),
+  };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_allocateClassPair(
+  superclass: *const Class,
+  name: &CStr,
+  _extra_bytes: usize,
+) -> *mut Class {
+  let name = cstring_bytes(name.as_ptr());
+  if CLASSES.lock().iter().any(|class| class.name == name) {
+    return null_mut();
+  }
+  let superclass = if superclass.is_null() { null() } else { class_data(superclass) as *const _ };
+  return allocate_class(leak_cstring(&name[..name.len() - 1]), superclass) as *mut Class;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_registerClassPair(class: *mut Class) {
+  let class = class_data(class);
+  let mut classes = CLASSES.lock();
+  if classes.iter().any(|registered| registered.name == class.name) {
+    panic!("class {} is already registered", as_str(class.name));
+  }
+  classes.push(class);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_disposeClassPair(class: *mut Class) {
+  let class = class as *mut ClassData;
+  CLASSES.lock().retain(|registered| !core::ptr::eq(*registered, class));
+  let metaclass = (*class).object.isa as *mut ClassData;
+  drop(Box::from_raw(class));
+  drop(Box::from_raw(metaclass));
+}
+
+// Ivars are laid out after the object's header (ignoring any superclass ivars), but instances never
+// have storage for them.
+#[no_mangle]
+pub unsafe extern "C" fn class_addIvar(
+  class: *mut Class,
+  name: &CStr,
+  size: usize,
+  alignment: u8,
+  types: &CStr,
+) -> bool {
+  let name = cstring_bytes(name.as_ptr());
+  let mut ivars = class_data(class).ivars.lock();
+  if ivars.iter().any(|ivar| ivar.name == name) {
+    return false;
+  }
+  let end = ivars.last().map_or(core::mem::size_of::<Object>(), |last| last.offset + last.size);
+  let alignment = 1usize << alignment;
+  let types = cstring_bytes(types.as_ptr());
+  ivars.push(Box::leak(Box::new(IvarData {
+    name: leak_cstring(&name[..name.len() - 1]),
+    types: leak_cstring(&types[..types.len() - 1]),
+    offset: (end + alignment - 1) & !(alignment - 1),
+    size: size,
+  })));
+  return true;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_addMethod(
+  class: *mut Class,
+  sel: &Sel,
+  imp: *const (),
+  types: &CStr,
+) -> bool {
+  let data = class_data(class);
+  let name = sel_name(sel);
+  if data.methods.lock().iter().any(|method| method.name == name) {
+    return false;
+  }
+  let types = cstring_bytes(types.as_ptr());
+  insert_method(data, sel, imp, as_str(types));
+  return true;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_addProtocol(class: *mut Class, protocol: *const Protocol) -> bool {
+  let protocol = &*(protocol as *const ProtocolData);
+  let mut protocols = class_data(class).protocols.lock();
+  if protocols.iter().any(|existing| core::ptr::eq(*existing, protocol)) {
+    return false;
+  }
+  protocols.push(protocol);
+  return true;
+}
+
+// Like the runtime's copy*List functions, the list is allocated with malloc (so the caller must free
+// it) and NULL if it's empty.
+unsafe fn copy_list<T>(items: &[*const T], out_count: *mut libc::c_uint) -> *mut *const T {
+  if !out_count.is_null() {
+    *out_count = items.len() as libc::c_uint;
+  }
+  if items.is_empty() {
+    return null_mut();
+  }
+  let list = libc::malloc(core::mem::size_of::<*const T>() * items.len()) as *mut *const T;
+  core::ptr::copy_nonoverlapping(items.as_ptr(), list, items.len());
+  return list;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn objc_copyClassList(out_count: *mut libc::c_uint) -> *mut *const Class {
+  let classes: Vec<*const Class> =
+    CLASSES.lock().iter().map(|class| *class as *const ClassData as *const Class).collect();
+  return copy_list(&classes, out_count);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_copyMethodList(
+  class: *const Class,
+  out_count: *mut libc::c_uint,
+) -> *mut *const Method {
+  let methods: Vec<*const Method> = class_data(class)
+    .methods
+    .lock()
+    .iter()
+    .map(|method| *method as *const MethodData as *const Method)
+    .collect();
+  return copy_list(&methods, out_count);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_copyIvarList(
+  class: *const Class,
+  out_count: *mut libc::c_uint,
+) -> *mut *const Ivar {
+  let ivars: Vec<*const Ivar> = class_data(class)
+    .ivars
+    .lock()
+    .iter()
+    .map(|ivar| *ivar as *const IvarData as *const Ivar)
+    .collect();
+  return copy_list(&ivars, out_count);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_copyPropertyList(
+  class: *const Class,
+  out_count: *mut libc::c_uint,
+) -> *mut *const Property {
+  let properties: Vec<*const Property> = class_data(class)
+    .properties
+    .lock()
+    .iter()
+    .map(|property| *property as *const PropertyData as *const Property)
+    .collect();
+  return copy_list(&properties, out_count);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_copyProtocolList(
+  class: *const Class,
+  out_count: *mut libc::c_uint,
+) -> *mut *const Protocol {
+  let protocols: Vec<*const Protocol> = class_data(class)
+    .protocols
+    .lock()
+    .iter()
+    .map(|protocol| *protocol as *const ProtocolData as *const Protocol)
+    .collect();
+  return copy_list(&protocols, out_count);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivar_getName(ivar: *const Ivar) -> *const libc::c_char {
+  return (*(ivar as *const IvarData)).name.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivar_getTypeEncoding(ivar: *const Ivar) -> *const libc::c_char {
+  return (*(ivar as *const IvarData)).types.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn ivar_getOffset(ivar: *const Ivar) -> isize {
+  return (*(ivar as *const IvarData)).offset as isize;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn property_getName(property: *const Property) -> *const libc::c_char {
+  return (*(property as *const PropertyData)).name.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn property_getAttributes(property: *const Property) -> *const libc::c_char {
+  return (*(property as *const PropertyData)).attributes.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn protocol_getName(protocol: *const Protocol) -> *const libc::c_char {
+  return (*(protocol as *const ProtocolData)).name.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_getName(class: *const Class) -> *const libc::c_char {
+  if class.is_null() {
+    return NIL_NAME.as_ptr() as *const _;
+  }
+  return class_data(class).name.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_getSuperclass(class: *const Class) -> *const Class {
+  if class.is_null() {
+    return null();
+  }
+  return class_data(class).superclass as *const Class;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_respondsToSelector(class: *const Class, sel: &Sel) -> bool {
+  return !class.is_null() && find_method(class_data(class), sel_name(sel)).is_some();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_getInstanceMethod(class: *const Class, sel: &Sel) -> *const Method {
+  if class.is_null() {
+    return null();
+  }
+  return match find_method(class_data(class), sel_name(sel)) {
+    Some(method) => method as *const MethodData as *const Method,
+    None => null(),
+  };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn class_getClassMethod(class: *const Class, sel: &Sel) -> *const Method {
+  if class.is_null() {
+    return null();
+  }
+  let data = class_data(class);
+  if data.is_metaclass {
+    return class_getInstanceMethod(class, sel);
+  }
+  return class_getInstanceMethod(data.object.isa as *const Class, sel);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn object_getClass(obj: *const Id) -> *const Class {
+  if obj.is_null() {
+    return null();
+  }
+  return object(obj).isa as *const Class;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn object_isClass(obj: *const Id) -> bool {
+  return !obj.is_null() && (*object(obj).isa).is_metaclass;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn object_getClassName(obj: *const Id) -> *const libc::c_char {
+  return class_getName(object_getClass(obj));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn method_getTypeEncoding(method: NonNull<Method>) -> *const libc::c_char {
+  return (*(method.as_ptr() as *const MethodData)).types.as_ptr() as *const _;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn method_getName(method: NonNull<Method>) -> &'static Sel {
+  return &*((*(method.as_ptr() as *const MethodData)).name.as_ptr() as *const Sel);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn method_getImplementation(method: NonNull<Method>) -> *const () {
+  return (*(method.as_ptr() as *const MethodData)).imp;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn method_getNumberOfArguments(method: NonNull<Method>) -> libc::c_uint {
+  let types = (*(method.as_ptr() as *const MethodData)).types;
+  let types = core::str::from_utf8(&types[..types.len() - 1]).unwrap_or("");
+  // The first type is the return type.
+  let count = encoding::parse_method(types).count();
+  return count.saturating_sub(1) as libc::c_uint;
+}
+
+// The blocks runtime. Heap blocks keep their retain count in the low bits of their flags (as in
+// Apple's runtime).
+const BLOCK_REFCOUNT_MASK: i32 = 0xfffe;
+const BLOCK_NEEDS_FREE: i32 = 1 << 24;
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+// Only the fields that the blocks runtime uses are read.
+#[allow(dead_code)]
+#[repr(C)]
+struct BlockHeader {
+  isa: *const [usize; 32],
+  flags: AtomicI32,
+  reserved: i32,
+  invoke: *const (),
+  descriptor: *const BlockDescriptor,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct BlockDescriptor {
+  reserved: usize,
+  size: usize,
+  // Only present if the block's flags include BLOCK_HAS_COPY_DISPOSE.
+  copy: unsafe extern "C" fn(dst: *mut libc::c_void, src: *const libc::c_void),
+  dispose: unsafe extern "C" fn(block: *mut libc::c_void),
+}
+
+#[no_mangle]
+pub static _NSConcreteStackBlock: [usize; 32] = [0; 32];
+#[no_mangle]
+pub static _NSConcreteMallocBlock: [usize; 32] = [0; 32];
+#[no_mangle]
+pub static _NSConcreteGlobalBlock: [usize; 32] = [0; 32];
+
+#[no_mangle]
+pub unsafe extern "C" fn _Block_copy(block: *const libc::c_void) -> *mut libc::c_void {
+  let block = block as *mut BlockHeader;
+  if block.is_null() {
+    return null_mut();
+  }
+  let flags = (*block).flags.load(Ordering::Relaxed);
+  if flags & BLOCK_IS_GLOBAL != 0 {
+    return block as *mut libc::c_void;
+  }
+  if flags & BLOCK_NEEDS_FREE != 0 {
+    (*block).flags.fetch_add(2, Ordering::Relaxed);
+    return block as *mut libc::c_void;
+  }
+
+  let descriptor = &*(*block).descriptor;
+  let copy = libc::malloc(descriptor.size) as *mut BlockHeader;
+  assert!(!copy.is_null(), "out of memory");
+  core::ptr::copy_nonoverlapping(block as *const u8, copy as *mut u8, descriptor.size);
+  (*copy).isa = &_NSConcreteMallocBlock;
+  (*copy).flags = AtomicI32::new((flags & !BLOCK_REFCOUNT_MASK) | BLOCK_NEEDS_FREE | 2);
+  if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+    (descriptor.copy)(copy as *mut libc::c_void, block as *const libc::c_void);
+  }
+  return copy as *mut libc::c_void;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn _Block_release(block: *const libc::c_void) {
+  let block = block as *mut BlockHeader;
+  if block.is_null() || (*block).flags.load(Ordering::Relaxed) & BLOCK_NEEDS_FREE == 0 {
+    return;
+  }
+  let flags = (*block).flags.fetch_sub(2, Ordering::Release);
+  if flags & BLOCK_REFCOUNT_MASK != 2 {
+    return;
+  }
+  core::sync::atomic::fence(Ordering::Acquire);
+  if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+    ((*(*block).descriptor).dispose)(block as *mut libc::c_void);
+  }
+  libc::free(block as *mut libc::c_void);
+}
+
+// Finds the implementation for a message send, starting at `class`. Called by the objc_msgSend
+// family of functions (which then tail call the implementation).
+unsafe fn lookup(class: *const ClassData, receiver: *const Id, sel: &Sel) -> *const () {
+  if let Some(method) = find_method(class, sel_name(sel)) {
+    return method.imp;
+  }
+  let class_name = as_str(cstring_bytes(object_getClassName(receiver)));
+  panic!(
+    "{}[{} {}]: unrecognized selector sent to {:p}",
+    if object_isClass(receiver) { "+" } else { "-" },
+    class_name,
+    as_str(sel_name(sel)),
+    receiver,
+  );
+}
+
+#[no_mangle]
+#[unwind(allowed)]
+unsafe extern "C" fn __objrs_mock_lookup(receiver: *const Id, sel: &Sel) -> *const () {
+  return lookup(object(receiver).isa, receiver, sel);
+}
+
+#[no_mangle]
+#[unwind(allowed)]
+unsafe extern "C" fn __objrs_mock_lookup_super(sup: &objc_super, sel: &Sel) -> *const () {
+  return lookup(class_data(sup.super_class), sup.receiver.as_ptr(), sel);
+}
+
+// objc_msgSendSuper2's objc_super holds the current class rather than its superclass.
+#[no_mangle]
+#[unwind(allowed)]
+unsafe extern "C" fn __objrs_mock_lookup_super2(sup: &objc_super, sel: &Sel) -> *const () {
+  return lookup(class_data(sup.super_class).superclass, sup.receiver.as_ptr(), sel);
+}
+
+// Each objc_msgSend function saves the argument registers, looks up the implementation, restores the
+// argument registers, and tail calls the implementation. Messages to nil return zero (without
+// looking anything up). The stret variants take the struct return pointer as their first argument
+// (so the receiver and selector are shifted by one register). The super variants take an objc_super
+// pointer instead of the receiver, and replace it with the receiver before the tail call.
+#[cfg(all(target_arch = "x86_64", not(target_vendor = "apple")))]
+global_asm! {r#"
+  .macro OBJRS_MOCK_SEND name, lookup, stret, super
+  .text
+  .globl  \name
+  .type  \name,@function
+  .p2align  4, 0x90
+\name:
+  .cfi_startproc
+  .if !\super
+  .if \stret
+  testq  %rsi, %rsi
+  .else
+  testq  %rdi, %rdi
+  .endif
+  je  2f
+  .endif
+  pushq  %rbp
+  .cfi_def_cfa_offset 16
+  .cfi_offset %rbp, -16
+  movq  %rsp, %rbp
+  .cfi_def_cfa_register %rbp
+  subq  $192, %rsp
+  movq  %rdi, (%rsp)
+  movq  %rsi, 8(%rsp)
+  movq  %rdx, 16(%rsp)
+  movq  %rcx, 24(%rsp)
+  movq  %r8, 32(%rsp)
+  movq  %r9, 40(%rsp)
+  movq  %rax, 48(%rsp)
+  movdqa  %xmm0, 64(%rsp)
+  movdqa  %xmm1, 80(%rsp)
+  movdqa  %xmm2, 96(%rsp)
+  movdqa  %xmm3, 112(%rsp)
+  movdqa  %xmm4, 128(%rsp)
+  movdqa  %xmm5, 144(%rsp)
+  movdqa  %xmm6, 160(%rsp)
+  movdqa  %xmm7, 176(%rsp)
+  .if \stret
+  movq  %rsi, %rdi
+  movq  %rdx, %rsi
+  .endif
+  callq  \lookup@PLT
+  movq  %rax, %r11
+  movdqa  176(%rsp), %xmm7
+  movdqa  160(%rsp), %xmm6
+  movdqa  144(%rsp), %xmm5
+  movdqa  128(%rsp), %xmm4
+  movdqa  112(%rsp), %xmm3
+  movdqa  96(%rsp), %xmm2
+  movdqa  80(%rsp), %xmm1
+  movdqa  64(%rsp), %xmm0
+  movq  48(%rsp), %rax
+  movq  40(%rsp), %r9
+  movq  32(%rsp), %r8
+  movq  24(%rsp), %rcx
+  movq  16(%rsp), %rdx
+  movq  8(%rsp), %rsi
+  movq  (%rsp), %rdi
+  movq  %rbp, %rsp
+  popq  %rbp
+  .cfi_def_cfa %rsp, 8
+  .cfi_restore %rbp
+  .if \super
+  .if \stret
+  movq  (%rsi), %rsi
+  .else
+  movq  (%rdi), %rdi
+  .endif
+  jmpq  *%r11
+  .else
+  jmpq  *%r11
+2:
+  .if \stret
+  movq  %rdi, %rax
+  .else
+  xorl  %eax, %eax
+  xorl  %edx, %edx
+  xorps  %xmm0, %xmm0
+  xorps  %xmm1, %xmm1
+  .endif
+  retq
+  .endif
+  .cfi_endproc
+  .size  \name, .-\name
+  .endm
+
+  OBJRS_MOCK_SEND objc_msgSend, __objrs_mock_lookup, 0, 0
+  OBJRS_MOCK_SEND objc_msgSend_stret, __objrs_mock_lookup, 1, 0
+  OBJRS_MOCK_SEND objc_msgSendSuper, __objrs_mock_lookup_super, 0, 1
+  OBJRS_MOCK_SEND objc_msgSendSuper_stret, __objrs_mock_lookup_super, 1, 1
+  OBJRS_MOCK_SEND objc_msgSendSuper2, __objrs_mock_lookup_super2, 0, 1
+  OBJRS_MOCK_SEND objc_msgSendSuper2_stret, __objrs_mock_lookup_super2, 1, 1
+
+  // Rust doesn't have `long double`, so the fpret variants are the same as objc_msgSend.
+  .globl  objc_msgSend_fpret
+  .type  objc_msgSend_fpret,@function
+  .set  objc_msgSend_fpret, objc_msgSend
+  .globl  objc_msgSend_fp2ret
+  .type  objc_msgSend_fp2ret,@function
+  .set  objc_msgSend_fp2ret, objc_msgSend
+"#}
+
+#[cfg(all(target_arch = "aarch64", not(target_vendor = "apple")))]
+global_asm! {r#"
+  .macro OBJRS_MOCK_SEND name, lookup, super
+  .text
+  .globl  \name
+  .type  \name,%function
+  .p2align  2
+\name:
+  .cfi_startproc
+  .if !\super
+  cbz  x0, 2f
+  .endif
+  stp  x29, x30, [sp, #-224]!
+  .cfi_def_cfa_offset 224
+  .cfi_offset x30, -216
+  .cfi_offset x29, -224
+  mov  x29, sp
+  stp  x0, x1, [sp, #16]
+  stp  x2, x3, [sp, #32]
+  stp  x4, x5, [sp, #48]
+  stp  x6, x7, [sp, #64]
+  str  x8, [sp, #80]
+  stp  q0, q1, [sp, #96]
+  stp  q2, q3, [sp, #128]
+  stp  q4, q5, [sp, #160]
+  stp  q6, q7, [sp, #192]
+  bl  \lookup
+  mov  x16, x0
+  ldp  q6, q7, [sp, #192]
+  ldp  q4, q5, [sp, #160]
+  ldp  q2, q3, [sp, #128]
+  ldp  q0, q1, [sp, #96]
+  ldr  x8, [sp, #80]
+  ldp  x6, x7, [sp, #64]
+  ldp  x4, x5, [sp, #48]
+  ldp  x2, x3, [sp, #32]
+  ldp  x0, x1, [sp, #16]
+  ldp  x29, x30, [sp], #224
+  .cfi_def_cfa_offset 0
+  .cfi_restore x29
+  .cfi_restore x30
+  .if \super
+  ldr  x0, [x0]
+  br  x16
+  .else
+  br  x16
+2:
+  mov  x0, #0
+  mov  x1, #0
+  movi  d0, #0
+  movi  d1, #0
+  movi  d2, #0
+  movi  d3, #0
+  ret
+  .endif
+  .cfi_endproc
+  .size  \name, .-\name
+  .endm
+
+  OBJRS_MOCK_SEND objc_msgSend, __objrs_mock_lookup, 0
+  OBJRS_MOCK_SEND objc_msgSendSuper, __objrs_mock_lookup_super, 1
+  OBJRS_MOCK_SEND objc_msgSendSuper2, __objrs_mock_lookup_super2, 1
+"#}
+
+#[cfg(test)]
+mod tests {
+  extern crate std;
+
+  use super::*;
+  use crate::runtime;
+  use crate::{autoreleasepool, selector, Weak, WeakCell};
+  use core::mem::MaybeUninit;
+  use core::pin::Pin;
+
+  fn class_named(name: &str) -> &'static Class {
+    let name = leak_cstring(name.as_bytes());
+    return unsafe { runtime::objc_getClass(CStr::with_bytes(name)).as_ref() }.unwrap();
+  }
+
+  #[test]
+  fn retain_release() {
+    static DEALLOCS: AtomicUsize = AtomicUsize::new(0);
+    extern "C" fn dealloc(_this: *mut Id, _sel: &Sel) {
+      DEALLOCS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let class = register_class("MockRetainRelease", None);
+    add_method(class, selector!("dealloc"), dealloc as *const (), "v16@0:8");
+
+    let object = new_object(class);
+    assert_eq!(retain_count(&object), 1);
+    let clone = object.clone();
+    assert_eq!(retain_count(&object), 2);
+    drop(clone);
+    assert_eq!(retain_count(&object), 1);
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 0);
+    object.release();
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 1);
+  }
+
+  #[test]
+  fn autorelease_pool() {
+    static DEALLOCS: AtomicUsize = AtomicUsize::new(0);
+    extern "C" fn dealloc(_this: *mut Id, _sel: &Sel) {
+      DEALLOCS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let class = register_class("MockAutoreleasePool", None);
+    add_method(class, selector!("dealloc"), dealloc as *const (), "v16@0:8");
+
+    autoreleasepool(|pool| {
+      let object = new_object(class).autorelease(pool);
+      let retained = object.retain();
+      assert_eq!(retain_count(&retained), 2);
+      autoreleasepool(|pool| {
+        retained.clone().autorelease(pool);
+        assert_eq!(retain_count(&retained), 3);
+      });
+      assert_eq!(retain_count(&retained), 2);
+      retained.autorelease(pool);
+      assert_eq!(DEALLOCS.load(Ordering::Relaxed), 0);
+    });
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 1);
+
+    let count = autoreleasepool(|pool| {
+      let object = pool.autorelease(new_object(class));
+      return retain_count(&object);
+    });
+    assert_eq!(count, 1);
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 2);
+
+    // The pool is popped even if a panic unwinds through it.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      autoreleasepool(|pool| {
+        pool.autorelease(new_object(class));
+        panic!("unwinding through an autorelease pool");
+      });
+    }));
+    assert!(result.is_err());
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 3);
+
+    // Only the innermost pool can be autoreleased into.
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      autoreleasepool(|outer| {
+        autoreleasepool(|_| {
+          outer.autorelease(new_object(class));
+        });
+      });
+    }));
+    assert!(result.is_err());
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 4);
+    autoreleasepool(|outer| {
+      autoreleasepool(|_| {});
+      outer.autorelease(new_object(class));
+    });
+    assert_eq!(DEALLOCS.load(Ordering::Relaxed), 5);
+  }
+
+  #[test]
+  fn autoreleased_return_values() {
+    let class = register_class("MockAutoreleasedReturnValue", None);
+
+    let object = new_object(class);
+    autoreleasepool(|_| {
+      let returned = unsafe { crate::__objrs::autorelease_return_value(Some(object.clone())) };
+      let claimed: Option<Strong<Id>> =
+        unsafe { crate::__objrs::retain_autoreleased_return_value(returned) };
+      assert!(core::ptr::eq(&*claimed.unwrap(), &*object));
+      assert_eq!(retain_count(&object), 2);
+
+      let null: Option<Strong<Id>> =
+        unsafe { crate::__objrs::retain_autoreleased_return_value(core::ptr::null_mut()) };
+      assert!(null.is_none());
+    });
+    assert_eq!(retain_count(&object), 1);
+  }
+
+  #[test]
+  fn weak() {
+    let class = register_class("MockWeak", None);
+
+    let object = new_object(class);
+    let weak = Weak::from(object.clone());
+    assert_eq!(retain_count(&object), 1);
+    let retained = Weak::from(object.clone()).retain().unwrap();
+    assert_eq!(retain_count(&retained), 2);
+    drop(retained);
+
+    let mut clone = weak.clone();
+    assert!(clone == weak);
+    assert!(core::ptr::eq(&*clone.upgrade().unwrap(), &*object));
+    assert!(core::ptr::eq(&*weak.upgrade().unwrap(), &*object));
+    assert_eq!(retain_count(&object), 1);
+    clone.set(None);
+    assert!(clone.upgrade().is_none());
+    assert!(clone != weak);
+    assert!(clone == Weak::new());
+    clone.set(Some(&object));
+    assert!(clone == weak);
+
+    drop(object);
+    assert!(clone.upgrade().is_none());
+    assert!(clone == Weak::new());
+    assert!(weak.retain().is_none());
+
+    let object = new_object(class);
+    let ptr = &*object as *const Id as *mut Id;
+    let mut location = null_mut();
+    unsafe {
+      assert_eq!(objc_initWeak(&mut location, ptr), ptr);
+      assert_eq!(location, ptr);
+      assert!(objc_storeWeak(&mut location, null_mut()).is_null());
+      assert!(objc_loadWeakRetained(&mut location).is_null());
+      assert_eq!(objc_storeWeak(&mut location, ptr), ptr);
+      drop(object);
+      assert!(location.is_null());
+      objc_destroyWeak(&mut location);
+    }
+  }
+
+  #[test]
+  fn weak_cell() {
+    let class = register_class("MockWeakCell", None);
+
+    let object = new_object(class);
+    let cell = Box::pin(WeakCell::<Id>::new());
+    assert!(cell.load().is_none());
+    cell.as_ref().store(Some(&*object));
+    assert!(core::ptr::eq(&*cell.load().unwrap(), &*object));
+    assert_eq!(retain_count(&object), 1);
+
+    let mut copy = Box::new(MaybeUninit::<WeakCell<Id>>::uninit());
+    let mut moved = Box::new(MaybeUninit::<WeakCell<Id>>::uninit());
+    unsafe {
+      WeakCell::init_copy(copy.as_mut_ptr(), cell.as_ref());
+      WeakCell::init_move(moved.as_mut_ptr(), Pin::new_unchecked(&mut *copy.as_mut_ptr()));
+      assert!((*copy.as_ptr()).load().is_none());
+      assert!(core::ptr::eq(&*(*moved.as_ptr()).load().unwrap(), &*object));
+
+      drop(object);
+      assert!(cell.load().is_none());
+      assert!((*moved.as_ptr()).load().is_none());
+      core::ptr::drop_in_place(copy.as_mut_ptr());
+      core::ptr::drop_in_place(moved.as_mut_ptr());
+    }
+  }
+
+  #[test]
+  fn selectors() {
+    unsafe {
+      let sel = runtime::sel_registerName(CStr::new("mockSelector:\0"));
+      assert!(core::ptr::eq(sel, runtime::sel_getUid(CStr::new("mockSelector:\0"))));
+      assert_eq!(runtime::sel_getName(sel).len(), 13);
+      assert_eq!(sel_name(sel), sel_name(selector!("mockSelector:")));
+      assert!(core::ptr::eq(Sel::register(CStr::new("mockSelector:\0")), sel));
+      assert_eq!(sel.name().to_str(), Ok("mockSelector:"));
+    }
+  }
+
+  #[cfg(all(any(target_arch = "x86_64", target_arch = "aarch64"), not(target_vendor = "apple")))]
+  #[test]
+  fn message_send() {
+    extern "C" fn add(this: &Id, _sel: &Sel, a: i32, b: f64) -> f64 {
+      return retain_count(this) as f64 + a as f64 + b;
+    }
+    extern "C" fn name(_this: &Class, _sel: &Sel) -> *const libc::c_char {
+      return b"MockSubclass\0".as_ptr() as *const _;
+    }
+    extern "C" fn description(_this: &Id, _sel: &Sel) -> *const libc::c_char {
+      return b"root\0".as_ptr() as *const _;
+    }
+
+    let root = register_class("MockRoot", None);
+    let subclass = register_class("MockSubclass", Some(root));
+    add_method(root, selector!("description"), description as *const (), "*16@0:8");
+    add_method(subclass, selector!("add:to:"), add as *const (), "d28@0:8i16d20");
+    add_class_method(subclass, selector!("name"), name as *const (), "*16@0:8");
+    assert!(core::ptr::eq(class_named("MockSubclass"), subclass));
+
+    let object = new_object(subclass);
+    unsafe {
+      let send_add: unsafe extern "C" fn(&Id, &Sel, i32, f64) -> f64 =
+        core::mem::transmute(runtime::objc_msgSend as *const ());
+      assert_eq!(send_add(&object, selector!("add:to:"), 2, 0.5), 3.5);
+
+      let send_name: unsafe extern "C" fn(&Class, &Sel) -> *const libc::c_char =
+        core::mem::transmute(runtime::objc_msgSend as *const ());
+      assert_eq!(cstring_bytes(send_name(subclass, selector!("name"))), b"MockSubclass\0");
+
+      // Root instance methods are inherited by subclasses and by classes themselves.
+      let send_description: unsafe extern "C" fn(*const Id, &Sel) -> *const libc::c_char =
+        core::mem::transmute(runtime::objc_msgSend as *const ());
+      let description = send_description(&*object, selector!("description"));
+      assert_eq!(cstring_bytes(description), b"root\0");
+      let description = send_description(&**subclass, selector!("description"));
+      assert_eq!(cstring_bytes(description), b"root\0");
+      assert!(send_description(null(), selector!("description")).is_null());
+
+      let sup = objc_super {
+        receiver: NonNull::from(&*object),
+        super_class: subclass,
+      };
+      let send_super: unsafe extern "C" fn(&objc_super, &Sel) -> *const libc::c_char =
+        core::mem::transmute(runtime::objc_msgSendSuper2 as *const ());
+      assert_eq!(cstring_bytes(send_super(&sup, selector!("description"))), b"root\0");
+
+      assert!(runtime::class_respondsToSelector(subclass, selector!("add:to:")));
+      assert!(!runtime::class_respondsToSelector(root, selector!("add:to:")));
+      let method = NonNull::new(runtime::class_getInstanceMethod(
+        subclass as *const Class as *mut Class,
+        selector!("add:to:"),
+      ))
+      .unwrap();
+      assert_eq!(runtime::method_getNumberOfArguments(method), 4);
+      assert_eq!(runtime::method_getTypeEncoding(method).unwrap().len(), 13);
+    }
+  }
+}
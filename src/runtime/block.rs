@@ -0,0 +1,21 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// See https://opensource.apple.com/source/libclosure/libclosure-73/Block.h and
+// https://opensource.apple.com/source/libclosure/libclosure-73/Block_private.h
+
+extern crate libc;
+
+// The blocks runtime is part of libSystem on Apple platforms (and part of libobjc2 for GNUstep), so
+// it doesn't need to be linked separately.
+extern "C" {
+  // These are declared as `void *_NSConcreteStackBlock[32]` (etc.). Only their addresses are used.
+  pub static _NSConcreteStackBlock: [usize; 32];
+  pub static _NSConcreteMallocBlock: [usize; 32];
+  pub static _NSConcreteGlobalBlock: [usize; 32];
+
+  pub fn _Block_copy(block: *const libc::c_void) -> *mut libc::c_void;
+  pub fn _Block_release(block: *const libc::c_void);
+}
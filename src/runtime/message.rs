@@ -10,7 +10,7 @@ use crate::runtime::objc;
 // TODO: these need #[unwind] since FFI code may throw an Objective-C exception. We probably need
 // #[unwind] on the Rust (msg_recv) method implementations too. Make sure that the casted function
 // pointers also allow unwinding.
-#[link(name = "objc")]
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   pub fn objc_msgSend();
   pub fn objc_msgSendSuper();
@@ -3,18 +3,24 @@
 // may not be copied, modified, or distributed except according to those terms. For copies of these
 // licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
 
+mod block;
 mod message;
 mod objc;
 mod objc_abi;
 mod objc_exception;
+#[cfg(feature = "runtime-gnustep")]
+mod objc_gnustep;
 mod objc_internal;
 mod objc_runtime_new;
 mod runtime;
 
+pub use crate::runtime::block::*;
 pub use crate::runtime::message::*;
 pub use crate::runtime::objc::*;
 pub use crate::runtime::objc_abi::*;
 pub use crate::runtime::objc_exception::*;
+#[cfg(feature = "runtime-gnustep")]
+pub use crate::runtime::objc_gnustep::*;
 pub use crate::runtime::objc_internal::*;
 pub use crate::runtime::objc_runtime_new::*;
 pub use crate::runtime::runtime::*;
@@ -26,9 +32,12 @@ pub mod __objrs {
     const CLASS_NAME_CSTR: &'static str;
 
     const HAS_IVARS: bool;
+    const HAS_PROPERTIES: bool;
+    const HAS_CLASS_PROPERTIES: bool;
     const IS_ROOT_CLASS: bool;
     const REQUIRES_CXX_CONSTRUCT: bool;
     const REQUIRES_CXX_DESTRUCT: bool;
+    const IS_EXTERN: bool;
 
     type FIELDS;
 
@@ -42,6 +51,10 @@ pub mod __objrs {
 
     extern "C" fn cxx_construct(this: *mut Self, _: usize) -> *mut Self;
     extern "C" fn cxx_destruct(this: *mut Self, _: usize);
+
+    // The list of protocols the class adopts. Extern classes don't have one (the protocols they
+    // adopt with `impl Protocol for Class` are recorded in categories instead).
+    fn protocol_list() -> Option<&'static crate::__objrs::ClassProtocolList>;
   }
 
   pub unsafe trait RootClass: Class {}
@@ -14,6 +14,8 @@ extern "C" {
   type objc_object;
   type objc_selector;
   type objc_method;
+  type objc_ivar;
+  type objc_property;
 
   // This type is just for `Imp`. Alternatively, `unsafe extern "C" fn()` could be used, but Rust's
   // function pointers are nonnullable, and I'm concerned that using it for `Imp` would lead to
@@ -79,7 +81,16 @@ pub struct Imp(opaque_function);
 #[repr(transparent)]
 pub struct Method(objc_method);
 
-#[link(name = "objc")]
+#[repr(transparent)]
+pub struct Ivar(objc_ivar);
+
+#[repr(transparent)]
+pub struct Property(objc_property);
+
+#[repr(transparent)]
+pub struct Protocol(Id);
+
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   pub fn sel_isMapped(sel: &Sel) -> bool;
   pub fn sel_getName(sel: &Sel) -> &'static CStr;
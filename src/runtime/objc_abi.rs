@@ -5,9 +5,10 @@
 
 // See https://github.com/opensource-apple/objc4/blob/master/runtime/objc-abi.h
 
+use crate::runtime::objc;
 use crate::runtime::objc_runtime_new;
 
-#[link(name = "objc")]
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   #[link_name = "_objc_empty_cache"]
   pub static _objc_empty_cache: objc_runtime_new::bucket_t;
@@ -16,6 +17,23 @@ extern "C" {
   pub fn objc_msgSendSuper2();
   #[cfg(not(target_arch = "aarch64"))]
   pub fn objc_msgSendSuper2_stret();
+
+  // Property accessors used by synthesized getters and setters. `offset` is the offset of the ivar
+  // from `this`. `should_copy` is 0 (retain), 1 (copy), or 2 (mutableCopy).
+  pub fn objc_getProperty(
+    this: *mut objc::Id,
+    sel: &objc::Sel,
+    offset: isize,
+    atomic: bool,
+  ) -> *mut objc::Id;
+  pub fn objc_setProperty(
+    this: *mut objc::Id,
+    sel: &objc::Sel,
+    offset: isize,
+    value: *mut objc::Id,
+    atomic: bool,
+    should_copy: i8,
+  );
 }
 
 #[repr(C)]
@@ -7,11 +7,10 @@
 
 extern crate libc;
 
-use crate::arc;
 use crate::runtime::objc;
 
-#[link(name = "objc")]
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   #[unwind(allowed)]
-  pub fn objc_exception_throw(exception: arc::Auto<objc::Id>) -> !;
+  pub fn objc_exception_throw(exception: *mut objc::Id) -> !;
 }
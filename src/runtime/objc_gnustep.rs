@@ -0,0 +1,112 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// See https://github.com/gnustep/libobjc2/blob/master/loader.c and clang's CGObjCGNUstep2.
+//
+// With libobjc2's v2 ABI, each loaded image registers its Objective-C metadata by calling
+// `__objc_load` with the bounds of the metadata sections. The linker defines `__start_SECTION` and
+// `__stop_SECTION` symbols for each section, so one call covers every crate's metadata in the image.
+// objrs makes that call from a constructor in `.init_array`.
+//
+// objrs only emits selector and class references. It doesn't emit v2 class, category, or protocol
+// metadata, so `__objc_classes` only holds a null placeholder and defining classes is unsupported.
+
+extern crate libc;
+
+use crate::runtime::objc;
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct objc_selector_v2 {
+  pub name: *const libc::c_char,
+  pub types: *const libc::c_char,
+}
+
+unsafe impl Sync for objc_selector_v2 {}
+
+#[repr(C)]
+#[allow(non_camel_case_types)]
+pub struct objc_init {
+  pub version: u64,
+  pub sel_begin: *const objc_selector_v2,
+  pub sel_end: *const objc_selector_v2,
+  pub cls_begin: *const *mut objc::Class,
+  pub cls_end: *const *mut objc::Class,
+  pub cls_ref_begin: *const *mut objc::Class,
+  pub cls_ref_end: *const *mut objc::Class,
+  // objrs doesn't emit categories, protocols, class aliases, or constant strings, so the remaining
+  // fields are always null.
+  pub cat_begin: *const libc::c_void,
+  pub cat_end: *const libc::c_void,
+  pub proto_begin: *const libc::c_void,
+  pub proto_end: *const libc::c_void,
+  pub proto_ref_begin: *const libc::c_void,
+  pub proto_ref_end: *const libc::c_void,
+  pub alias_begin: *const libc::c_void,
+  pub alias_end: *const libc::c_void,
+  pub strings_begin: *const libc::c_void,
+  pub strings_end: *const libc::c_void,
+}
+
+#[link(name = "objc")]
+extern "C" {
+  pub fn __objc_load(init: *mut objc_init);
+}
+
+extern "C" {
+  static __start___objc_selectors: objc_selector_v2;
+  static __stop___objc_selectors: objc_selector_v2;
+  static __start___objc_classes: *mut objc::Class;
+  static __stop___objc_classes: *mut objc::Class;
+  static __start___objc_class_refs: *mut objc::Class;
+  static __stop___objc_class_refs: *mut objc::Class;
+}
+
+// Null placeholders guarantee that each section exists (so the linker defines its start and stop
+// symbols), even if no crate in the image uses it. libobjc2 skips null entries.
+#[link_section = "__objc_selectors"]
+#[used]
+static NULL_SELECTOR: objc_selector_v2 = objc_selector_v2 {
+  name: 0 as *const _,
+  types: 0 as *const _,
+};
+
+#[link_section = "__objc_classes"]
+#[used]
+static NULL_CLASS: usize = 0;
+
+#[link_section = "__objc_class_refs"]
+#[used]
+static NULL_CLASS_REF: usize = 0;
+
+static mut INIT: objc_init = unsafe {
+  objc_init {
+    version: 0,
+    sel_begin: &__start___objc_selectors,
+    sel_end: &__stop___objc_selectors,
+    cls_begin: &__start___objc_classes,
+    cls_end: &__stop___objc_classes,
+    cls_ref_begin: &__start___objc_class_refs,
+    cls_ref_end: &__stop___objc_class_refs,
+    cat_begin: 0 as *const _,
+    cat_end: 0 as *const _,
+    proto_begin: 0 as *const _,
+    proto_end: 0 as *const _,
+    proto_ref_begin: 0 as *const _,
+    proto_ref_end: 0 as *const _,
+    alias_begin: 0 as *const _,
+    alias_end: 0 as *const _,
+    strings_begin: 0 as *const _,
+    strings_end: 0 as *const _,
+  }
+};
+
+extern "C" fn load() {
+  unsafe { __objc_load(&mut INIT) };
+}
+
+#[link_section = ".init_array"]
+#[used]
+static LOAD: extern "C" fn() = load;
@@ -12,18 +12,25 @@ extern crate libc;
 use crate::arc;
 use crate::runtime::objc;
 
-#[link(name = "objc")]
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   pub fn objc_autoreleasePoolPush() -> *mut libc::c_void;
   pub fn objc_autoreleasePoolPop(pool: *mut libc::c_void);
 
   pub fn objc_retain(obj: *mut objc::Id) -> Option<arc::Strong<objc::Id>>;
   pub fn objc_release(obj: Option<arc::Strong<objc::Id>>);
-  pub fn objc_autorelease(obj: Option<arc::Strong<objc::Id>>) -> Option<arc::Auto<objc::Id>>;
-  pub fn objc_retainAutorelease(obj: *mut objc::Id) -> Option<arc::Auto<objc::Id>>;
+  // These return autoreleased objects, so they return raw pointers rather than `arc::Auto` (whose
+  // lifetime depends on the caller's autorelease pool).
+  pub fn objc_autorelease(obj: Option<arc::Strong<objc::Id>>) -> *mut objc::Id;
+  pub fn objc_retainAutorelease(obj: *mut objc::Id) -> *mut objc::Id;
+  pub fn objc_retainAutoreleasedReturnValue(obj: *mut objc::Id) -> Option<arc::Strong<objc::Id>>;
+  pub fn objc_autoreleaseReturnValue(obj: Option<arc::Strong<objc::Id>>) -> *mut objc::Id;
 
   pub fn objc_initWeak(weak: &mut *mut objc::Id, value: *mut objc::Id) -> *mut objc::Id;
   pub fn objc_destroyWeak(weak: &mut *mut objc::Id);
-  pub fn objc_loadWeak(weak: &mut *mut objc::Id) -> Option<arc::Auto<objc::Id>>;
+  pub fn objc_storeWeak(weak: &mut *mut objc::Id, value: *mut objc::Id) -> *mut objc::Id;
+  pub fn objc_loadWeak(weak: &mut *mut objc::Id) -> *mut objc::Id;
   pub fn objc_loadWeakRetained(weak: &mut *mut objc::Id) -> Option<arc::Strong<objc::Id>>;
+  pub fn objc_copyWeak(to: &mut *mut objc::Id, from: &mut *mut objc::Id);
+  pub fn objc_moveWeak(to: &mut *mut objc::Id, from: &mut *mut objc::Id);
 }
@@ -85,6 +85,8 @@ pub struct property_list_t {
 #[repr(C)]
 #[allow(non_camel_case_types)]
 pub struct protocol_t {
+  // protocol_t inherits from objc_object.
+  pub isa: *mut objc::Class,
   pub mangled_name: *const libc::c_char,
   pub protocols: *mut usize,
   pub instance_methods: *mut method_list_t,
@@ -96,6 +98,7 @@ pub struct protocol_t {
   pub flags: u32,
   pub extended_method_types: *mut *const libc::c_char,
   pub demangled_name: *const libc::c_char,
+  pub class_properties: *mut property_list_t,
 }
 
 #[repr(C)]
@@ -12,19 +12,62 @@ use crate::runtime::objc;
 use crate::CStr;
 use core::ptr::NonNull;
 
-#[link(name = "objc")]
+#[cfg_attr(not(feature = "mock-runtime"), link(name = "objc"))]
 extern "C" {
   pub fn objc_getClass(name: &CStr) -> *mut objc::Class;
+  pub fn objc_getProtocol(name: &CStr) -> Option<&'static objc::Protocol>;
+  // The objc_copy*List and class_copy*List functions return an array that must be freed with free().
+  pub fn objc_copyClassList(out_count: *mut libc::c_uint) -> *mut &'static objc::Class;
+
+  pub fn objc_allocateClassPair(
+    superclass: Option<&objc::Class>,
+    name: &CStr,
+    extra_bytes: usize,
+  ) -> *mut objc::Class;
+  pub fn objc_registerClassPair(class: *mut objc::Class);
+  pub fn objc_disposeClassPair(class: *mut objc::Class);
 
   pub fn class_getName(class: *const objc::Class) -> Option<&'static CStr>;
   pub fn class_getSuperclass(name: *mut objc::Class) -> *mut objc::Class;
   pub fn class_respondsToSelector(class: *const objc::Class, sel: &objc::Sel) -> bool;
   pub fn class_getClassMethod(class: *mut objc::Class, sel: &objc::Sel) -> *mut objc::Method;
   pub fn class_getInstanceMethod(class: *mut objc::Class, sel: &objc::Sel) -> *mut objc::Method;
+  pub fn class_addIvar(
+    class: *mut objc::Class,
+    name: &CStr,
+    size: usize,
+    alignment: u8,
+    types: &CStr,
+  ) -> bool;
+  pub fn class_addMethod(
+    class: *mut objc::Class,
+    name: &objc::Sel,
+    imp: &objc::Imp,
+    types: &CStr,
+  ) -> bool;
+  pub fn class_addProtocol(class: *mut objc::Class, protocol: &objc::Protocol) -> bool;
+  pub fn class_copyMethodList(
+    class: *const objc::Class,
+    out_count: *mut libc::c_uint,
+  ) -> *mut &'static objc::Method;
+  pub fn class_copyIvarList(
+    class: *const objc::Class,
+    out_count: *mut libc::c_uint,
+  ) -> *mut &'static objc::Ivar;
+  pub fn class_copyPropertyList(
+    class: *const objc::Class,
+    out_count: *mut libc::c_uint,
+  ) -> *mut &'static objc::Property;
+  pub fn class_copyProtocolList(
+    class: *const objc::Class,
+    out_count: *mut libc::c_uint,
+  ) -> *mut &'static objc::Protocol;
 
   pub fn object_getClass(object: *mut objc::Id) -> *mut objc::Class;
   pub fn object_isClass(object: *const objc::Id) -> bool;
 
+  pub fn method_getName(method: NonNull<objc::Method>) -> &'static objc::Sel;
+  pub fn method_getImplementation(method: NonNull<objc::Method>) -> &'static objc::Imp;
   pub fn method_getNumberOfArguments(method: NonNull<objc::Method>) -> libc::c_uint;
   pub fn method_getTypeEncoding(method: NonNull<objc::Method>) -> Option<&'static CStr>;
 
@@ -41,4 +84,13 @@ extern "C" {
     dst: NonNull<CStr>,
     dst_len: usize,
   );
+
+  pub fn ivar_getName(ivar: &objc::Ivar) -> Option<&'static CStr>;
+  pub fn ivar_getTypeEncoding(ivar: &objc::Ivar) -> Option<&'static CStr>;
+  pub fn ivar_getOffset(ivar: &objc::Ivar) -> isize;
+
+  pub fn property_getName(property: &objc::Property) -> &'static CStr;
+  pub fn property_getAttributes(property: &objc::Property) -> Option<&'static CStr>;
+
+  pub fn protocol_getName(protocol: &objc::Protocol) -> &'static CStr;
 }
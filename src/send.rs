@@ -0,0 +1,493 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Dynamic message sending, for selectors that aren't known at compile time. #[objrs(selector)]
+// generates a msg_send function for each selector, but `Id::send` sends any selector with any
+// arguments (as long as their types implement TypeEncoding).
+//
+// The objc_msgSend variant depends on how the return type is returned by the C ABI, which is worked
+// out from the return type's encoding (its fields and their layout) and the target OS:
+//   - objc_msgSend_stret is used for aggregates that are returned in memory (i.e. via a hidden
+//     pointer argument). It doesn't exist on aarch64, where the struct return pointer has its own
+//     register.
+//   - objc_msgSend_fpret is used on x86 for floating point return values (which are returned on the
+//     x87 stack). On x86_64 it's only used for `long double`, which Rust doesn't have.
+//   - objc_msgSend is used for everything else.
+//
+// See https://developer.apple.com/documentation/objectivec/1456712-objc_msgsend
+
+extern crate core;
+
+#[cfg(any(not(target_arch = "aarch64"), feature = "verify-signatures"))]
+use crate::__objrs::type_encoding_bytes;
+#[cfg(not(target_arch = "aarch64"))]
+use crate::encoding::{Encoding, Kind};
+use crate::runtime::{self, Id, Sel};
+use crate::{CStr, TypeEncoding};
+
+/// The arguments of a dynamically sent message (see `Id::send`). This is implemented for tuples (of
+/// up to 8 elements) whose elements implement `TypeEncoding`. Use `()` for messages that don't take
+/// any arguments, and `(arg,)` for messages that take one argument.
+pub unsafe trait MessageArguments: Sized {
+  #[doc(hidden)]
+  unsafe fn invoke<R>(self, msg_send: unsafe extern "C" fn(), receiver: *const Id, sel: &Sel) -> R;
+
+  // Verifies the argument and return types against the receiver's method (if it has one).
+  #[cfg(feature = "verify-signatures")]
+  #[doc(hidden)]
+  unsafe fn verify<R: TypeEncoding>(receiver: *const Id, sel: &Sel);
+}
+
+macro_rules! message_arguments {
+  ($($arg:ident $index:tt),*) => {
+    unsafe impl<$($arg: TypeEncoding),*> MessageArguments for ($($arg,)*) {
+      #[inline(always)]
+      unsafe fn invoke<R>(
+        self,
+        msg_send: unsafe extern "C" fn(),
+        receiver: *const Id,
+        sel: &Sel,
+      ) -> R {
+        let msg_send: unsafe extern "C" fn(*const Id, &Sel, $($arg),*) -> R =
+          core::mem::transmute(msg_send);
+        return msg_send(receiver, sel, $(self.$index),*);
+      }
+
+      #[cfg(feature = "verify-signatures")]
+      unsafe fn verify<R: TypeEncoding>(receiver: *const Id, sel: &Sel) {
+        let return_bytes = <R as TypeEncoding>::BYTES;
+        let arg_bytes = ($(<$arg as TypeEncoding>::BYTES,)*);
+        let expected = [
+          type_encoding_bytes(&return_bytes),
+          $(type_encoding_bytes(&arg_bytes.$index)),*
+        ];
+        crate::verify::verify_send(receiver, sel, &expected);
+      }
+    }
+  };
+}
+
+message_arguments!();
+message_arguments!(A 0);
+message_arguments!(A 0, B 1);
+message_arguments!(A 0, B 1, C 2);
+message_arguments!(A 0, B 1, C 2, D 3);
+message_arguments!(A 0, B 1, C 2, D 3, E 4);
+message_arguments!(A 0, B 1, C 2, D 3, E 4, F 5);
+message_arguments!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+message_arguments!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+// How the C ABI returns a value, which determines the objc_msgSend variant.
+#[cfg(not(target_arch = "aarch64"))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ReturnClass {
+  // In registers (or not at all, for `void` and empty structs).
+  Registers,
+  // On the x87 floating point stack.
+  #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+  X87,
+  // In memory, via a hidden struct return pointer.
+  Memory,
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn is_aggregate(encoding: Encoding) -> bool {
+  return match encoding.kind() {
+    Kind::Struct(..) | Kind::Union(..) | Kind::Array(..) => true,
+    _ => false,
+  };
+}
+
+// The System V x86_64 ABI classifies each eightbyte of a small aggregate by the fields that overlap
+// it, and returns the aggregate in memory if any eightbyte is MEMORY (e.g. because a field is
+// unaligned) or mixes x87 and non-x87 data. See section 3.2.3 of the ABI.
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EightbyteClass {
+  NoClass,
+  Integer,
+  Sse,
+  X87,
+  X87Up,
+  Memory,
+}
+
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+impl EightbyteClass {
+  fn merge(self, other: EightbyteClass) -> EightbyteClass {
+    use self::EightbyteClass::*;
+    return match (self, other) {
+      _ if self == other => self,
+      (NoClass, class) | (class, NoClass) => class,
+      (Memory, _) | (_, Memory) => Memory,
+      (Integer, _) | (_, Integer) => Integer,
+      (X87, _) | (_, X87) | (X87Up, _) | (_, X87Up) => Memory,
+      _ => Sse,
+    };
+  }
+
+  // Merges `class` into the class of `classes[eightbyte]`. Returns `None` if the eightbyte is out of
+  // bounds (i.e. the encoding doesn't fit in the type).
+  fn merge_into(
+    classes: &mut [EightbyteClass; 2],
+    eightbyte: usize,
+    class: EightbyteClass,
+  ) -> Option<()> {
+    let merged = classes.get(eightbyte)?.merge(class);
+    classes[eightbyte] = merged;
+    return Some(());
+  }
+}
+
+// Merges the classes of the eightbytes that `encoding` occupies at `offset` into `classes`. Returns
+// `None` if the layout can't be determined from the encoding (e.g. bitfields).
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+fn classify_eightbytes(
+  encoding: Encoding,
+  offset: usize,
+  classes: &mut [EightbyteClass; 2],
+) -> Option<()> {
+  let size = encoding.size()?;
+  if offset % encoding.align()? != 0 {
+    classes[0] = EightbyteClass::Memory;
+    return Some(());
+  }
+  match encoding.kind() {
+    Kind::Array(count, element) => {
+      let element = element.get();
+      let element_size = element.size()?;
+      for i in 0..count {
+        classify_eightbytes(element, offset + i * element_size, classes)?;
+      }
+    }
+    Kind::Struct(_, fields) => {
+      let mut field_offset = offset;
+      for field in fields? {
+        let align = field.encoding.align()?;
+        field_offset = (field_offset + align - 1) / align * align;
+        classify_eightbytes(field.encoding, field_offset, classes)?;
+        field_offset += field.encoding.size()?;
+      }
+    }
+    Kind::Union(_, fields) => {
+      for field in fields? {
+        classify_eightbytes(field.encoding, offset, classes)?;
+      }
+    }
+    Kind::LongDouble => {
+      EightbyteClass::merge_into(classes, offset / 8, EightbyteClass::X87)?;
+      EightbyteClass::merge_into(classes, offset / 8 + 1, EightbyteClass::X87Up)?;
+    }
+    Kind::Float | Kind::Double => {
+      EightbyteClass::merge_into(classes, offset / 8, EightbyteClass::Sse)?
+    }
+    _ => {
+      for eightbyte in offset / 8..(offset + size + 7) / 8 {
+        EightbyteClass::merge_into(classes, eightbyte, EightbyteClass::Integer)?;
+      }
+    }
+  }
+  return Some(());
+}
+
+// Classifies a return value with the given encoding and size (the type's real size, which is smaller
+// than the encoding's if the type is packed).
+#[cfg(all(target_arch = "x86_64", not(target_os = "windows")))]
+fn return_class(encoding: Option<Encoding>, size: usize) -> ReturnClass {
+  // Anything larger than two eightbytes is returned in memory.
+  if size > 16 {
+    return ReturnClass::Memory;
+  }
+  let encoding = match encoding {
+    Some(encoding) => encoding,
+    None => return ReturnClass::Registers,
+  };
+  if !is_aggregate(encoding) {
+    if encoding.kind() == Kind::LongDouble {
+      return ReturnClass::X87;
+    }
+    return ReturnClass::Registers;
+  }
+  match encoding.size() {
+    // A packed aggregate's fields aren't all at their natural offsets, so some are unaligned.
+    Some(natural_size) if size < natural_size => return ReturnClass::Memory,
+    Some(_) => (),
+    None => return ReturnClass::Registers,
+  }
+  let mut classes = [EightbyteClass::NoClass; 2];
+  if classify_eightbytes(encoding, 0, &mut classes).is_none() {
+    return ReturnClass::Registers;
+  }
+  return match classes {
+    [EightbyteClass::X87, EightbyteClass::X87Up] => ReturnClass::X87,
+    [EightbyteClass::Memory, _] | [_, EightbyteClass::Memory] => ReturnClass::Memory,
+    // X87UP is only valid after X87.
+    [_, EightbyteClass::X87Up] => ReturnClass::Memory,
+    _ => ReturnClass::Registers,
+  };
+}
+
+// The Microsoft x64 ABI returns aggregates of 1, 2, 4, or 8 bytes in RAX, and everything else in
+// memory.
+#[cfg(all(target_arch = "x86_64", target_os = "windows"))]
+fn return_class(encoding: Option<Encoding>, size: usize) -> ReturnClass {
+  if encoding.map_or(false, is_aggregate) && !(size == 1 || size == 2 || size == 4 || size == 8) {
+    return ReturnClass::Memory;
+  }
+  return ReturnClass::Registers;
+}
+
+// On Darwin and Windows, i386 aggregates whose size and fields (recursively) are all 1, 2, 4, or 8
+// bytes are returned in registers. This mirrors clang's `shouldReturnTypeInRegister`. Fields whose
+// size can't be determined from the encoding (e.g. bitfields) are assumed to be register sized.
+#[cfg(all(target_arch = "x86", any(target_vendor = "apple", target_os = "windows")))]
+fn is_register_sized(encoding: Encoding) -> bool {
+  match encoding.size() {
+    Some(1) | Some(2) | Some(4) | Some(8) | None => (),
+    Some(_) => return false,
+  }
+  return match encoding.kind() {
+    Kind::Array(_, element) => is_register_sized(element.get()),
+    Kind::Struct(_, Some(mut fields)) | Kind::Union(_, Some(mut fields)) => {
+      fields.all(|field| field.encoding.size() == Some(0) || is_register_sized(field.encoding))
+    }
+    _ => true,
+  };
+}
+
+// Returns true if the encoding is a `float` or `double`, or a struct (or array) that wraps just one.
+#[cfg(all(target_arch = "x86", target_vendor = "apple"))]
+fn single_float_element(encoding: Encoding) -> bool {
+  return match encoding.kind() {
+    Kind::Float | Kind::Double => true,
+    Kind::Array(1, element) => single_float_element(element.get()),
+    Kind::Struct(_, Some(mut fields)) => match (fields.next(), fields.next()) {
+      (Some(field), None) => single_float_element(field.encoding),
+      _ => false,
+    },
+    _ => false,
+  };
+}
+
+#[cfg(target_arch = "x86")]
+fn return_class(encoding: Option<Encoding>, size: usize) -> ReturnClass {
+  let encoding = match encoding {
+    Some(encoding) => encoding,
+    None => return ReturnClass::Registers,
+  };
+  if !is_aggregate(encoding) {
+    return match encoding.kind() {
+      Kind::Float | Kind::Double | Kind::LongDouble => ReturnClass::X87,
+      _ => ReturnClass::Registers,
+    };
+  }
+
+  // Linux and the other System V targets return every aggregate in memory.
+  #[cfg(not(any(target_vendor = "apple", target_os = "windows")))]
+  {
+    let _ = size;
+    return ReturnClass::Memory;
+  }
+
+  #[cfg(any(target_vendor = "apple", target_os = "windows"))]
+  {
+    if size == 0 {
+      return ReturnClass::Registers;
+    }
+    // The size is checked separately since the encoding doesn't know if the type is packed.
+    if !(size == 1 || size == 2 || size == 4 || size == 8) || !is_register_sized(encoding) {
+      return ReturnClass::Memory;
+    }
+    // Darwin returns structs that wrap a single `float` or `double` on the x87 stack.
+    #[cfg(target_vendor = "apple")]
+    {
+      if single_float_element(encoding) {
+        return ReturnClass::X87;
+      }
+    }
+    return ReturnClass::Registers;
+  }
+}
+
+// AAPCS (and Apple's armv7 ABI) returns aggregates larger than a word in memory.
+#[cfg(target_arch = "arm")]
+fn return_class(encoding: Option<Encoding>, size: usize) -> ReturnClass {
+  if encoding.map_or(false, is_aggregate) && size > 4 {
+    return ReturnClass::Memory;
+  }
+  return ReturnClass::Registers;
+}
+
+// Picks the objc_msgSend variant for a message that returns an `R`.
+#[inline]
+fn msg_send_fn<R: TypeEncoding>() -> unsafe extern "C" fn() {
+  #[cfg(target_arch = "aarch64")]
+  {
+    return runtime::objc_msgSend;
+  }
+
+  #[cfg(not(target_arch = "aarch64"))]
+  {
+    let return_bytes = <R as TypeEncoding>::BYTES;
+    let bytes = type_encoding_bytes(&return_bytes);
+    let encoding = core::str::from_utf8(bytes).ok().and_then(|s| crate::encoding::parse(s).ok());
+    return match return_class(encoding, core::mem::size_of::<R>()) {
+      ReturnClass::Registers => runtime::objc_msgSend,
+      #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+      ReturnClass::X87 => runtime::objc_msgSend_fpret,
+      ReturnClass::Memory => runtime::objc_msgSend_stret,
+    };
+  }
+}
+
+impl Sel {
+  /// Registers a selector with the runtime (or looks it up, if it's already registered). Use
+  /// `selector!` instead for selectors that are known at compile time.
+  #[inline]
+  pub fn register(name: &CStr) -> &'static Sel {
+    return unsafe { runtime::sel_registerName(name) };
+  }
+
+  #[inline]
+  pub fn name(&self) -> &'static CStr {
+    return unsafe { runtime::sel_getName(self) };
+  }
+}
+
+impl<T: ?Sized> Id<T> {
+  /// Sends the message `sel` with `args` (a tuple of the message's arguments) to this object (or to
+  /// a class, since `Class` derefs to `Id`), and returns the result as an `R`. The right
+  /// objc_msgSend variant is picked based on `R`'s type and layout.
+  ///
+  /// If the `verify-signatures` feature is enabled, the argument and return types are checked
+  /// against the type encoding of the receiver's method before each send, and a mismatch panics.
+  ///
+  /// # Safety
+  ///
+  /// The receiver's method must take arguments of the types in `A` and return an `R`. The return
+  /// value's ownership must match the selector's method family: `Strong` may only be used for
+  /// selectors that return +1 objects (i.e. the `alloc`, `copy`, `init`, `mutableCopy`, and `new`
+  /// families). Use `Auto` or a raw pointer for other selectors.
+  #[inline]
+  pub unsafe fn send<A: MessageArguments, R: TypeEncoding>(&self, sel: &Sel, args: A) -> R {
+    let receiver = self as *const Id<T> as *const Id;
+    #[cfg(feature = "verify-signatures")]
+    A::verify::<R>(receiver, sel);
+    return args.invoke(msg_send_fn::<R>(), receiver, sel);
+  }
+}
+
+#[cfg(all(
+  test,
+  feature = "mock-runtime",
+  any(target_arch = "x86_64", target_arch = "aarch64"),
+  not(target_vendor = "apple"),
+))]
+mod tests {
+  use super::*;
+  use crate::mock::{add_class_method, add_method, new_object, register_class};
+  use crate::runtime::Class;
+  use crate::selector;
+
+  #[test]
+  fn dynamic_send() {
+    // Large enough to be returned in memory (i.e. with objc_msgSend_stret on x86_64).
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Triple {
+      a: u64,
+      b: u64,
+      c: u64,
+    }
+    crate::struct_type_encoding!(Triple { u64, u64, u64 });
+
+    // Only 16 bytes, but `b` is unaligned, so it's also returned in memory on x86_64.
+    #[repr(C, packed)]
+    #[derive(Clone, Copy)]
+    struct Packed {
+      a: u32,
+      b: u64,
+      c: u32,
+    }
+    crate::struct_type_encoding!(Packed { u32, u64, u32 });
+
+    extern "C" fn triple(_this: &Id, _sel: &Sel, value: u64) -> Triple {
+      return Triple {
+        a: value,
+        b: value * 2,
+        c: value * 3,
+      };
+    }
+    extern "C" fn packed(_this: &Id, _sel: &Sel, value: u64) -> Packed {
+      return Packed {
+        a: 1,
+        b: value,
+        c: 3,
+      };
+    }
+    extern "C" fn add(_this: &Id, _sel: &Sel, a: i32, b: f64) -> f64 {
+      return a as f64 + b;
+    }
+    extern "C" fn version(_this: &Class, _sel: &Sel) -> u8 {
+      return 3;
+    }
+
+    let class = register_class("MockDynamicSend", None);
+    add_method(class, selector!("triple:"), triple as *const (), "{Triple=QQQ}24@0:8Q16");
+    add_method(class, selector!("packed:"), packed as *const (), "{Packed=IQI}16@0:8Q16");
+    add_method(class, selector!("add:to:"), add as *const (), "d28@0:8i16d20");
+    add_class_method(class, selector!("version"), version as *const (), "C16@0:8");
+
+    let object = new_object(class);
+    unsafe {
+      let expected = Triple {
+        a: 2,
+        b: 4,
+        c: 6,
+      };
+      assert_eq!(object.send::<_, Triple>(selector!("triple:"), (2u64,)), expected);
+      let result = object.send::<_, Packed>(selector!("packed:"), (5u64,));
+      assert_eq!({ result.a }, 1);
+      assert_eq!({ result.b }, 5);
+      assert_eq!({ result.c }, 3);
+      assert_eq!(object.send::<_, f64>(selector!("add:to:"), (2i32, 0.5f64)), 2.5);
+      assert_eq!(class.send::<_, u8>(selector!("version"), ()), 3);
+    }
+  }
+
+  #[cfg(target_arch = "x86_64")]
+  #[test]
+  fn return_classes() {
+    let class = |encoding: &str, size: usize| {
+      return return_class(Some(crate::encoding::parse(encoding).unwrap()), size);
+    };
+    assert_eq!(class("Q", 8), ReturnClass::Registers);
+    assert_eq!(class("D", 16), ReturnClass::X87);
+    assert_eq!(class("{?=dQ}", 16), ReturnClass::Registers);
+    assert_eq!(class("{?=QQQ}", 24), ReturnClass::Memory);
+    assert_eq!(class("{?=[3f]}", 12), ReturnClass::Registers);
+    // A packed struct with an unaligned field.
+    assert_eq!(class("{?=IQI}", 16), ReturnClass::Memory);
+    // `long double` is only returned on the x87 stack if it's alone, and in memory otherwise.
+    assert_eq!(class("{?=D}", 16), ReturnClass::X87);
+    assert_eq!(class("(?=Di)", 16), ReturnClass::Memory);
+    // Bitfields don't have a layout, so they fall back to the size.
+    assert_eq!(class("{?=b3b5}", 4), ReturnClass::Registers);
+  }
+
+  #[cfg(feature = "verify-signatures")]
+  #[test]
+  #[should_panic(expected = "doesn't match the Rust signature")]
+  fn dynamic_send_verify() {
+    extern "C" fn add(_this: &Id, _sel: &Sel, a: i32, b: f64) -> f64 {
+      return a as f64 + b;
+    }
+
+    let class = register_class("MockDynamicSendVerify", None);
+    add_method(class, selector!("add:to:"), add as *const (), "d28@0:8i16d20");
+    let object = new_object(class);
+    unsafe { object.send::<_, f64>(selector!("add:to:"), (2.0f64, 0.5f64)) };
+  }
+}
@@ -66,6 +66,10 @@ macro_rules! transparent_generic_type_encoding {
       type Type = <$primitive_ty as TypeEncoding>::Type;
       const BYTES: Self::Type = <$primitive_ty as TypeEncoding>::BYTES;
     }
+    unsafe impl<$($bounds)*> ComponentTypeEncoding for $ty where $primitive_ty: TypeEncoding + ComponentTypeEncoding {
+      type Type = <$primitive_ty as ComponentTypeEncoding>::Type;
+      const BYTES: Self::Type = <$primitive_ty as ComponentTypeEncoding>::BYTES;
+    }
     transparent_generic_type_encoding!{[where $($bounds)*] $($tt)*}
   };
 }
@@ -97,7 +101,10 @@ transparent_generic_type_encoding! {
 
   [where T: marker::Class + ?Sized]
   arc::Strong<T> => *mut T,
-  arc::Auto<T> => *mut T,
+  arc::WeakCell<T> => *mut T,
+
+  [where 'pool, T: marker::Class + ?Sized]
+  arc::Auto<'pool, T> => *mut T,
 }
 
 #[cfg(feature = "alloc")]
@@ -166,60 +173,193 @@ atomic_type_encoding! {
   // unsafe impl<T> TypeEncoding for core::sync::atomic::AtomicPtr<T> {}
 }
 
-macro_rules! special_pointer_type_encoding {
+// Objective-C only encodes `const` (i.e. `r`) for the outermost type. For example, `*const *const u8`
+// is encoded as "r^^C", not "r^r^C". ComponentTypeEncoding is the encoding a type has when it is a
+// component of another type (e.g. a pointee, array element, or struct field). It's the same as the
+// type's TypeEncoding, except for pointers and references (which drop the `r`).
+pub unsafe trait ComponentTypeEncoding {
+  type Type: Copy + core::cmp::PartialEq + core::fmt::Debug + Send + Sync;
+  const BYTES: Self::Type;
+}
+
+unsafe impl<T: TypeEncoding + ?Sized> ComponentTypeEncoding for T {
+  default type Type = <T as TypeEncoding>::Type;
+  default const BYTES: Self::Type =
+    unsafe { crate::__objrs::TransmuteHack { from: <T as TypeEncoding>::BYTES }.to };
+}
+
+// The encodings of pointers (and references) to Self. `Type` and `BYTES` are for `*mut Self`, and
+// `ConstType` and `CONST_BYTES` are for `*const Self`. Object pointers are encoded as "@", not
+// "^{Class=#}", so the #[objrs(class)] macro implements this trait for classes.
+pub unsafe trait PointeeTypeEncoding {
+  type Type: Copy + core::cmp::PartialEq + core::fmt::Debug + Send + Sync;
+  const BYTES: Self::Type;
+
+  type ConstType: Copy + core::cmp::PartialEq + core::fmt::Debug + Send + Sync;
+  const CONST_BYTES: Self::ConstType;
+}
+
+unsafe impl<T: ComponentTypeEncoding + ?Sized> PointeeTypeEncoding for T {
+  type Type = crate::__objrs::Packed2<u8, <T as ComponentTypeEncoding>::Type>;
+  const BYTES: Self::Type = crate::__objrs::Packed2(b'^', <T as ComponentTypeEncoding>::BYTES);
+
+  type ConstType = crate::__objrs::Packed3<u8, u8, <T as ComponentTypeEncoding>::Type>;
+  const CONST_BYTES: Self::ConstType =
+    crate::__objrs::Packed3(b'r', b'^', <T as ComponentTypeEncoding>::BYTES);
+}
+
+macro_rules! special_pointee_type_encoding {
   () => {};
-  ($ty:ty => $encoding:expr, $($tt:tt)*) => {
-    primitive_type_encoding!{
-      *const $ty => $encoding,
-      *mut $ty => $encoding,
-      &$ty => $encoding,
-      &mut $ty => $encoding,
+  ([$($generics:tt)*] $ty:ty => $encoding:expr, $($tt:tt)*) => {
+    unsafe impl<$($generics)*> PointeeTypeEncoding for $ty {
+      type Type = [u8; 1];
+      const BYTES: Self::Type = [$encoding];
+
+      type ConstType = [u8; 1];
+      const CONST_BYTES: Self::ConstType = [$encoding];
     }
 
-    special_pointer_type_encoding!{$($tt)*}
+    special_pointee_type_encoding!{$($tt)*}
   };
 }
 
-special_pointer_type_encoding! {
-  runtime::Sel => b':',
-  runtime::Id => b'@',
-  runtime::Class => b'#',
-  cstr::CStr => b'*',
+special_pointee_type_encoding! {
+  [] runtime::Sel => b':',
+  [T: ?Sized] runtime::Id<T> => b'@',
+  [] runtime::Class => b'#',
+  [] cstr::CStr => b'*',
 }
 
-// transparent_generic_type_encoding! {
-//   [where T: marker::Class + ?Sized]
-//   *const T => *const runtime::Id,
-//   *mut T => *mut runtime::Id,
-//   &T => &'static runtime::Id,
-//   &mut T => &'static mut runtime::Id,
-// }
-
-// TODO: The const should only apply to the outer-most pointer. e.g. *const *const _ should be "r^^", not "r^r^".
-// unsafe impl<T: TypeEncoding + ?Sized> TypeEncoding for *const T {
-//   type Type = [u8; 2 + core::mem::size_of::<<T as TypeEncoding>::Type>()];
-//   const BYTES: Self::Type = unsafe { core::mem::transmute(__objrs::Packed2([b'r', b'^'], <T as TypeEncoding>::BYTES)) };
-// }
-
-// unsafe impl<T: TypeEncoding + ?Sized> TypeEncoding for *mut T {
-//   type Type = [u8; 1 + core::mem::size_of::<<T as TypeEncoding>::Type>()];
-//   const BYTES: Self::Type = unsafe { core::mem::transmute(__objrs::Packed2(b'^', <T as TypeEncoding>::BYTES)) };
-// }
-
-// TODO: The const should only apply to the outer-most pointer. e.g. && _ should be "r^^", not "r^r^".
-// unsafe impl<'a, T: TypeEncoding + ?Sized> TypeEncoding for &'a T {
-//   type Type = [u8; 2 + core::mem::size_of::<<T as TypeEncoding>::Type>()];
-//   const BYTES: Self::Type = unsafe { core::mem::transmute(__objrs::Packed2([b'r', b'^'], <T as TypeEncoding>::BYTES)) };
-// }
-
-// unsafe impl<'a, T: TypeEncoding + ?Sized> TypeEncoding for &'a mut T {
-//   type Type = [u8; 1 + core::mem::size_of::<<T as TypeEncoding>::Type>()];
-//   const BYTES: Self::Type = unsafe { core::mem::transmute(__objrs::Packed2(b'^', <T as TypeEncoding>::BYTES)) };
-// }
-
-// unsafe impl<T: ?Sized> TypeEncoding for [T; 0] {}
-// unsafe impl<T: TypeEncoding + ?Sized> TypeEncoding for [T; 1] {}
-// ...
+macro_rules! pointer_type_encoding {
+  () => {};
+  ([$($generics:tt)*] $ty:ty => $encoding_ty:ident, $encoding:ident, $($tt:tt)*) => {
+    unsafe impl<$($generics)* T: PointeeTypeEncoding + ?Sized> TypeEncoding for $ty {
+      type Type = <T as PointeeTypeEncoding>::$encoding_ty;
+      const BYTES: Self::Type = <T as PointeeTypeEncoding>::$encoding;
+    }
+
+    unsafe impl<$($generics)* T: PointeeTypeEncoding + ?Sized> ComponentTypeEncoding for $ty {
+      type Type = <T as PointeeTypeEncoding>::Type;
+      const BYTES: Self::Type = <T as PointeeTypeEncoding>::BYTES;
+    }
+
+    pointer_type_encoding!{$($tt)*}
+  };
+}
+
+pointer_type_encoding! {
+  [] *const T => ConstType, CONST_BYTES,
+  [] *mut T => Type, BYTES,
+  ['a,] &'a T => ConstType, CONST_BYTES,
+  ['a,] &'a mut T => Type, BYTES,
+}
+
+// Arrays are encoded as "[" + length + element + "]" (e.g. `[u8; 16]` is "[16C]").
+macro_rules! array_type_encoding {
+  () => {};
+  ($len:expr, $($tt:tt)*) => {
+    unsafe impl<T: ComponentTypeEncoding> TypeEncoding for [T; $len] {
+      type Type = crate::__objrs::Packed3<
+        crate::__objrs::Packed2<u8, [u8; 1 + ($len >= 10) as usize + ($len >= 100) as usize + ($len >= 1000) as usize]>,
+        <T as ComponentTypeEncoding>::Type,
+        u8,
+      >;
+      const BYTES: Self::Type = {
+        struct Len;
+        impl crate::__objrs::ToAsciiHack for Len {
+          const VALUE: usize = $len;
+        }
+        let len = unsafe {
+          crate::__objrs::TransmuteHack { from: <Len as crate::__objrs::ToAsciiHack>::STR }.to
+        };
+        crate::__objrs::Packed3(
+          crate::__objrs::Packed2(b'[', len),
+          <T as ComponentTypeEncoding>::BYTES,
+          b']',
+        )
+      };
+    }
+
+    array_type_encoding!{$($tt)*}
+  };
+}
+
+array_type_encoding! {
+  0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+  17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32,
+  64, 128, 256, 512, 1024, 2048, 4096,
+}
+
+// The concatenated component encodings of a struct's (or union's) fields. This is implemented for
+// tuples of the field types, and is used by the `struct_type_encoding!` and `union_type_encoding!`
+// macros.
+#[doc(hidden)]
+pub unsafe trait FieldsTypeEncoding {
+  type Type: Copy + core::cmp::PartialEq + core::fmt::Debug + Send + Sync;
+  const BYTES: Self::Type;
+}
+
+macro_rules! fields_type_encoding {
+  () => {
+    unsafe impl FieldsTypeEncoding for () {
+      type Type = [u8; 0];
+      const BYTES: Self::Type = [];
+    }
+  };
+  ($first:ident $(, $rest:ident)*) => {
+    unsafe impl<$first: ComponentTypeEncoding, $($rest: ComponentTypeEncoding),*> FieldsTypeEncoding for ($first, $($rest,)*) {
+      type Type = crate::__objrs::Packed2<<$first as ComponentTypeEncoding>::Type, <($($rest,)*) as FieldsTypeEncoding>::Type>;
+      const BYTES: Self::Type = crate::__objrs::Packed2(<$first as ComponentTypeEncoding>::BYTES, <($($rest,)*) as FieldsTypeEncoding>::BYTES);
+    }
+
+    fields_type_encoding!{$($rest),*}
+  };
+}
+
+fields_type_encoding! { A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P }
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __objrs_aggregate_type_encoding {
+  ($open:expr, $close:expr, $name:ident, $($field:ty),*) => {
+    unsafe impl $crate::TypeEncoding for $name {
+      type Type = $crate::__objrs::Packed3<
+        $crate::__objrs::Packed3<$crate::__objrs::u8, [$crate::__objrs::u8; stringify!($name).len()], $crate::__objrs::u8>,
+        <($($field,)*) as $crate::FieldsTypeEncoding>::Type,
+        $crate::__objrs::u8,
+      >;
+      const BYTES: Self::Type = $crate::__objrs::Packed3(
+        $crate::__objrs::Packed3(
+          $open,
+          unsafe { *$crate::__objrs::TransmuteHack::<*const $crate::__objrs::u8, &[$crate::__objrs::u8; stringify!($name).len()]> { from: stringify!($name).as_ptr() }.to },
+          b'=',
+        ),
+        <($($field,)*) as $crate::FieldsTypeEncoding>::BYTES,
+        $close,
+      );
+    }
+  };
+}
+
+// Implements TypeEncoding for a C struct, given the types of its fields (in declaration order). For
+// example, `struct_type_encoding!(CGPoint { CGFloat, CGFloat });` encodes `CGPoint` as "{CGPoint=dd}".
+// The struct must be `#[repr(C)]`.
+#[macro_export]
+macro_rules! struct_type_encoding {
+  ($name:ident { $($field:ty),* $(,)? }) => {
+    $crate::__objrs_aggregate_type_encoding!(b'{', b'}', $name, $($field),*);
+  };
+}
+
+// Implements TypeEncoding for a C union, given the types of its fields (in declaration order). For
+// example, `union_type_encoding!(Value { i32, f32 });` encodes `Value` as "(Value=if)". The union
+// must be `#[repr(C)]`.
+#[macro_export]
+macro_rules! union_type_encoding {
+  ($name:ident { $($field:ty),* $(,)? }) => {
+    $crate::__objrs_aggregate_type_encoding!(b'(', b')', $name, $($field),*);
+  };
+}
 
 // Types that need to be made repr(transparent) first:
 // core::cmp::Reverse
@@ -227,6 +367,13 @@ special_pointer_type_encoding! {
 // core::cell::RefCell?
 // core::cell::RefMut?
 
+#[cfg(test)]
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+  return unsafe {
+    core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+  };
+}
+
 #[cfg(test)]
 #[test]
 fn type_encoding_test() {
@@ -269,51 +416,99 @@ fn type_encoding_test() {
   assert_eq!(<Option<core::num::NonZeroU128> as TypeEncoding>::BYTES, *b"T");
   // assert_eq!(<Option<core::num::NonZeroUsize> as TypeEncoding>::BYTES, *b"A");
 
-  // assert_eq!(<*mut u8 as TypeEncoding>::BYTES, *b"^C");
-  // assert_eq!(<*mut *mut u8 as TypeEncoding>::BYTES, *b"^^C");
-  // assert_eq!(<*const u8 as TypeEncoding>::BYTES, *b"r^C");
-  // assert_eq!(<*const *const u8 as TypeEncoding>::BYTES, *b"r^^C");
-
-  // assert_eq!(<&'static mut u8 as TypeEncoding>::BYTES, *b"^C");
-  // assert_eq!(<&'static mut &'static mut u8 as TypeEncoding>::BYTES, *b"^^C");
-  // assert_eq!(<&'static u8 as TypeEncoding>::BYTES, *b"r^C");
-  // assert_eq!(<&'static &'static u8 as TypeEncoding>::BYTES, *b"r^^C");
-
-  // core::ptr::NonNull<T>
-
-  // Option<&'a T>
-  // Option<&'a mut T>
-  // Option<core::ptr::NonNull<T>>
-
-  // assert_eq!(<core::cell::Cell<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::Cell<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::Cell<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::Cell<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::Cell<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
-
-  // assert_eq!(<core::cell::UnsafeCell<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::UnsafeCell<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::UnsafeCell<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::UnsafeCell<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::cell::UnsafeCell<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
+  assert_eq!(as_bytes(&<*mut u8 as TypeEncoding>::BYTES), b"^C");
+  assert_eq!(as_bytes(&<*mut *mut u8 as TypeEncoding>::BYTES), b"^^C");
+  assert_eq!(as_bytes(&<*const u8 as TypeEncoding>::BYTES), b"r^C");
+  assert_eq!(as_bytes(&<*const *const u8 as TypeEncoding>::BYTES), b"r^^C");
+
+  assert_eq!(as_bytes(&<&'static mut u8 as TypeEncoding>::BYTES), b"^C");
+  assert_eq!(as_bytes(&<&'static mut &'static mut u8 as TypeEncoding>::BYTES), b"^^C");
+  assert_eq!(as_bytes(&<&'static u8 as TypeEncoding>::BYTES), b"r^C");
+  assert_eq!(as_bytes(&<&'static &'static u8 as TypeEncoding>::BYTES), b"r^^C");
+
+  assert_eq!(as_bytes(&<core::ptr::NonNull<u8> as TypeEncoding>::BYTES), b"^C");
+
+  assert_eq!(as_bytes(&<Option<&'static u8> as TypeEncoding>::BYTES), b"r^C");
+  assert_eq!(as_bytes(&<Option<&'static mut u8> as TypeEncoding>::BYTES), b"^C");
+  assert_eq!(as_bytes(&<Option<core::ptr::NonNull<u8>> as TypeEncoding>::BYTES), b"^C");
+  assert_eq!(as_bytes(&<*const Option<&'static u8> as TypeEncoding>::BYTES), b"r^^C");
+
+  assert_eq!(<&'static runtime::Sel as TypeEncoding>::BYTES, *b":");
+  assert_eq!(<*mut runtime::Id as TypeEncoding>::BYTES, *b"@");
+  assert_eq!(<&'static runtime::Class as TypeEncoding>::BYTES, *b"#");
+  assert_eq!(<*const cstr::CStr as TypeEncoding>::BYTES, *b"*");
+  assert_eq!(as_bytes(&<*mut *mut runtime::Id as TypeEncoding>::BYTES), b"^@");
+  assert_eq!(as_bytes(&<*const &'static runtime::Sel as TypeEncoding>::BYTES), b"r^:");
+
+  assert_eq!(as_bytes(&<[u8; 0] as TypeEncoding>::BYTES), b"[0C]");
+  assert_eq!(as_bytes(&<[u8; 16] as TypeEncoding>::BYTES), b"[16C]");
+  assert_eq!(as_bytes(&<[[f32; 4]; 4] as TypeEncoding>::BYTES), b"[4[4f]]");
+  assert_eq!(as_bytes(&<[*const u8; 2] as TypeEncoding>::BYTES), b"[2^C]");
+  assert_eq!(as_bytes(&<[u8; 1024] as TypeEncoding>::BYTES), b"[1024C]");
+  assert_eq!(as_bytes(&<*const [u8; 3] as TypeEncoding>::BYTES), b"r^[3C]");
+
+  #[repr(C)]
+  struct Point {
+    x: f64,
+    y: f64,
+  }
+  struct_type_encoding!(Point { f64, f64 });
+  assert_eq!(as_bytes(&<Point as TypeEncoding>::BYTES), b"{Point=dd}");
+  assert_eq!(as_bytes(&<*const Point as TypeEncoding>::BYTES), b"r^{Point=dd}");
+
+  #[repr(C)]
+  struct Nested {
+    origin: Point,
+    name: *const u8,
+    bytes: [u8; 4],
+  }
+  struct_type_encoding!(Nested { Point, *const u8, [u8; 4] });
+  assert_eq!(as_bytes(&<Nested as TypeEncoding>::BYTES), b"{Nested={Point=dd}^C[4C]}");
+
+  #[repr(C)]
+  struct Empty {}
+  struct_type_encoding!(Empty {});
+  assert_eq!(as_bytes(&<Empty as TypeEncoding>::BYTES), b"{Empty=}");
+
+  #[repr(C)]
+  #[allow(dead_code)]
+  union Value {
+    i: i32,
+    f: f32,
+    p: *mut u8,
+  }
+  union_type_encoding!(Value { i32, f32, *mut u8 });
+  assert_eq!(as_bytes(&<Value as TypeEncoding>::BYTES), b"(Value=if^C)");
+
+  assert_eq!(<core::cell::Cell<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::Cell<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::Cell<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::Cell<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::Cell<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
+
+  assert_eq!(<core::cell::UnsafeCell<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::UnsafeCell<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::UnsafeCell<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::UnsafeCell<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
+  assert_eq!(<core::cell::UnsafeCell<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
 
   // core::sync::atomic::AtomicUsize => usize,
   // core::sync::atomic::AtomicIsize => isize,
   // core::sync::atomic::AtomicBool => bool,
   // core::sync::atomic::AtomicPtr<T>
 
-  // assert_eq!(<core::num::Wrapping<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<u8> as TypeEncoding>::BYTES, <u8 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<u16> as TypeEncoding>::BYTES, <u16 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<u32> as TypeEncoding>::BYTES, <u32 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<u64> as TypeEncoding>::BYTES, <u64 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<u128> as TypeEncoding>::BYTES, <u128 as TypeEncoding>::BYTES);
 
-  // assert_eq!(<core::num::Wrapping<i8> as TypeEncoding>::BYTES, <i8 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<i16> as TypeEncoding>::BYTES, <i16 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<i32> as TypeEncoding>::BYTES, <i32 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<i64> as TypeEncoding>::BYTES, <i64 as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<i128> as TypeEncoding>::BYTES, <i128 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<i8> as TypeEncoding>::BYTES, <i8 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<i16> as TypeEncoding>::BYTES, <i16 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<i32> as TypeEncoding>::BYTES, <i32 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<i64> as TypeEncoding>::BYTES, <i64 as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<i128> as TypeEncoding>::BYTES, <i128 as TypeEncoding>::BYTES);
 
-  // assert_eq!(<core::num::Wrapping<usize> as TypeEncoding>::BYTES, <usize as TypeEncoding>::BYTES);
-  // assert_eq!(<core::num::Wrapping<isize> as TypeEncoding>::BYTES, <isize as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<usize> as TypeEncoding>::BYTES, <usize as TypeEncoding>::BYTES);
+  assert_eq!(<core::num::Wrapping<isize> as TypeEncoding>::BYTES, <isize as TypeEncoding>::BYTES);
 }
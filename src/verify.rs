@@ -0,0 +1,242 @@
+// This file and its contents are licensed by their authors and copyright holders under the Apache
+// License (Version 2.0), MIT license, or Mozilla Public License (Version 2.0), at your option, and
+// may not be copied, modified, or distributed except according to those terms. For copies of these
+// licenses and more information, see the COPYRIGHT file in this distribution's top-level directory.
+
+// Runtime verification of extern selector declarations (enabled by the `verify-signatures` feature).
+//
+// When enabled, the msg_send function that #[objrs(selector)] generates for an extern class calls
+// `verify_method_type` before sending the message. The first time a call site sends its message to a
+// non-nil receiver, the method's type encoding is looked up in the runtime and compared against the
+// encodings of the Rust signature's types. A mismatch panics, since it almost certainly means the
+// extern declaration is wrong (and the message send would be undefined behavior).
+
+extern crate core;
+extern crate libc;
+
+use crate::encoding::{self, Encoding, Kind};
+use crate::runtime;
+use crate::{CStr, Id, Sel};
+use core::fmt;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+fn cstr_to_str(cstr: &CStr) -> &str {
+  return cstr.to_str().unwrap_or("<invalid UTF-8>");
+}
+
+fn is_integer(kind: Kind) -> bool {
+  match kind {
+    Kind::Char
+    | Kind::Short
+    | Kind::Int
+    | Kind::Long
+    | Kind::LongLong
+    | Kind::Int128
+    | Kind::UnsignedChar
+    | Kind::UnsignedShort
+    | Kind::UnsignedInt
+    | Kind::UnsignedLong
+    | Kind::UnsignedLongLong
+    | Kind::UnsignedInt128
+    | Kind::Bool => return true,
+    _ => return false,
+  }
+}
+
+// Returns true if a Rust type with the `expected` encoding may be used for a method parameter (or
+// return value) with the `actual` encoding. This is intentionally lenient about differences that
+// don't affect the ABI: qualifiers, object class names, integer signedness (and BOOL, which is a
+// `signed char` on some targets), and struct names if both structs have known fields. An expected
+// encoding of `?` (i.e., a Rust type that doesn't implement TypeEncoding) matches anything.
+fn compatible(expected: Encoding, actual: Encoding) -> bool {
+  match (expected.kind(), actual.kind()) {
+    (Kind::Unknown, _) => return true,
+    (Kind::Object(_), Kind::Object(_))
+    | (Kind::Object(_), Kind::Block)
+    | (Kind::Block, Kind::Object(_))
+    | (Kind::Block, Kind::Block) => return true,
+    (Kind::CString, Kind::Pointer(pointee)) | (Kind::Pointer(pointee), Kind::CString) => {
+      let pointee = pointee.get().kind();
+      return pointee == Kind::Char || pointee == Kind::UnsignedChar || pointee == Kind::Void;
+    }
+    (Kind::Pointer(expected), Kind::Pointer(actual)) => {
+      let (expected, actual) = (expected.get(), actual.get());
+      if expected.kind() == Kind::Void || actual.kind() == Kind::Void {
+        return true;
+      }
+      return compatible(expected, actual);
+    }
+    (Kind::Array(expected_count, expected), Kind::Array(actual_count, actual)) => {
+      return expected_count == actual_count && compatible(expected.get(), actual.get());
+    }
+    (Kind::Struct(expected_name, expected_fields), Kind::Struct(actual_name, actual_fields))
+    | (Kind::Union(expected_name, expected_fields), Kind::Union(actual_name, actual_fields)) => {
+      match (expected_fields, actual_fields) {
+        (Some(expected_fields), Some(actual_fields)) => {
+          let mut actual_fields = actual_fields;
+          for expected_field in expected_fields {
+            match actual_fields.next() {
+              Some(actual_field) if compatible(expected_field.encoding, actual_field.encoding) => {
+                continue;
+              }
+              _ => return false,
+            }
+          }
+          return actual_fields.next().is_none();
+        }
+        _ => return expected_name == actual_name,
+      }
+    }
+    (expected_kind, actual_kind) if is_integer(expected_kind) && is_integer(actual_kind) => {
+      return expected.size() == actual.size();
+    }
+    (expected_kind, actual_kind) => return expected_kind == actual_kind,
+  }
+}
+
+// Compares the expected types against the method's actual types (skipping the actual receiver and
+// selector, which are implicit in `expected`).
+fn types_match(expected: &[&[u8]], mut actual: encoding::MethodTypes) -> bool {
+  let mut expected = expected.iter().map(|bytes| {
+    return core::str::from_utf8(bytes).ok().and_then(|s| encoding::parse(s).ok());
+  });
+  let mut index = 0;
+  loop {
+    // The actual receiver and selector are at indices 1 and 2.
+    let actual_type = actual.next();
+    if index == 1 || index == 2 {
+      if actual_type.map_or(true, |r| r.is_err()) {
+        return false;
+      }
+      index += 1;
+      continue;
+    }
+    match (expected.next(), actual_type) {
+      (None, None) => return true,
+      (Some(Some(expected)), Some(Ok(actual))) if compatible(expected, actual) => index += 1,
+      _ => return false,
+    }
+  }
+}
+
+// Displays the expected types (which don't have frame offsets).
+struct ExpectedTypes<'a>(&'a [&'a [u8]]);
+
+impl<'a> fmt::Display for ExpectedTypes<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, bytes) in self.0.iter().enumerate() {
+      f.write_str(core::str::from_utf8(bytes).unwrap_or("?"))?;
+      // Insert the implicit receiver and selector after the return type.
+      if i == 0 {
+        f.write_str("@:")?;
+      }
+    }
+    return Ok(());
+  }
+}
+
+/// Verifies that the receiver's implementation of the selector has a type encoding compatible with
+/// `expected`, which holds the return type's encoding followed by each (explicit) argument's
+/// encoding. Panics if they're incompatible. Verification only happens once per `verified` flag
+/// (i.e. once per call site), and is skipped if the receiver is nil or doesn't have a method for
+/// the selector (since message forwarding may still handle it).
+#[cold]
+#[inline(never)]
+pub unsafe fn verify_method_type(
+  verified: &AtomicBool,
+  receiver: *mut Id,
+  sel: &Sel,
+  is_instance_method: bool,
+  expected: &[&[u8]],
+) {
+  if verified.load(Ordering::Relaxed) || receiver.is_null() {
+    return;
+  }
+
+  // For class methods, the receiver is a class, so this returns the metaclass (which holds the
+  // class methods).
+  let class = runtime::object_getClass(receiver);
+  let method = match NonNull::new(runtime::class_getInstanceMethod(class, sel)) {
+    Some(method) => method,
+    None => return,
+  };
+  let actual = match runtime::method_getTypeEncoding(method) {
+    Some(actual) => cstr_to_str(actual),
+    None => return,
+  };
+
+  if !types_match(expected, encoding::parse_method(actual)) {
+    let class_name = runtime::class_getName(class).map_or("<unknown>", cstr_to_str);
+    panic!(
+      "{}[{} {}] has type encoding `{}`, which doesn't match the Rust signature's `{}`",
+      if is_instance_method { "-" } else { "+" },
+      class_name,
+      cstr_to_str(runtime::sel_getName(sel)),
+      actual,
+      ExpectedTypes(expected),
+    );
+  }
+
+  verified.store(true, Ordering::Relaxed);
+}
+
+/// Verifies a dynamically sent message (see `Id::send`). Since the selector isn't fixed, every send
+/// is verified. `expected` is the same as for `verify_method_type`.
+#[inline(never)]
+pub unsafe fn verify_send(receiver: *const Id, sel: &Sel, expected: &[&[u8]]) {
+  let is_instance_method = !runtime::object_isClass(receiver);
+  let verified = AtomicBool::new(false);
+  verify_method_type(&verified, receiver as *mut Id, sel, is_instance_method, expected);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn check(expected: &str, actual: &str) -> bool {
+    return compatible(encoding::parse(expected).unwrap(), encoding::parse(actual).unwrap());
+  }
+
+  #[test]
+  fn compatible_encodings() {
+    assert!(check("?", "{CGRect={CGPoint=dd}{CGSize=dd}}"));
+    assert!(check("@", "@\"NSString\""));
+    assert!(check("@", "@?"));
+    assert!(check("B", "c"));
+    assert!(check("Q", "q"));
+    assert!(check("r*", "*"));
+    assert!(check("*", "r^c"));
+    assert!(check("^v", "^{__CFString=}"));
+    assert!(check("^{CGPoint=dd}", "^{CGPoint=dd}"));
+    assert!(check("{NSRect=dd}", "{CGPoint=dd}"));
+    assert!(check("{Opaque=}", "{Opaque}"));
+    assert!(check("[4f]", "[4f]"));
+    assert!(check("Vv", "v"));
+
+    assert!(!check("v", "@"));
+    assert!(!check("i", "q"));
+    assert!(!check("f", "d"));
+    assert!(!check("f", "i"));
+    assert!(!check("#", "@"));
+    assert!(!check(":", "*"));
+    assert!(!check("^i", "^d"));
+    assert!(!check("[4f]", "[3f]"));
+    assert!(!check("{CGPoint=dd}", "{CGPoint=ff}"));
+    assert!(!check("{CGPoint=dd}", "{CGPoint=ddd}"));
+    assert!(!check("{Opaque}", "{Other}"));
+    assert!(!check("(?=if)", "{?=if}"));
+  }
+
+  #[test]
+  fn method_types() {
+    let expected: [&[u8]; 2] = [b"v", b"d"];
+    assert!(types_match(&expected, encoding::parse_method("v24@0:8d16")));
+    assert!(types_match(&expected, encoding::parse_method("Vv24@0:8d16")));
+    assert!(!types_match(&expected, encoding::parse_method("v20@0:8f16")));
+    assert!(!types_match(&expected, encoding::parse_method("v16@0:8")));
+    assert!(!types_match(&expected, encoding::parse_method("v32@0:8d16d24")));
+    assert!(types_match(&expected[..1], encoding::parse_method("v16@0:8")));
+    assert!(!types_match(&expected[..1], encoding::parse_method("v")));
+  }
+}